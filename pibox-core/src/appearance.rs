@@ -0,0 +1,46 @@
+//! Light/dark appearance detection, shared by every client that needs to
+//! pick a palette: the TUI queries its terminal's background color, the
+//! GUI queries the OS -- both reduce to the same light-or-dark question,
+//! so the luminance math and the resulting enum live here once.
+
+/// Which broad appearance a client should render in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Appearance {
+    Light,
+    Dark,
+}
+
+/// Classify a background color as light or dark by perceived luminance
+/// (ITU-R BT.601 weights), the same formula most terminal/editor theme
+/// switchers use
+pub fn appearance_from_rgb(r: u8, g: u8, b: u8) -> Appearance {
+    let luminance = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    if luminance > 127.0 {
+        Appearance::Light
+    } else {
+        Appearance::Dark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_white_background_is_light() {
+        assert_eq!(appearance_from_rgb(255, 255, 255), Appearance::Light);
+    }
+
+    #[test]
+    fn test_black_background_is_dark() {
+        assert_eq!(appearance_from_rgb(0, 0, 0), Appearance::Dark);
+    }
+
+    #[test]
+    fn test_pure_green_leans_on_luminance_not_raw_average() {
+        // Pure green reads brighter than pure red/blue at the same value
+        // under BT.601 weights: (0, 255, 0) averages only 85 but has a
+        // luminance of ~150, so this should land on the light side
+        assert_eq!(appearance_from_rgb(0, 255, 0), Appearance::Light);
+    }
+}
@@ -8,11 +8,30 @@
 //! - No session storage needed on server
 //! - Tokens are self-contained and verifiable
 //! - Refresh flow allows long sessions without storing state
+//!
+//! Signing uses an Ed25519 keypair rather than a symmetric HMAC secret, so the
+//! private key can be persisted to disk and survive the restarts that are
+//! routine on power-cycled embedded devices, and the public key can be handed
+//! out to other replicas for verification-only deployments.
+//!
+//! Accounts may additionally enroll a TOTP second factor (see [`crate::totp`]),
+//! in which case `authenticate` requires a valid code alongside the password.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
+use async_trait::async_trait;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, EncodePrivateKey};
+use ed25519_dalek::SigningKey;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::totp;
+
 /// JWT claims embedded in tokens
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
@@ -36,6 +55,163 @@ pub enum TokenType {
     Refresh,
 }
 
+/// Credentials presented by a client when authenticating
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    /// Username/password pair, plus a TOTP code if the account has 2FA
+    /// enrolled (checked via [`AuthError::TwoFactorRequired`] when absent)
+    Password {
+        username: String,
+        password: String,
+        totp_code: Option<String>,
+    },
+    /// A pre-shared API token (for headless devices that can't do an
+    /// interactive login)
+    ApiToken { token: String },
+}
+
+/// An authenticated identity, independent of how it was verified
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub username: String,
+    pub device_id: Option<String>,
+}
+
+/// Pluggable credential verification and token issuance
+///
+/// `JwtAuth` is wired into `AppState` as `Box<dyn AuthProvider>` rather than
+/// a concrete type, so a deployment can swap in a provider that delegates to
+/// the Filebrowser backend's own auth, checks a fixed API token for headless
+/// devices, or anything else, without forking the WebSocket/HTTP handlers.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Verify credentials and resolve them to an identity
+    async fn authenticate(&self, credentials: Credentials) -> Result<Identity, AuthError>;
+
+    /// Mint a token pair for an already-authenticated identity
+    fn issue_tokens(&self, identity: &Identity) -> Result<TokenPair, AuthError>;
+
+    /// Verify an access token and return its claims
+    fn verify_access(&self, token: &str) -> Result<Claims, AuthError>;
+
+    /// Verify a refresh token and issue a fresh token pair
+    fn refresh(&self, refresh_token: &str) -> Result<TokenPair, AuthError>;
+
+    /// Enroll `username` in TOTP 2FA, returning an `otpauth://` URI an
+    /// authenticator app can scan or import
+    fn enroll_totp(&self, username: &str) -> String;
+
+    /// Remove `username`'s TOTP enrollment, disabling the second factor
+    fn remove_totp(&self, username: &str);
+}
+
+#[async_trait]
+impl AuthProvider for JwtAuth {
+    async fn authenticate(&self, credentials: Credentials) -> Result<Identity, AuthError> {
+        match credentials {
+            // TODO: validate against an actual user database; for now any
+            // non-empty password is accepted, matching the prior behavior.
+            Credentials::Password {
+                username,
+                password,
+                totp_code,
+            } => {
+                if username.is_empty() || password.is_empty() {
+                    return Err(AuthError::InvalidCredentials);
+                }
+
+                if let Some(secret) = self.totp_secret(&username) {
+                    match totp_code {
+                        None => return Err(AuthError::TwoFactorRequired),
+                        Some(code) if !totp::verify_code(&secret, &code) => {
+                            return Err(AuthError::InvalidTotpCode)
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                Ok(Identity {
+                    username,
+                    device_id: None,
+                })
+            }
+            Credentials::ApiToken { .. } => Err(AuthError::InvalidCredentials),
+        }
+    }
+
+    fn issue_tokens(&self, identity: &Identity) -> Result<TokenPair, AuthError> {
+        self.generate_tokens(&identity.username, identity.device_id.as_deref())
+    }
+
+    fn verify_access(&self, token: &str) -> Result<Claims, AuthError> {
+        self.verify_access_token(token)
+    }
+
+    fn refresh(&self, refresh_token: &str) -> Result<TokenPair, AuthError> {
+        self.refresh_tokens(refresh_token)
+    }
+
+    fn enroll_totp(&self, username: &str) -> String {
+        JwtAuth::enroll_totp(self, username)
+    }
+
+    fn remove_totp(&self, username: &str) {
+        JwtAuth::remove_totp(self, username)
+    }
+}
+
+/// Auth provider for headless devices: authenticates a fixed set of
+/// `device_name -> token` API tokens instead of a username/password prompt,
+/// then delegates token issuance/verification to an inner `JwtAuth`.
+pub struct ApiTokenProvider {
+    tokens: HashMap<String, String>,
+    inner: JwtAuth,
+}
+
+impl ApiTokenProvider {
+    pub fn new(inner: JwtAuth, tokens: HashMap<String, String>) -> Self {
+        Self { tokens, inner }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for ApiTokenProvider {
+    async fn authenticate(&self, credentials: Credentials) -> Result<Identity, AuthError> {
+        match credentials {
+            Credentials::ApiToken { token } => self
+                .tokens
+                .iter()
+                .find(|(_, v)| **v == token)
+                .map(|(name, _)| Identity {
+                    username: name.clone(),
+                    device_id: Some(name.clone()),
+                })
+                .ok_or(AuthError::InvalidCredentials),
+            Credentials::Password { .. } => Err(AuthError::InvalidCredentials),
+        }
+    }
+
+    fn issue_tokens(&self, identity: &Identity) -> Result<TokenPair, AuthError> {
+        self.inner.issue_tokens(identity)
+    }
+
+    fn verify_access(&self, token: &str) -> Result<Claims, AuthError> {
+        self.inner.verify_access(token)
+    }
+
+    fn refresh(&self, refresh_token: &str) -> Result<TokenPair, AuthError> {
+        self.inner.refresh(refresh_token)
+    }
+
+    fn enroll_totp(&self, username: &str) -> String {
+        self.inner.enroll_totp(username)
+    }
+
+    fn remove_totp(&self, username: &str) {
+        self.inner.remove_totp(username)
+    }
+}
+
 /// Pair of access and refresh tokens
 #[derive(Debug, Clone)]
 pub struct TokenPair {
@@ -48,8 +224,20 @@ pub struct TokenPair {
 pub struct JwtAuth {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    header: Header,
     access_token_ttl: u64,  // seconds
     refresh_token_ttl: u64, // seconds
+    /// Per-user base32 TOTP secrets for accounts with 2FA enrolled. A
+    /// `RwLock` rather than requiring `&mut self` since `JwtAuth` is shared
+    /// behind `Box<dyn AuthProvider>` and enrollment can happen concurrently
+    /// with logins.
+    totp_secrets: RwLock<HashMap<String, String>>,
+
+    /// Where `totp_secrets` is persisted so enrollments survive a restart.
+    /// `None` for handlers built via [`JwtAuth::new`]/[`JwtAuth::new_ed25519`]
+    /// directly (tests, and callers that don't need enrollment to survive a
+    /// restart) -- enrolling still works, it just doesn't outlive the process.
+    totp_secrets_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Error)]
@@ -65,10 +253,27 @@ pub enum AuthError {
 
     #[error("Invalid credentials")]
     InvalidCredentials,
+
+    #[error("TOTP code required")]
+    TwoFactorRequired,
+
+    #[error("Invalid TOTP code")]
+    InvalidTotpCode,
+
+    #[error("Failed to read or write JWT keypair: {0}")]
+    KeyIo(#[from] std::io::Error),
+
+    #[error("Invalid JWT keypair: {0}")]
+    KeyFormat(String),
 }
 
 impl JwtAuth {
-    /// Create new JWT auth handler
+    /// Create new JWT auth handler using a symmetric HMAC secret
+    ///
+    /// Kept for callers (and tests) that don't need keys to survive a
+    /// restart. Prefer [`JwtAuth::persisted`] for the server daemon, since an
+    /// HMAC secret generated fresh on every boot invalidates all outstanding
+    /// tokens.
     ///
     /// # Arguments
     /// * `secret` - HMAC secret for signing tokens (should be >= 32 bytes)
@@ -78,11 +283,57 @@ impl JwtAuth {
         Self {
             encoding_key: EncodingKey::from_secret(secret),
             decoding_key: DecodingKey::from_secret(secret),
+            header: Header::default(),
             access_token_ttl: access_token_ttl.unwrap_or(900),
             refresh_token_ttl: refresh_token_ttl.unwrap_or(604800),
+            totp_secrets: RwLock::new(HashMap::new()),
+            totp_secrets_path: None,
         }
     }
 
+    /// Create a JWT auth handler signing with an Ed25519 keypair (EdDSA)
+    pub fn new_ed25519(signing_key: &SigningKey, access_token_ttl: Option<u64>, refresh_token_ttl: Option<u64>) -> Result<Self, AuthError> {
+        let pkcs8_pem = signing_key
+            .to_pkcs8_pem(Default::default())
+            .map_err(|e| AuthError::KeyFormat(e.to_string()))?;
+
+        let encoding_key = EncodingKey::from_ed_pem(pkcs8_pem.as_bytes())?;
+        let decoding_key = DecodingKey::from_ed_pem(pkcs8_pem.as_bytes())?;
+
+        Ok(Self {
+            encoding_key,
+            decoding_key,
+            header: Header::new(jsonwebtoken::Algorithm::EdDSA),
+            access_token_ttl: access_token_ttl.unwrap_or(900),
+            refresh_token_ttl: refresh_token_ttl.unwrap_or(604800),
+            totp_secrets: RwLock::new(HashMap::new()),
+            totp_secrets_path: None,
+        })
+    }
+
+    /// Create a JWT auth handler backed by an Ed25519 keypair persisted at
+    /// `key_path` (typically `Config::config_dir()/jwt_key.pem`).
+    ///
+    /// If the file doesn't exist, a new keypair is generated and written with
+    /// `0600` permissions (Unix only); the public key is always derived from
+    /// the private key rather than stored separately. This keeps a device's
+    /// token-signing identity stable across restarts and lets other replicas
+    /// verify tokens by loading the same key (or just the derived public key).
+    ///
+    /// TOTP enrollments are persisted alongside the keypair, at `key_path`'s
+    /// sibling `totp_secrets.json` (also `0600`), so an enrolled account's
+    /// 2FA survives a restart the same way the signing key does.
+    pub fn persisted(key_path: &Path, access_token_ttl: Option<u64>, refresh_token_ttl: Option<u64>) -> Result<Self, AuthError> {
+        let signing_key = load_or_generate_keypair(key_path)?;
+        let mut auth = Self::new_ed25519(&signing_key, access_token_ttl, refresh_token_ttl)?;
+
+        let totp_path = key_path.with_file_name("totp_secrets.json");
+        auth.totp_secrets = RwLock::new(load_totp_secrets(&totp_path)?);
+        auth.totp_secrets_path = Some(totp_path);
+
+        Ok(auth)
+    }
+
     /// Generate a new token pair for a user
     pub fn generate_tokens(&self, username: &str, device_id: Option<&str>) -> Result<TokenPair, AuthError> {
         let now = std::time::SystemTime::now()
@@ -106,8 +357,8 @@ impl JwtAuth {
             device_id: device_id.map(String::from),
         };
 
-        let access_token = encode(&Header::default(), &access_claims, &self.encoding_key)?;
-        let refresh_token = encode(&Header::default(), &refresh_claims, &self.encoding_key)?;
+        let access_token = encode(&self.header, &access_claims, &self.encoding_key)?;
+        let refresh_token = encode(&self.header, &refresh_claims, &self.encoding_key)?;
 
         Ok(TokenPair {
             access_token,
@@ -146,7 +397,7 @@ impl JwtAuth {
 
     /// Decode and validate a token
     fn decode_token(&self, token: &str) -> Result<Claims, AuthError> {
-        let validation = Validation::default();
+        let validation = Validation::new(self.header.alg);
         let token_data = decode::<Claims>(token, &self.decoding_key, &validation)?;
 
         let now = std::time::SystemTime::now()
@@ -170,6 +421,43 @@ impl JwtAuth {
     pub fn refresh_token_ttl(&self) -> u64 {
         self.refresh_token_ttl
     }
+
+    /// Look up a user's enrolled TOTP secret, if any
+    fn totp_secret(&self, username: &str) -> Option<String> {
+        self.totp_secrets.read().unwrap().get(username).cloned()
+    }
+
+    /// Enroll `username` in TOTP 2FA, generating a new secret and returning
+    /// an `otpauth://` URI an authenticator app can scan or import. Persisted
+    /// to `totp_secrets_path` immediately, if set, so the enrollment isn't
+    /// lost on the next restart.
+    pub fn enroll_totp(&self, username: &str) -> String {
+        let secret = totp::generate_secret();
+        let uri = totp::provisioning_uri("pibox", username, &secret);
+        let mut secrets = self.totp_secrets.write().unwrap();
+        secrets.insert(username.to_string(), secret);
+        self.persist_totp_secrets(&secrets);
+        uri
+    }
+
+    /// Remove a user's TOTP enrollment, disabling the second factor
+    pub fn remove_totp(&self, username: &str) {
+        let mut secrets = self.totp_secrets.write().unwrap();
+        secrets.remove(username);
+        self.persist_totp_secrets(&secrets);
+    }
+
+    /// Write `secrets` to `totp_secrets_path`, if one is configured. Failures
+    /// are logged rather than propagated -- enrollment has already succeeded
+    /// in memory by the time this is called, and refusing the caller a
+    /// working 2FA setup over a disk write failure would be worse than an
+    /// enrollment that doesn't survive the next restart.
+    fn persist_totp_secrets(&self, secrets: &HashMap<String, String>) {
+        let Some(path) = &self.totp_secrets_path else { return };
+        if let Err(e) = save_totp_secrets(path, secrets) {
+            tracing::warn!("Failed to persist TOTP secrets to {}: {}", path.display(), e);
+        }
+    }
 }
 
 /// Generate a secure random secret for JWT signing
@@ -178,6 +466,101 @@ pub fn generate_secret() -> [u8; 32] {
     rand::thread_rng().r#gen()
 }
 
+/// Load the Ed25519 keypair at `key_path`, generating and persisting a new
+/// one if the file doesn't exist yet.
+///
+/// The private key is written as a PKCS#8 PEM with `0600` permissions on
+/// Unix; the public key is never stored separately and is always re-derived
+/// from the private key on load.
+fn load_or_generate_keypair(key_path: &Path) -> Result<SigningKey, AuthError> {
+    if key_path.exists() {
+        let pem = std::fs::read_to_string(key_path)?;
+        return SigningKey::from_pkcs8_pem(&pem).map_err(|e| AuthError::KeyFormat(e.to_string()));
+    }
+
+    let signing_key = SigningKey::generate(&mut OsRng);
+    write_keypair(key_path, &signing_key)?;
+    Ok(signing_key)
+}
+
+fn write_keypair(key_path: &Path, signing_key: &SigningKey) -> Result<(), AuthError> {
+    if let Some(parent) = key_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let pem = signing_key
+        .to_pkcs8_pem(Default::default())
+        .map_err(|e| AuthError::KeyFormat(e.to_string()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(key_path)?;
+        file.write_all(pem.as_bytes())?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::fs::write(key_path, pem.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Load persisted TOTP secrets from `path`, if it exists. A missing file
+/// means no accounts have enrolled yet, not an error.
+fn load_totp_secrets(path: &Path) -> Result<HashMap<String, String>, AuthError> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let json = std::fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(|e| AuthError::KeyFormat(e.to_string()))
+}
+
+/// Write `secrets` to `path` as JSON with `0600` permissions (Unix only),
+/// the same treatment the JWT signing key gets in [`write_keypair`].
+fn save_totp_secrets(path: &Path, secrets: &HashMap<String, String>) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let json = serde_json::to_string(secrets)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(json.as_bytes())?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, json.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Path to the persisted JWT signing key, alongside the rest of pibox's
+/// config directory.
+pub fn default_key_path() -> Result<PathBuf, std::io::Error> {
+    let dir = dirs::config_dir()
+        .map(|p| p.join("pibox"))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "config directory not found"))?;
+    Ok(dir.join("jwt_key.pem"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -226,4 +609,94 @@ mod tests {
         let result = auth.verify_access_token(&tokens.refresh_token);
         assert!(matches!(result, Err(AuthError::InvalidTokenType { .. })));
     }
+
+    #[test]
+    fn test_ed25519_signing() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let auth = JwtAuth::new_ed25519(&signing_key, Some(60), Some(3600)).unwrap();
+
+        let tokens = auth.generate_tokens("testuser", None).unwrap();
+        let claims = auth.verify_access_token(&tokens.access_token).unwrap();
+        assert_eq!(claims.sub, "testuser");
+    }
+
+    #[test]
+    fn test_persisted_keypair_survives_reload() {
+        let dir = std::env::temp_dir().join(format!("pibox-auth-test-{:?}", std::thread::current().id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_path = dir.join("jwt_key.pem");
+
+        let auth1 = JwtAuth::persisted(&key_path, Some(60), Some(3600)).unwrap();
+        let tokens = auth1.generate_tokens("testuser", None).unwrap();
+
+        // A second handler loading the same path should verify tokens signed by the first
+        let auth2 = JwtAuth::persisted(&key_path, Some(60), Some(3600)).unwrap();
+        let claims = auth2.verify_access_token(&tokens.access_token).unwrap();
+        assert_eq!(claims.sub, "testuser");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_persisted_totp_enrollment_survives_reload() {
+        let dir = std::env::temp_dir().join(format!("pibox-auth-totp-test-{:?}", std::thread::current().id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_path = dir.join("jwt_key.pem");
+
+        let auth1 = JwtAuth::persisted(&key_path, Some(60), Some(3600)).unwrap();
+        auth1.enroll_totp("alice");
+
+        // A second handler loading the same directory should see alice's enrollment
+        let auth2 = JwtAuth::persisted(&key_path, Some(60), Some(3600)).unwrap();
+        let result = futures::executor::block_on(auth2.authenticate(Credentials::Password {
+            username: "alice".to_string(),
+            password: "hunter2".to_string(),
+            totp_code: None,
+        }));
+        assert!(matches!(result, Err(AuthError::TwoFactorRequired)));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_totp_enrollment_requires_code_on_login() {
+        let secret = generate_secret();
+        let auth = JwtAuth::new(&secret, Some(60), Some(3600));
+        auth.enroll_totp("alice");
+
+        let result = auth.authenticate(Credentials::Password {
+            username: "alice".to_string(),
+            password: "hunter2".to_string(),
+            totp_code: None,
+        });
+        let result = futures::executor::block_on(result);
+        assert!(matches!(result, Err(AuthError::TwoFactorRequired)));
+    }
+
+    #[test]
+    fn test_totp_wrong_code_rejected() {
+        let secret = generate_secret();
+        let auth = JwtAuth::new(&secret, Some(60), Some(3600));
+        auth.enroll_totp("alice");
+
+        let result = futures::executor::block_on(auth.authenticate(Credentials::Password {
+            username: "alice".to_string(),
+            password: "hunter2".to_string(),
+            totp_code: Some("000000".to_string()),
+        }));
+        assert!(matches!(result, Err(AuthError::InvalidTotpCode)));
+    }
+
+    #[test]
+    fn test_account_without_totp_skips_2fa() {
+        let secret = generate_secret();
+        let auth = JwtAuth::new(&secret, Some(60), Some(3600));
+
+        let result = futures::executor::block_on(auth.authenticate(Credentials::Password {
+            username: "bob".to_string(),
+            password: "hunter2".to_string(),
+            totp_code: None,
+        }));
+        assert!(result.is_ok());
+    }
 }
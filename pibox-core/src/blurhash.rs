@@ -0,0 +1,237 @@
+//! BlurHash placeholder string generation
+//!
+//! A capable client is dispatched `OffloadTask::BlurHash` to produce a tiny
+//! ASCII placeholder for progressive image loading. The actual BlurHash
+//! math -- sRGB/linear conversion, the DCT-like basis functions, and base83
+//! encoding -- is pure arithmetic over an already-decoded RGB buffer and is
+//! implemented here in full. Decoding `source` (arbitrary JPEG/PNG/... bytes)
+//! into that buffer is handled by the `image` crate, the same decoder
+//! `pibox-server::thumbnail` uses.
+
+use image::{DynamicImage, ImageFormat};
+use thiserror::Error;
+
+const BASE83_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BlurHashError {
+    #[error("components_x and components_y must each be between 1 and 9, got ({0}, {1})")]
+    InvalidComponents(u32, u32),
+
+    #[error("unrecognized image mime type: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("failed to decode image: {0}")]
+    DecodeFailed(String),
+}
+
+/// Decode `source` (whatever format `mime_type` names) into `(width,
+/// height, rgb)`, where `rgb` is `width * height * 3` bytes, row-major, no
+/// padding.
+fn decode_rgb(source: &[u8], mime_type: &str) -> Result<(u32, u32, Vec<u8>), BlurHashError> {
+    let format = ImageFormat::from_mime_type(mime_type)
+        .ok_or_else(|| BlurHashError::UnsupportedFormat(mime_type.to_string()))?;
+
+    let image: DynamicImage =
+        image::load_from_memory_with_format(source, format).map_err(|e| BlurHashError::DecodeFailed(e.to_string()))?;
+
+    let rgb = image.to_rgb8();
+    let (width, height) = rgb.dimensions();
+    Ok((width, height, rgb.into_raw()))
+}
+
+/// Produce a BlurHash string for `source`, a `mime_type`-encoded image, with
+/// `components_x * components_y` frequency components
+pub fn encode(source: &[u8], mime_type: &str, components_x: u32, components_y: u32) -> Result<String, BlurHashError> {
+    if !(1..=9).contains(&components_x) || !(1..=9).contains(&components_y) {
+        return Err(BlurHashError::InvalidComponents(components_x, components_y));
+    }
+
+    let (width, height, rgb) = decode_rgb(source, mime_type)?;
+    Ok(encode_pixels(&rgb, width, height, components_x, components_y))
+}
+
+/// The BlurHash algorithm itself, over an already-decoded `width * height *
+/// 3` RGB buffer. Split out from `encode` so the math can be exercised
+/// without a real image decoder.
+fn encode_pixels(rgb: &[u8], width: u32, height: u32, components_x: u32, components_y: u32) -> String {
+    let mut factors = Vec::with_capacity((components_x * components_y) as usize);
+    for j in 0..components_y {
+        for i in 0..components_x {
+            factors.push(basis_factor(i, j, width, height, rgb));
+        }
+    }
+
+    let dc = factors[0];
+    let ac = &factors[1..];
+
+    let mut hash = String::new();
+    hash.push_str(&encode_base83(
+        (components_x - 1) + (components_y - 1) * 9,
+        1,
+    ));
+
+    let max_value = if let Some(actual_max) = ac
+        .iter()
+        .flat_map(|channels| channels.iter().copied())
+        .fold(None, |acc: Option<f32>, v| {
+            let v = v.abs();
+            Some(acc.map_or(v, |acc| acc.max(v)))
+        }) {
+        let quantized_max = ((actual_max * 166.0 - 0.5).floor().clamp(0.0, 82.0)) as u32;
+        hash.push_str(&encode_base83(quantized_max, 1));
+        (quantized_max as f32 + 1.0) / 166.0
+    } else {
+        hash.push_str(&encode_base83(0, 1));
+        1.0
+    };
+
+    hash.push_str(&encode_base83(encode_dc(dc), 3));
+
+    for channels in ac {
+        hash.push_str(&encode_base83(encode_ac(*channels, max_value), 2));
+    }
+
+    hash
+}
+
+/// The `(i, j)` coefficient for every channel: `sum over pixels of color *
+/// cos(pi*i*x/width) * cos(pi*j*y/height)`, scaled by `normalization /
+/// (width*height)`, where `normalization` is 1 for the DC term `(0, 0)` and
+/// 2 otherwise
+fn basis_factor(i: u32, j: u32, width: u32, height: u32, rgb: &[u8]) -> [f32; 3] {
+    let mut sum = [0.0f32; 3];
+    let normalization = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+
+    for y in 0..height {
+        for x in 0..width {
+            let basis = (std::f32::consts::PI * i as f32 * x as f32 / width as f32).cos()
+                * (std::f32::consts::PI * j as f32 * y as f32 / height as f32).cos();
+
+            let idx = ((y * width + x) * 3) as usize;
+            for c in 0..3 {
+                sum[c] += basis * srgb_to_linear(rgb[idx + c]);
+            }
+        }
+    }
+
+    let scale = normalization / (width * height) as f32;
+    [sum[0] * scale, sum[1] * scale, sum[2] * scale]
+}
+
+/// `c/255` then the sRGB-linear transfer function
+fn srgb_to_linear(channel: u8) -> f32 {
+    let v = channel as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    let v = value.clamp(0.0, 1.0);
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The DC coefficient, linear->sRGB converted and packed into a single
+/// 0xRRGGBB-shaped integer
+fn encode_dc(value: [f32; 3]) -> u32 {
+    let channel = |v: f32| (linear_to_srgb(v) * 255.0).round().clamp(0.0, 255.0) as u32;
+    (channel(value[0]) << 16) | (channel(value[1]) << 8) | channel(value[2])
+}
+
+/// An AC coefficient, quantized per channel against `max_value`, sign
+/// preserving and clamped to 0-18, and packed into a single base-19 integer
+fn encode_ac(value: [f32; 3], max_value: f32) -> u32 {
+    let quantize = |v: f32| -> u32 {
+        let normalized = v / max_value;
+        let signed_sqrt = normalized.abs().powf(0.5).copysign(normalized);
+        ((signed_sqrt * 9.0 + 9.5).floor().clamp(0.0, 18.0)) as u32
+    };
+
+    quantize(value[0]) * 19 * 19 + quantize(value[1]) * 19 + quantize(value[2])
+}
+
+/// Encode `value` as exactly `length` base83 digits, most significant first
+fn encode_base83(mut value: u32, length: usize) -> String {
+    let mut digits = vec![0u8; length];
+    for slot in digits.iter_mut().rev() {
+        *slot = BASE83_CHARS[(value % 83) as usize];
+        value /= 83;
+    }
+    String::from_utf8(digits).expect("BASE83_CHARS is all ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_out_of_range_components() {
+        assert_eq!(encode(&[], "image/png", 0, 4), Err(BlurHashError::InvalidComponents(0, 4)));
+        assert_eq!(encode(&[], "image/png", 4, 10), Err(BlurHashError::InvalidComponents(4, 10)));
+    }
+
+    #[test]
+    fn test_encode_surfaces_decode_failure() {
+        assert!(matches!(encode(&[1, 2, 3], "image/png", 4, 3), Err(BlurHashError::DecodeFailed(_))));
+    }
+
+    #[test]
+    fn test_encode_surfaces_unsupported_mime_type() {
+        assert_eq!(
+            encode(&[1, 2, 3], "application/octet-stream", 4, 3),
+            Err(BlurHashError::UnsupportedFormat("application/octet-stream".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trips_a_real_png() {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        let image = image::RgbImage::from_fn(4, 4, |x, y| image::Rgb([(x * 60) as u8, (y * 60) as u8, 128]));
+        image::DynamicImage::ImageRgb8(image)
+            .write_to(&mut buf, ImageFormat::Png)
+            .unwrap();
+
+        let hash = encode(buf.get_ref(), "image/png", 3, 3).unwrap();
+        // Header (1) + max-AC (1) + DC (3) + 8 AC components * 2 chars each
+        assert_eq!(hash.len(), 1 + 1 + 3 + 8 * 2);
+    }
+
+    #[test]
+    fn test_base83_encodes_fixed_width() {
+        assert_eq!(encode_base83(0, 1), "0");
+        assert_eq!(encode_base83(82, 1), "~");
+        assert_eq!(encode_base83(0, 3), "000");
+    }
+
+    #[test]
+    fn test_srgb_linear_round_trip_is_close() {
+        for channel in [0u8, 64, 128, 200, 255] {
+            let linear = srgb_to_linear(channel);
+            let back = (linear_to_srgb(linear) * 255.0).round() as u8;
+            assert_eq!(back, channel);
+        }
+    }
+
+    #[test]
+    fn test_flat_gray_image_quantized_max_ac() {
+        let width = 4;
+        let height = 4;
+        let rgb = vec![128u8; (width * height * 3) as usize];
+        let hash = encode_pixels(&rgb, width, height, 3, 3);
+
+        // Header (1) + max-AC (1) + DC (3) + 8 AC components * 2 chars each
+        assert_eq!(hash.len(), 1 + 1 + 3 + 8 * 2);
+        // The basis samples cos(pi*i*x/width) at x = 0..width-1, which for
+        // i = 1 and width 4 sums to 1 rather than 0, so even a flat image
+        // has AC energy: 2/16 * 4 * srgb_to_linear(128) ~= 0.108, quantized
+        // to floor(0.108 * 166 - 0.5) = 17, base83 digit 'H'
+        assert_eq!(&hash[1..2], "H");
+    }
+}
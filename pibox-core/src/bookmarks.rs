@@ -0,0 +1,138 @@
+//! Persistent single-key directory bookmarks (`bookmarks.toml`)
+//!
+//! Mirrors hunter's `bookmarks.rs`: a flat single-char-key to path map, so
+//! jumping back to a frequently-visited directory deep in a Pi's
+//! filesystem is one key instead of several navigation steps. Shared by
+//! every client (TUI, GUI) the same way `Config` is.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BookmarksError {
+    #[error("Failed to read bookmarks: {0}")]
+    ReadError(#[from] std::io::Error),
+
+    #[error("Failed to parse bookmarks: {0}")]
+    ParseError(#[from] toml::de::Error),
+
+    #[error("Failed to serialize bookmarks: {0}")]
+    SerializeError(#[from] toml::ser::Error),
+
+    #[error("Config directory not found")]
+    NoDirFound,
+}
+
+/// Single-char-key to path map, loaded from and saved to `bookmarks.toml`
+/// alongside `config.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Bookmarks {
+    #[serde(default)]
+    paths: HashMap<char, String>,
+}
+
+impl Bookmarks {
+    pub fn get(&self, key: char) -> Option<&str> {
+        self.paths.get(&key).map(String::as_str)
+    }
+
+    /// Bookmark `path` under `key`, overwriting whatever was there before
+    pub fn set(&mut self, key: char, path: String) {
+        self.paths.insert(key, path);
+    }
+
+    /// All bookmarks, sorted by key, for rendering the quick-jump overlay
+    pub fn entries(&self) -> Vec<(char, &str)> {
+        let mut entries: Vec<(char, &str)> = self.paths.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        entries.sort_by_key(|(key, _)| *key);
+        entries
+    }
+
+    /// Load from `~/.config/pibox/bookmarks.toml`, falling back to an
+    /// empty set if the file is absent or fails to parse
+    pub fn load() -> Self {
+        match Self::default_path().and_then(|path| {
+            if path.exists() {
+                Self::load_from(&path)
+            } else {
+                Ok(Self::default())
+            }
+        }) {
+            Ok(bookmarks) => bookmarks,
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, BookmarksError> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Save to `~/.config/pibox/bookmarks.toml`, creating the config
+    /// directory if it doesn't exist yet
+    pub fn save(&self) -> Result<(), BookmarksError> {
+        self.save_to(&Self::default_path()?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), BookmarksError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    fn default_path() -> Result<PathBuf, BookmarksError> {
+        let dir = crate::Config::config_dir().map_err(|_| BookmarksError::NoDirFound)?;
+        Ok(dir.join("bookmarks.toml"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_then_get_round_trips() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set('a', "/home/pi/Documents".to_string());
+        assert_eq!(bookmarks.get('a'), Some("/home/pi/Documents"));
+        assert_eq!(bookmarks.get('z'), None);
+    }
+
+    #[test]
+    fn test_set_overwrites_existing_key() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set('a', "/one".to_string());
+        bookmarks.set('a', "/two".to_string());
+        assert_eq!(bookmarks.get('a'), Some("/two"));
+    }
+
+    #[test]
+    fn test_entries_are_sorted_by_key() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set('z', "/z".to_string());
+        bookmarks.set('a', "/a".to_string());
+        assert_eq!(bookmarks.entries(), vec![('a', "/a"), ('z', "/z")]);
+    }
+
+    #[test]
+    fn test_save_then_load_round_trips_through_toml() {
+        let dir = std::env::temp_dir().join(format!("pibox-bookmarks-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bookmarks.toml");
+
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set('d', "/home/pi/Downloads".to_string());
+        bookmarks.save_to(&path).unwrap();
+
+        let loaded = Bookmarks::load_from(&path).unwrap();
+        assert_eq!(loaded, bookmarks);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
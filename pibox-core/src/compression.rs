@@ -0,0 +1,77 @@
+//! Compression for individual transfer payloads
+//!
+//! Base64-over-JSON already inflates binary payloads by about a third; on
+//! top of that, `pibox-server::compress` only compresses whole WebSocket
+//! frames, which doesn't help a single large `FileContent`/`Upload`/
+//! `OffloadResult` message sent on its own. This compresses just that
+//! message's payload bytes, with the codec used travelling alongside it via
+//! `TransferEncoding` so the receiver knows how to reverse it.
+
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use thiserror::Error;
+
+use crate::protocol::TransferEncoding;
+
+#[derive(Debug, Error)]
+pub enum CompressionError {
+    #[error("failed to decompress payload: {0}")]
+    Decompress(#[from] std::io::Error),
+}
+
+/// Compress `data` for `encoding` at `level` (1-9; ignored for codecs that
+/// don't take one). `Zstd` has no crate available in this tree (see
+/// `TransferEncoding::Zstd`) and passes `data` through unchanged.
+pub fn compress(data: &[u8], encoding: TransferEncoding, level: u8) -> Vec<u8> {
+    match encoding {
+        TransferEncoding::Identity | TransferEncoding::Zstd => data.to_vec(),
+        TransferEncoding::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level.clamp(1, 9) as u32));
+            encoder.write_all(data).expect("writing to an in-memory encoder cannot fail");
+            encoder.finish().expect("finishing an in-memory encoder cannot fail")
+        }
+    }
+}
+
+/// Reverse of `compress`
+pub fn decompress(data: &[u8], encoding: TransferEncoding) -> Result<Vec<u8>, CompressionError> {
+    match encoding {
+        TransferEncoding::Identity | TransferEncoding::Zstd => Ok(data.to_vec()),
+        TransferEncoding::Deflate => {
+            let mut decoder = DeflateDecoder::new(data);
+            let mut out = Vec::new();
+            decoder.read_to_end(&mut out)?;
+            Ok(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deflate_round_trips() {
+        let data = b"the quick brown fox jumps over the lazy dog".repeat(20);
+        let compressed = compress(&data, TransferEncoding::Deflate, 6);
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed, TransferEncoding::Deflate).unwrap(), data);
+    }
+
+    #[test]
+    fn test_identity_is_a_no_op() {
+        let data = vec![1, 2, 3, 4];
+        assert_eq!(compress(&data, TransferEncoding::Identity, 6), data);
+        assert_eq!(decompress(&data, TransferEncoding::Identity).unwrap(), data);
+    }
+
+    #[test]
+    fn test_zstd_passes_through_until_implemented() {
+        let data = vec![5, 6, 7];
+        assert_eq!(compress(&data, TransferEncoding::Zstd, 6), data);
+        assert_eq!(decompress(&data, TransferEncoding::Zstd).unwrap(), data);
+    }
+}
@@ -6,6 +6,7 @@
 //! - Windows: %APPDATA%\pibox\
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -22,11 +23,374 @@ pub enum ConfigError {
 
     #[error("Config directory not found")]
     NoDirFound,
+
+    #[error("Invalid pairing data: {0}")]
+    PairingError(String),
+
+    #[error("{0}")]
+    ValidationError(String),
+}
+
+/// The current `Config` schema version. Bump this and add a transform to
+/// [`MIGRATIONS`] (indexed by the version it migrates *from*) whenever a
+/// field is renamed or removed in a way `#[serde(default)]` alone can't
+/// paper over.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Ordered chain of per-version transforms, one entry per version to
+/// migrate away from: `MIGRATIONS[0]` takes a v0 document to v1,
+/// `MIGRATIONS[1]` would take v1 to v2, and so on. Applied left to right
+/// starting at the document's own `schema_version`, so a file several
+/// versions behind is brought forward one step at a time rather than
+/// requiring every old version to be migrated directly to the latest.
+const MIGRATIONS: &[fn(toml::Value) -> toml::Value] = &[
+    // v0 -> v1: `schema_version` becomes an explicit field rather than an
+    // implicit "absent means oldest" convention. No structural changes yet.
+    |v| v,
+];
+
+/// Apply every migration from `from` up to [`CURRENT_SCHEMA_VERSION`] and
+/// stamp the result with the current version.
+fn migrate(mut raw: toml::Value, from: u32) -> toml::Value {
+    for step in MIGRATIONS.iter().skip(from as usize) {
+        raw = step(raw);
+    }
+
+    if let toml::Value::Table(ref mut table) = raw {
+        table.insert(
+            "schema_version".to_string(),
+            toml::Value::Integer(CURRENT_SCHEMA_VERSION as i64),
+        );
+    }
+
+    raw
+}
+
+/// `#[serde(deny_unknown_fields)]` mirrors of the real config structs, used
+/// only by [`Config::load_strict`] to catch typos that the regular
+/// (permissive) structs above silently ignore. Every known field is an
+/// `Option<toml::Value>` -- these types check field *names* only (types are
+/// re-checked for real against the actual `Config` right after), so there's
+/// exactly one list of valid names per section, expressed the same way the
+/// real structs are, rather than a second hand-maintained array that has to
+/// be kept in sync by hand.
+mod strict {
+    // These fields exist only so `#[serde(deny_unknown_fields)]` has
+    // somewhere to route recognized keys to -- `load_strict` only inspects
+    // whether deserialization succeeds, never the fields themselves.
+    #![allow(dead_code)]
+
+    use serde::Deserialize;
+    use toml::Value;
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct Config {
+        pub schema_version: Option<Value>,
+        pub server: Option<ServerConfig>,
+        pub client: Option<ClientConfig>,
+        pub devices: Option<Vec<DeviceConfig>>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct ServerConfig {
+        pub listen_addr: Option<Value>,
+        pub ws_port: Option<Value>,
+        pub filebrowser_url: Option<Value>,
+        pub filebrowser_username: Option<Value>,
+        pub filebrowser_password: Option<Value>,
+        pub jwt_secret: Option<Value>,
+        pub access_token_ttl: Option<Value>,
+        pub refresh_token_ttl: Option<Value>,
+        pub max_concurrent_transfers: Option<Value>,
+        pub load_report_interval: Option<Value>,
+        pub tls_enabled: Option<Value>,
+        pub tls_cert_path: Option<Value>,
+        pub tls_key_path: Option<Value>,
+        pub watch_root: Option<Value>,
+        pub metrics_pushgateway_url: Option<Value>,
+        pub metrics_push_interval: Option<Value>,
+        pub upload_idle_timeout: Option<Value>,
+        pub cache_dir: Option<Value>,
+        pub cache_capacity_mb: Option<Value>,
+        pub network: Option<NetworkConfig>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct NetworkConfig {
+        pub tailscale: Option<Value>,
+        pub wifi: Option<Vec<WifiNetwork>>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct WifiNetwork {
+        pub ssid: Option<Value>,
+        pub security: Option<Value>,
+        pub password: Option<Value>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct ClientConfig {
+        pub default_device: Option<Value>,
+        pub theme: Option<Value>,
+        pub show_hidden: Option<Value>,
+        pub confirm_delete: Option<Value>,
+        pub vim_mode: Option<Value>,
+        pub tui: Option<TuiConfig>,
+        pub gui: Option<GuiConfig>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct TuiConfig {
+        pub true_color: Option<Value>,
+        pub mouse: Option<Value>,
+        pub image_preview: Option<Value>,
+        pub preview_ratios: Option<Value>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct GuiConfig {
+        pub window_width: Option<Value>,
+        pub window_height: Option<Value>,
+        pub thumbnails: Option<Value>,
+        pub thumbnail_cache_mb: Option<Value>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields, default)]
+    pub(super) struct DeviceConfig {
+        pub name: Option<Value>,
+        pub url: Option<Value>,
+        pub username: Option<Value>,
+        pub device_type: Option<Value>,
+    }
+}
+
+/// Levenshtein (edit) distance between two strings, used to compute a "did
+/// you mean" suggestion for a misspelled config key.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut prev_diag = row[0];
+        row[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let above = row[j];
+            row[j] = (row[j] + 1).min(row[j - 1] + 1).min(prev_diag + cost);
+            prev_diag = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// The closest name in `known` to `key`, if any are within a small edit
+/// distance -- close enough that it's worth suggesting rather than noise.
+fn suggest_field(key: &str, known: &[String]) -> Option<String> {
+    known
+        .iter()
+        .map(|candidate| (candidate, levenshtein(key, candidate)))
+        .min_by_key(|(_, dist)| *dist)
+        .filter(|(_, dist)| *dist <= 3)
+        .map(|(candidate, _)| candidate.clone())
+}
+
+/// Pull the text between the first `start`/`end` delimiters out of `haystack`
+fn extract_between<'a>(haystack: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let after_start = &haystack[haystack.find(start)? + start.len()..];
+    Some(&after_start[..after_start.find(end)?])
+}
+
+/// Pull every backtick-quoted name out of the "expected ..." clause of a
+/// serde unknown-field error, e.g. "expected one of `a`, `b` or `c`" ->
+/// `["a", "b", "c"]`. The separators between names ("`, `" vs "` or `")
+/// aren't significant here -- only the backtick-delimited names are.
+fn extract_expected_fields(message: &str) -> Vec<String> {
+    let Some(start) = message.find("expected") else {
+        return Vec::new();
+    };
+    message[start..]
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(String::from)
+        .collect()
+}
+
+/// Turn a `deny_unknown_fields` rejection from one of the `strict` mirror
+/// types (see above) into a diagnostic message, adding a "did you mean...?"
+/// suggestion computed by Levenshtein distance against the field names
+/// serde's own error already lists as expected -- there's no separate list
+/// of field names here to fall out of sync with the real config structs.
+fn unknown_field_diagnostic(err: &toml::de::Error) -> String {
+    let message = err.to_string();
+    let Some(unknown) = extract_between(&message, "unknown field `", "`") else {
+        return message;
+    };
+    let candidates = extract_expected_fields(&message);
+    match suggest_field(unknown, &candidates) {
+        Some(suggestion) => format!("{} -- did you mean `{}`?", message, suggestion),
+        None => message,
+    }
+}
+
+/// Merge `overlay` into `base` field-wise: where both sides are tables at
+/// the same path, recurse key by key (so an override for one field doesn't
+/// blow away its siblings); otherwise `overlay` wins outright.
+fn merge_tables(base: &mut toml::Value, overlay: toml::Value) {
+    let (toml::Value::Table(_), toml::Value::Table(_)) = (&*base, &overlay) else {
+        *base = overlay;
+        return;
+    };
+
+    let toml::Value::Table(overlay_table) = overlay else {
+        unreachable!("checked above");
+    };
+    let toml::Value::Table(base_table) = base else {
+        unreachable!("checked above");
+    };
+
+    for (key, value) in overlay_table {
+        match base_table.get_mut(&key) {
+            Some(existing) => merge_tables(existing, value),
+            None => {
+                base_table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Record the source layer of every leaf value in `value` (tables recurse;
+/// anything else -- including arrays, so `devices` is tracked as one unit
+/// rather than per-element -- is a leaf), keyed by its dotted path.
+fn record_leaf_sources(value: &toml::Value, prefix: &str, source: ConfigSource, sources: &mut HashMap<String, ConfigSource>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, nested) in table {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                record_leaf_sources(nested, &path, source, sources);
+            }
+        }
+        _ => {
+            sources.insert(prefix.to_string(), source);
+        }
+    }
+}
+
+/// Parse an override string (from an env var or a CLI flag) into the TOML
+/// value type it most likely represents. Env vars and CLI flags carry no
+/// type information of their own, and TOML's deserializer -- unlike JSON's
+/// -- doesn't coerce a string into a bool or integer field, so this has to
+/// guess: an integer if it parses as one, else a bool, else a plain string.
+fn parse_override_scalar(raw: &str) -> toml::Value {
+    if let Ok(n) = raw.parse::<i64>() {
+        toml::Value::Integer(n)
+    } else if let Ok(b) = raw.parse::<bool>() {
+        toml::Value::Boolean(b)
+    } else {
+        toml::Value::String(raw.to_string())
+    }
+}
+
+/// Recursively collect the dotted paths of every leaf where `a` and `b`
+/// disagree (including a leaf present in only one side). Used by
+/// [`Config::diff_fields`] to report what changed across a hot-reload.
+fn diff_values(a: &toml::Value, b: &toml::Value, prefix: &str, changed: &mut Vec<String>) {
+    if let (toml::Value::Table(ta), toml::Value::Table(tb)) = (a, b) {
+        let mut keys: std::collections::BTreeSet<&String> = ta.keys().collect();
+        keys.extend(tb.keys());
+        for key in keys {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            match (ta.get(key), tb.get(key)) {
+                (Some(av), Some(bv)) => diff_values(av, bv, &path, changed),
+                _ => changed.push(path),
+            }
+        }
+        return;
+    }
+
+    if a != b {
+        changed.push(prefix.to_string());
+    }
+}
+
+/// Insert `value` into `table` at the nested path described by `segments`
+/// (e.g. `["server", "ws_port"]`), creating intermediate tables as needed.
+fn insert_nested(table: &mut toml::map::Map<String, toml::Value>, segments: &[String], value: toml::Value) {
+    match segments {
+        [] => {}
+        [last] => {
+            table.insert(last.clone(), value);
+        }
+        [head, rest @ ..] => {
+            let entry = table
+                .entry(head.clone())
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+            if let toml::Value::Table(nested) = entry {
+                insert_nested(nested, rest, value);
+            }
+        }
+    }
+}
+
+/// Collect `PIBOX_SECTION__FIELD=value` environment variables into a TOML
+/// value tree, e.g. `PIBOX_SERVER__WS_PORT=9300` becomes `{server: {ws_port:
+/// 9300}}`. The double underscore is the nesting separator -- field names
+/// keep their own single underscores (`ws_port`, not `ws-port`) intact.
+fn env_overrides_to_value() -> toml::Value {
+    let mut root = toml::map::Map::new();
+
+    for (key, value) in std::env::vars() {
+        let Some(rest) = key.strip_prefix("PIBOX_") else {
+            continue;
+        };
+        let segments: Vec<String> = rest.split("__").map(|s| s.to_lowercase()).collect();
+        insert_nested(&mut root, &segments, parse_override_scalar(&value));
+    }
+
+    toml::Value::Table(root)
+}
+
+/// Turn a `{"server.ws_port": "9300"}`-style CLI override map into the same
+/// TOML value tree shape [`env_overrides_to_value`] produces, so both can be
+/// merged through the same path.
+fn cli_overrides_to_value(overrides: &HashMap<String, String>) -> toml::Value {
+    let mut root = toml::map::Map::new();
+
+    for (key, value) in overrides {
+        let segments: Vec<String> = key.split('.').map(str::to_string).collect();
+        insert_nested(&mut root, &segments, parse_override_scalar(value));
+    }
+
+    toml::Value::Table(root)
 }
 
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Schema version this file was written at. Missing (i.e. a config from
+    /// before this field existed) is treated as version 0 by [`Config::load`]
+    /// and [`Config::load_from`], which migrate it forward transparently.
+    #[serde(default)]
+    pub schema_version: u32,
+
     /// Server configuration (for pibox-server)
     #[serde(default)]
     pub server: ServerConfig,
@@ -38,6 +402,35 @@ pub struct Config {
     /// Known devices
     #[serde(default)]
     pub devices: Vec<DeviceConfig>,
+
+    /// Which layer ([`Config::load_layered`]'s default/file/env/cli) each
+    /// effective field ultimately came from, keyed by its dotted path (e.g.
+    /// `"server.ws_port"`). Empty unless built by `load_layered`; never
+    /// persisted -- see [`Config::describe_sources`].
+    #[serde(skip)]
+    sources: HashMap<String, ConfigSource>,
+}
+
+/// Which configuration layer an effective value came from, in increasing
+/// priority order -- a later layer always wins a field-wise merge over an
+/// earlier one. See [`Config::load_layered`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env,
+    Cli,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConfigSource::Default => "default",
+            ConfigSource::File => "file",
+            ConfigSource::Env => "env",
+            ConfigSource::Cli => "cli",
+        })
+    }
 }
 
 /// Server-side configuration
@@ -55,6 +448,19 @@ pub struct ServerConfig {
     #[serde(default = "default_filebrowser_url")]
     pub filebrowser_url: String,
 
+    /// Filebrowser account this server authenticates as, so a mid-session
+    /// token expiry (surfaced as a 401/403) can be recovered from with a
+    /// transparent re-login instead of every in-flight operation failing
+    /// with `PermissionDenied` until the process is restarted. Unset
+    /// leaves the server without Filebrowser credentials of its own --
+    /// `filebrowser_url` then has to be reachable without auth, or with a
+    /// token set some other way.
+    pub filebrowser_username: Option<String>,
+
+    /// Password for `filebrowser_username`. Required alongside it for the
+    /// server to log in to Filebrowser on its own behalf.
+    pub filebrowser_password: Option<String>,
+
     /// JWT secret (base64 encoded)
     /// If not set, a random secret is generated on first run
     pub jwt_secret: Option<String>,
@@ -74,6 +480,100 @@ pub struct ServerConfig {
     /// Load reporting interval in seconds
     #[serde(default = "default_load_interval")]
     pub load_report_interval: u64,
+
+    /// Serve wss:// / HTTPS using rustls instead of plain ws:// / HTTP
+    #[serde(default)]
+    pub tls_enabled: bool,
+
+    /// PEM certificate path (self-signed cert generated here on first boot if unset/missing)
+    pub tls_cert_path: Option<String>,
+
+    /// PEM private key path (generated alongside `tls_cert_path` if unset/missing)
+    pub tls_key_path: Option<String>,
+
+    /// Local filesystem root to watch for changes made directly on disk (or
+    /// by another process) so connected clients see them as `FsEvent`
+    /// broadcasts. Unset disables the watcher -- the common case when the
+    /// Filebrowser backend isn't colocated with this server.
+    pub watch_root: Option<String>,
+
+    /// Pushgateway URL to periodically POST metrics to, for headless
+    /// deployments that can't be scraped directly. Unset leaves metrics
+    /// available only by scraping `GET /metrics`.
+    pub metrics_pushgateway_url: Option<String>,
+
+    /// How often to push to `metrics_pushgateway_url`, in seconds
+    #[serde(default = "default_metrics_push_interval")]
+    pub metrics_push_interval: u64,
+
+    /// How long a chunked upload can go without a new `UploadChunk` before
+    /// its staged temp file is swept up and the upload abandoned, in seconds
+    #[serde(default = "default_upload_idle_timeout")]
+    pub upload_idle_timeout: u64,
+
+    /// Directory for the sled-backed download/thumbnail content cache.
+    /// Unset disables caching -- downloads and thumbnails are always
+    /// fetched/generated fresh.
+    pub cache_dir: Option<String>,
+
+    /// Combined size cap for the download and thumbnail caches, in
+    /// megabytes. Each cache is evicted independently (least-recently-used
+    /// first) once it exceeds half of this.
+    #[serde(default = "default_cache_capacity_mb")]
+    pub cache_capacity_mb: u32,
+
+    /// WiFi/Tailscale connectivity to bring up at boot, for a device flashed
+    /// headless with nothing but this config file. See
+    /// [`NetworkConfig::apply`].
+    #[serde(default)]
+    pub network: NetworkConfig,
+}
+
+/// Network provisioning applied once at server boot (see
+/// [`NetworkConfig::apply`]), so a headless device can bring up its own
+/// connectivity instead of requiring manual OS setup first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    /// Run `tailscale up` at boot.
+    #[serde(default)]
+    pub tailscale: bool,
+
+    /// WiFi networks to connect to, tried in order.
+    #[serde(default)]
+    pub wifi: Vec<WifiNetwork>,
+}
+
+/// A single WiFi network to provision. `password` is only ever read from
+/// TOML -- `#[serde(skip_serializing)]` means a freshly saved config never
+/// writes it back out, and [`WifiNetwork::secret`] persists it to the OS
+/// keyring on first use so it survives even though the file itself forgets it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WifiNetwork {
+    pub ssid: String,
+
+    #[serde(default)]
+    pub security: WifiSecurity,
+
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+}
+
+/// WiFi authentication scheme
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WifiSecurity {
+    #[default]
+    Wpa2,
+    Wpa3,
+    Open,
+}
+
+/// The outcome of a single provisioning action taken by [`NetworkConfig::apply`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ProvisionResult {
+    pub action: String,
+    pub success: bool,
+    pub message: String,
 }
 
 /// Client-side configuration
@@ -159,6 +659,11 @@ pub struct TuiConfig {
     /// Enable image preview (sixel/kitty)
     #[serde(default)]
     pub image_preview: bool,
+
+    /// Column width percentages for the Miller-column layout: parent
+    /// directory, file list, preview pane. Must sum to (roughly) 100.
+    #[serde(default = "default_preview_ratios")]
+    pub preview_ratios: [u16; 3],
 }
 
 /// GUI-specific configuration
@@ -203,6 +708,15 @@ fn default_max_transfers() -> u32 {
 fn default_load_interval() -> u64 {
     5
 }
+fn default_metrics_push_interval() -> u64 {
+    15
+}
+fn default_upload_idle_timeout() -> u64 {
+    300 // 5 minutes
+}
+fn default_cache_capacity_mb() -> u32 {
+    256
+}
 fn default_true() -> bool {
     true
 }
@@ -215,6 +729,203 @@ fn default_window_height() -> u32 {
 fn default_thumb_cache() -> u32 {
     100
 }
+fn default_preview_ratios() -> [u16; 3] {
+    [20, 40, 40]
+}
+
+impl ServerConfig {
+    /// URL scheme clients should dial: `wss` when TLS termination is enabled, `ws` otherwise
+    pub fn ws_scheme(&self) -> &'static str {
+        if self.tls_enabled {
+            "wss"
+        } else {
+            "ws"
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Bring up connectivity as described by this config: connect each
+    /// `wifi` entry in order, then run `tailscale up` if `tailscale` is set.
+    /// A no-op (empty result list) when nothing is configured. Linux-only;
+    /// other platforms report each configured action as a failure rather
+    /// than silently skipping it, so a misconfigured deployment doesn't look
+    /// like a working one.
+    pub fn apply(&self) -> Vec<ProvisionResult> {
+        let mut results: Vec<ProvisionResult> = self.wifi.iter().map(WifiNetwork::apply).collect();
+
+        if self.tailscale {
+            results.push(apply_tailscale());
+        }
+
+        results
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn apply_tailscale() -> ProvisionResult {
+    let action = "tailscale up".to_string();
+    match std::process::Command::new("tailscale").arg("up").status() {
+        Ok(status) if status.success() => ProvisionResult {
+            action,
+            success: true,
+            message: "tailscale up succeeded".to_string(),
+        },
+        Ok(status) => ProvisionResult {
+            action,
+            success: false,
+            message: format!("tailscale up exited with {}", status),
+        },
+        Err(e) => ProvisionResult {
+            action,
+            success: false,
+            message: format!("failed to invoke tailscale: {}", e),
+        },
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_tailscale() -> ProvisionResult {
+    ProvisionResult {
+        action: "tailscale up".to_string(),
+        success: false,
+        message: "Tailscale provisioning is only implemented on Linux".to_string(),
+    }
+}
+
+/// Escape a value for embedding in a double-quoted `wpa_supplicant.conf`
+/// string field (`ssid="..."`, `psk="..."`) by backslash-escaping `\` and
+/// `"`, so an SSID or password containing either can't break out of the
+/// quotes and inject an extra directive into a system file this applies
+/// unattended at every boot. Rejects embedded newlines outright: the
+/// format is line-based, so there's no safe way to keep one in a single
+/// field even escaped.
+fn escape_wpa_supplicant_string(value: &str) -> std::io::Result<String> {
+    if value.contains('\n') || value.contains('\r') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "wpa_supplicant SSID/password cannot contain a newline",
+        ));
+    }
+
+    Ok(value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl WifiNetwork {
+    /// Connect to this network: prefer NetworkManager (`nmcli`) if it's
+    /// present, falling back to appending a `wpa_supplicant` network block
+    /// when it isn't (e.g. a minimal Raspberry Pi OS Lite image).
+    #[cfg(target_os = "linux")]
+    fn apply(&self) -> ProvisionResult {
+        let action = format!("wifi `{}`", self.ssid);
+        let password = self.secret();
+
+        let mut nmcli_args = vec!["dev".to_string(), "wifi".to_string(), "connect".to_string(), self.ssid.clone()];
+        if let Some(ref password) = password {
+            nmcli_args.push("password".to_string());
+            nmcli_args.push(password.clone());
+        }
+
+        if let Ok(status) = std::process::Command::new("nmcli").args(&nmcli_args).status() {
+            if status.success() {
+                return ProvisionResult {
+                    action,
+                    success: true,
+                    message: "connected via NetworkManager (nmcli)".to_string(),
+                };
+            }
+        }
+
+        match self.write_wpa_supplicant_block(password.as_deref()) {
+            Ok(path) => ProvisionResult {
+                action,
+                success: true,
+                message: format!("wrote wpa_supplicant profile to {}", path),
+            },
+            Err(e) => ProvisionResult {
+                action,
+                success: false,
+                message: format!("failed to provision: {}", e),
+            },
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn apply(&self) -> ProvisionResult {
+        ProvisionResult {
+            action: format!("wifi `{}`", self.ssid),
+            success: false,
+            message: "WiFi provisioning is only implemented on Linux".to_string(),
+        }
+    }
+
+    /// Append a `network={...}` block for this SSID to
+    /// `/etc/wpa_supplicant/wpa_supplicant.conf`.
+    #[cfg(target_os = "linux")]
+    fn write_wpa_supplicant_block(&self, password: Option<&str>) -> std::io::Result<String> {
+        use std::io::Write;
+
+        const WPA_SUPPLICANT_CONF: &str = "/etc/wpa_supplicant/wpa_supplicant.conf";
+
+        let ssid = escape_wpa_supplicant_string(&self.ssid)?;
+        let block = match (&self.security, password) {
+            (WifiSecurity::Open, _) => format!("\nnetwork={{\n\tssid=\"{}\"\n\tkey_mgmt=NONE\n}}\n", ssid),
+            (_, Some(psk)) => {
+                let psk = escape_wpa_supplicant_string(psk)?;
+                format!("\nnetwork={{\n\tssid=\"{}\"\n\tpsk=\"{}\"\n}}\n", ssid, psk)
+            }
+            (_, None) => format!("\nnetwork={{\n\tssid=\"{}\"\n}}\n", ssid),
+        };
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(WPA_SUPPLICANT_CONF)?;
+        file.write_all(block.as_bytes())?;
+        Ok(WPA_SUPPLICANT_CONF.to_string())
+    }
+
+    /// The password to provision with: if this config carries one (read
+    /// straight from TOML), persist it to the OS keyring for next time and
+    /// use it now; otherwise fall back to whatever was already stored there.
+    fn secret(&self) -> Option<String> {
+        if let Some(ref password) = self.password {
+            let _ = crate::credentials::store_password("wifi", &self.ssid, password);
+            return Some(password.clone());
+        }
+
+        crate::credentials::get_password("wifi", &self.ssid).ok().flatten()
+    }
+}
+
+impl DeviceConfig {
+    /// Store `secret` in the OS keyring under this device's `username`,
+    /// keyed by `format!("pibox:{name}")` so it never ends up in the
+    /// on-disk TOML. No-op (returns `Ok`) if `username` isn't set, since
+    /// there's nothing to key the credential by.
+    pub fn save_secret(&self, secret: &str) -> Result<(), crate::credentials::CredentialError> {
+        let Some(ref username) = self.username else {
+            return Ok(());
+        };
+        crate::credentials::store_password(&self.name, username, secret)
+    }
+
+    /// Load the password previously stored for this device via
+    /// [`DeviceConfig::save_secret`], if any.
+    pub fn load_secret(&self) -> Option<String> {
+        let username = self.username.as_ref()?;
+        crate::credentials::get_password(&self.name, username).ok().flatten()
+    }
+
+    /// Remove this device's stored password from the keyring, e.g. when the
+    /// device is removed from the config.
+    pub fn delete_secret(&self) -> Result<(), crate::credentials::CredentialError> {
+        let Some(ref username) = self.username else {
+            return Ok(());
+        };
+        crate::credentials::delete_password(&self.name, username)
+    }
+}
 
 impl Default for ServerConfig {
     fn default() -> Self {
@@ -222,11 +933,23 @@ impl Default for ServerConfig {
             listen_addr: default_listen_addr(),
             ws_port: default_ws_port(),
             filebrowser_url: default_filebrowser_url(),
+            filebrowser_username: None,
+            filebrowser_password: None,
             jwt_secret: None,
             access_token_ttl: default_access_ttl(),
             refresh_token_ttl: default_refresh_ttl(),
             max_concurrent_transfers: default_max_transfers(),
             load_report_interval: default_load_interval(),
+            tls_enabled: false,
+            tls_cert_path: None,
+            tls_key_path: None,
+            watch_root: None,
+            metrics_pushgateway_url: None,
+            metrics_push_interval: default_metrics_push_interval(),
+            upload_idle_timeout: default_upload_idle_timeout(),
+            cache_dir: None,
+            cache_capacity_mb: default_cache_capacity_mb(),
+            network: NetworkConfig::default(),
         }
     }
 }
@@ -251,6 +974,7 @@ impl Default for TuiConfig {
             true_color: true,
             mouse: true,
             image_preview: false,
+            preview_ratios: default_preview_ratios(),
         }
     }
 }
@@ -269,9 +993,11 @@ impl Default for GuiConfig {
 impl Default for Config {
     fn default() -> Self {
         Self {
+            schema_version: CURRENT_SCHEMA_VERSION,
             server: ServerConfig::default(),
             client: ClientConfig::default(),
             devices: Vec::new(),
+            sources: HashMap::new(),
         }
     }
 }
@@ -297,18 +1023,167 @@ impl Config {
             return Ok(Self::default());
         }
 
-        let content = std::fs::read_to_string(&path)?;
-        let config: Config = toml::from_str(&content)?;
-        Ok(config)
+        Self::load_from(&path)
     }
 
-    /// Load config from specific path
+    /// Load config from specific path, migrating it to [`CURRENT_SCHEMA_VERSION`]
+    /// and rewriting it in place if it's behind -- keeping a timestamped
+    /// `.bak` of the pre-migration file alongside it.
     pub fn load_from(path: &std::path::Path) -> Result<Self, ConfigError> {
         let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let raw: toml::Value = toml::from_str(&content)?;
+
+        let from_version = raw
+            .get("schema_version")
+            .and_then(toml::Value::as_integer)
+            .unwrap_or(0) as u32;
+
+        if from_version >= CURRENT_SCHEMA_VERSION {
+            return Ok(raw.try_into()?);
+        }
+
+        let migrated = migrate(raw, from_version);
+        let config: Config = migrated.clone().try_into()?;
+
+        let backup_path = path.with_file_name(format!(
+            "{}.bak.{}",
+            path.file_name().and_then(|n| n.to_str()).unwrap_or("config.toml"),
+            chrono::Utc::now().format("%Y%m%d%H%M%S")
+        ));
+        if let Err(e) = std::fs::write(&backup_path, &content) {
+            tracing::warn!("Failed to write config backup to {}: {}", backup_path.display(), e);
+        }
+
+        if let Ok(rewritten) = toml::to_string_pretty(&config) {
+            if let Err(e) = std::fs::write(path, rewritten) {
+                tracing::warn!("Failed to write migrated config to {}: {}", path.display(), e);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Load config from specific path in strict mode: unknown keys in any
+    /// section (a typo like `vim_modee`, or a misspelled section name) are
+    /// rejected with [`ConfigError::ValidationError`] reporting the offending
+    /// key and a "did you mean" suggestion, rather than being silently
+    /// ignored the way [`Config::load_from`] ignores them -- enforced via
+    /// `#[serde(deny_unknown_fields)]` on the `strict` mirror types above, not
+    /// a hand-maintained field registry. Also runs [`Config::validate_semantics`]
+    /// on the result. Does not run schema migration -- a config worth
+    /// linting this closely is expected to already be current.
+    pub fn load_strict(path: &std::path::Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)?;
+        let raw: toml::Value = toml::from_str(&content)?;
+
+        if let Err(e) = raw.clone().try_into::<strict::Config>() {
+            return Err(ConfigError::ValidationError(unknown_field_diagnostic(&e)));
+        }
+
+        let config: Config = raw.try_into()?;
+        config.validate_semantics()?;
+        Ok(config)
+    }
+
+    /// Load config from default location, layering `Config::default()` ->
+    /// the on-disk file (if any) -> `PIBOX_SECTION__FIELD` environment
+    /// variables -> `cli_overrides`, in that order of increasing priority.
+    /// See [`Config::load_layered_from`] for the merge semantics.
+    pub fn load_layered(cli_overrides: &HashMap<String, String>) -> Result<Self, ConfigError> {
+        let path = Self::config_path()?;
+        Self::load_layered_from(&path, cli_overrides)
+    }
+
+    /// Load config from `path`, layering `Config::default()` -> `path` (if it
+    /// exists) -> `PIBOX_SECTION__FIELD` environment variables ->
+    /// `cli_overrides` (dotted keys, e.g. `"server.ws_port"`).
+    ///
+    /// Unlike [`Config::load_from`], merging is field-wise rather than
+    /// whole-section: an override of `server.ws_port` leaves every other
+    /// `[server]` field as the previous layer set it. Does not run schema
+    /// migration; a config fresh enough to be layered this way is expected
+    /// to already be current. Each layer's contribution to the final value
+    /// is recorded and can be inspected afterwards with
+    /// [`Config::describe_sources`].
+    pub fn load_layered_from(path: &std::path::Path, cli_overrides: &HashMap<String, String>) -> Result<Self, ConfigError> {
+        let mut merged = toml::Value::try_from(Self::default())?;
+        let mut sources = HashMap::new();
+        record_leaf_sources(&merged, "", ConfigSource::Default, &mut sources);
+
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let file_value: toml::Value = toml::from_str(&content)?;
+            record_leaf_sources(&file_value, "", ConfigSource::File, &mut sources);
+            merge_tables(&mut merged, file_value);
+        }
+
+        let env_value = env_overrides_to_value();
+        record_leaf_sources(&env_value, "", ConfigSource::Env, &mut sources);
+        merge_tables(&mut merged, env_value);
+
+        let cli_value = cli_overrides_to_value(cli_overrides);
+        record_leaf_sources(&cli_value, "", ConfigSource::Cli, &mut sources);
+        merge_tables(&mut merged, cli_value);
+
+        let mut config: Config = merged.try_into()?;
+        config.sources = sources;
         Ok(config)
     }
 
+    /// Which layer ([`ConfigSource`]) each effective field of a
+    /// [`Config::load_layered`]-built config ultimately came from, sorted by
+    /// dotted path. Empty for a config built any other way.
+    pub fn describe_sources(&self) -> Vec<(String, ConfigSource)> {
+        let mut entries: Vec<(String, ConfigSource)> = self
+            .sources
+            .iter()
+            .map(|(path, source)| (path.clone(), *source))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Dotted paths of every leaf field whose effective value differs
+    /// between `self` and `other`, e.g. before and after [`crate::config_watcher::ConfigWatcher`]
+    /// picks up an edited `config.toml`. Sorted for stable output.
+    pub fn diff_fields(&self, other: &Config) -> Vec<String> {
+        let a = toml::Value::try_from(self.clone()).unwrap_or(toml::Value::Table(Default::default()));
+        let b = toml::Value::try_from(other.clone()).unwrap_or(toml::Value::Table(Default::default()));
+
+        let mut changed = Vec::new();
+        diff_values(&a, &b, "", &mut changed);
+        changed.sort();
+        changed
+    }
+
+    /// Semantic validation that a permissive field-by-field parse can't
+    /// catch on its own: a refresh token that doesn't outlive the access
+    /// token it refreshes, a `0` port, or a `listen_addr` that isn't a
+    /// parseable IP.
+    pub fn validate_semantics(&self) -> Result<(), ConfigError> {
+        if self.server.access_token_ttl >= self.server.refresh_token_ttl {
+            return Err(ConfigError::ValidationError(format!(
+                "server.access_token_ttl ({}) must be less than server.refresh_token_ttl ({})",
+                self.server.access_token_ttl, self.server.refresh_token_ttl
+            )));
+        }
+
+        if self.server.ws_port == 0 {
+            return Err(ConfigError::ValidationError(
+                "server.ws_port must not be 0".to_string(),
+            ));
+        }
+
+        if self.server.listen_addr.parse::<std::net::IpAddr>().is_err() {
+            return Err(ConfigError::ValidationError(format!(
+                "server.listen_addr `{}` is not a valid IP address",
+                self.server.listen_addr
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Save config to default location
     pub fn save(&self) -> Result<(), ConfigError> {
         let path = Self::config_path()?;
@@ -339,8 +1214,36 @@ impl Config {
         self.devices.iter().find(|d| d.name == name)
     }
 
-    /// Add or update device
-    pub fn upsert_device(&mut self, device: DeviceConfig) {
+    /// Render a previously-added device as a scannable pairing QR code (see
+    /// [`crate::pairing`]), so a phone or headless second client can be
+    /// configured by scanning rather than typing a `ws://` URL in by hand.
+    /// `None` if no device named `name` exists.
+    pub fn export_device_qr(&self, name: &str) -> Option<String> {
+        let device = self.get_device(name)?;
+        let blob = crate::pairing::encode_device(device).ok()?;
+        Some(crate::pairing::render_qr(&blob))
+    }
+
+    /// Parse a pairing blob scanned from a QR code produced by
+    /// `export_device_qr` (or printed by a server at boot) and add/update
+    /// the device it describes. Never carries a password -- credentials are
+    /// paired separately via the OS keyring (see [`crate::credentials`]).
+    pub fn import_device_from_str(&mut self, blob: &str) -> Result<(), ConfigError> {
+        let device = crate::pairing::decode_device(blob).map_err(|e| ConfigError::PairingError(e.to_string()))?;
+        self.upsert_device(device, None);
+        Ok(())
+    }
+
+    /// Add or update device. `password`, if given, is routed straight to the
+    /// OS keyring via [`DeviceConfig::save_secret`] rather than into `self`
+    /// -- it's never written to the on-disk config.
+    pub fn upsert_device(&mut self, device: DeviceConfig, password: Option<&str>) {
+        if let Some(password) = password {
+            if let Err(e) = device.save_secret(password) {
+                tracing::warn!("Failed to store credential for device {}: {}", device.name, e);
+            }
+        }
+
         if let Some(existing) = self.devices.iter_mut().find(|d| d.name == device.name) {
             *existing = device;
         } else {
@@ -353,6 +1256,16 @@ impl Config {
 mod tests {
     use super::*;
 
+    /// `std::env::set_var`/`remove_var` are process-global, so two tests
+    /// mutating the same `PIBOX_*` variable concurrently (the default under
+    /// cargo's parallel test runner) can see each other's value mid-test.
+    /// Every test that touches process env vars takes this lock for its
+    /// duration instead.
+    fn env_test_lock() -> &'static std::sync::Mutex<()> {
+        static LOCK: std::sync::OnceLock<std::sync::Mutex<()>> = std::sync::OnceLock::new();
+        LOCK.get_or_init(|| std::sync::Mutex::new(()))
+    }
+
     #[test]
     fn test_default_config() {
         let config = Config::default();
@@ -370,28 +1283,352 @@ mod tests {
         assert_eq!(parsed.server.ws_port, config.server.ws_port);
     }
 
+    #[test]
+    fn test_schema_version_defaults_to_current() {
+        let config = Config::default();
+        assert_eq!(config.schema_version, CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_load_from_stamps_missing_schema_version_and_backs_up() {
+        let path = std::env::temp_dir().join(format!("pibox-config-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "[server]\nws_port = 9999\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(config.server.ws_port, 9999);
+
+        // The file on disk was rewritten with the current schema version...
+        let rewritten = std::fs::read_to_string(&path).unwrap();
+        assert!(rewritten.contains("schema_version = 1"));
+
+        // ...and a backup of the original (pre-migration) content exists.
+        let backup = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .find(|e| {
+                e.file_name()
+                    .to_str()
+                    .map(|n| n.starts_with(&format!("{}.bak.", path.file_name().unwrap().to_str().unwrap())))
+                    .unwrap_or(false)
+            });
+        assert!(backup.is_some());
+
+        std::fs::remove_file(&path).ok();
+        if let Some(entry) = backup {
+            std::fs::remove_file(entry.path()).ok();
+        }
+    }
+
+    #[test]
+    fn test_load_from_skips_migration_when_already_current() {
+        let path = std::env::temp_dir().join(format!("pibox-config-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, format!("schema_version = {}\n", CURRENT_SCHEMA_VERSION)).unwrap();
+
+        Config::load_from(&path).unwrap();
+
+        // No backup should have been written since the file was already current.
+        let has_backup = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .any(|e| {
+                e.file_name()
+                    .to_str()
+                    .map(|n| n.starts_with(&format!("{}.bak.", path.file_name().unwrap().to_str().unwrap())))
+                    .unwrap_or(false)
+            });
+        assert!(!has_backup);
+
+        std::fs::remove_file(&path).ok();
+    }
+
     #[test]
     fn test_device_upsert() {
         let mut config = Config::default();
 
-        config.upsert_device(DeviceConfig {
-            name: "nas".to_string(),
-            url: "ws://192.0.2.10:9280".to_string(),
-            username: Some("admin".to_string()),
-            device_type: DeviceType::Nas,
-        });
+        config.upsert_device(
+            DeviceConfig {
+                name: "nas".to_string(),
+                url: "ws://192.0.2.10:9280".to_string(),
+                username: Some("admin".to_string()),
+                device_type: DeviceType::Nas,
+            },
+            None,
+        );
 
         assert_eq!(config.devices.len(), 1);
 
         // Update existing
-        config.upsert_device(DeviceConfig {
-            name: "nas".to_string(),
-            url: "ws://192.0.2.11:9280".to_string(),
-            username: Some("admin".to_string()),
-            device_type: DeviceType::Nas,
-        });
+        config.upsert_device(
+            DeviceConfig {
+                name: "nas".to_string(),
+                url: "ws://192.0.2.11:9280".to_string(),
+                username: Some("admin".to_string()),
+                device_type: DeviceType::Nas,
+            },
+            None,
+        );
 
         assert_eq!(config.devices.len(), 1);
         assert!(config.devices[0].url.contains("192.0.2.11"));
     }
+
+    #[test]
+    fn test_export_device_qr_requires_existing_device() {
+        let config = Config::default();
+        assert!(config.export_device_qr("nope").is_none());
+    }
+
+    #[test]
+    fn test_export_import_device_round_trips() {
+        let mut config = Config::default();
+        config.upsert_device(
+            DeviceConfig {
+                name: "nas".to_string(),
+                url: "ws://192.0.2.10:9280".to_string(),
+                username: Some("admin".to_string()),
+                device_type: DeviceType::Nas,
+            },
+            None,
+        );
+
+        let blob = crate::pairing::encode_device(config.get_device("nas").unwrap()).unwrap();
+
+        let mut other = Config::default();
+        other.import_device_from_str(&blob).unwrap();
+
+        assert_eq!(other.get_device("nas").unwrap().url, "ws://192.0.2.10:9280");
+    }
+
+    #[test]
+    fn test_import_device_from_str_rejects_garbage() {
+        let mut config = Config::default();
+        assert!(config.import_device_from_str("not json").is_err());
+    }
+
+    #[test]
+    fn test_device_config_without_username_skips_secret_storage() {
+        let device = DeviceConfig {
+            name: "headless".to_string(),
+            url: "ws://192.0.2.20:9280".to_string(),
+            username: None,
+            device_type: DeviceType::Generic,
+        };
+
+        assert!(device.save_secret("irrelevant").is_ok());
+        assert_eq!(device.load_secret(), None);
+    }
+
+    #[test]
+    fn test_levenshtein_distance() {
+        assert_eq!(levenshtein("vim_mode", "vim_mode"), 0);
+        assert_eq!(levenshtein("vim_modee", "vim_mode"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest_field_finds_close_typo() {
+        let known: Vec<String> = vec!["vim_mode".to_string(), "theme".to_string()];
+        assert_eq!(suggest_field("vim_modee", &known), Some("vim_mode".to_string()));
+        assert_eq!(suggest_field("completely_unrelated_garbage", &known), None);
+    }
+
+    #[test]
+    fn test_extract_expected_fields_handles_oxford_or() {
+        let fields = extract_expected_fields("unknown field `x`, expected one of `a`, `b` or `c`");
+        assert_eq!(fields, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+    }
+
+    #[test]
+    fn test_load_strict_rejects_unknown_field_with_suggestion() {
+        let path = std::env::temp_dir().join(format!("pibox-strict-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, format!("schema_version = {}\n\n[client]\nvim_modee = true\n", CURRENT_SCHEMA_VERSION)).unwrap();
+
+        let err = Config::load_strict(&path).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("vim_modee"));
+        assert!(message.contains("vim_mode"));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_load_strict_accepts_clean_config() {
+        let path = std::env::temp_dir().join(format!("pibox-strict-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, format!("schema_version = {}\n\n[server]\nws_port = 9280\n", CURRENT_SCHEMA_VERSION)).unwrap();
+
+        assert!(Config::load_strict(&path).is_ok());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_validate_semantics_rejects_inverted_ttls() {
+        let mut config = Config::default();
+        config.server.access_token_ttl = 1000;
+        config.server.refresh_token_ttl = 500;
+        assert!(config.validate_semantics().is_err());
+    }
+
+    #[test]
+    fn test_validate_semantics_rejects_zero_port() {
+        let mut config = Config::default();
+        config.server.ws_port = 0;
+        assert!(config.validate_semantics().is_err());
+    }
+
+    #[test]
+    fn test_validate_semantics_rejects_invalid_listen_addr() {
+        let mut config = Config::default();
+        config.server.listen_addr = "not-an-ip".to_string();
+        assert!(config.validate_semantics().is_err());
+    }
+
+    #[test]
+    fn test_validate_semantics_accepts_defaults() {
+        assert!(Config::default().validate_semantics().is_ok());
+    }
+
+    #[test]
+    fn test_network_config_defaults_to_no_provisioning() {
+        let network = NetworkConfig::default();
+        assert!(!network.tailscale);
+        assert!(network.wifi.is_empty());
+        assert!(network.apply().is_empty());
+    }
+
+    #[test]
+    fn test_wifi_password_is_never_serialized() {
+        let wifi = WifiNetwork {
+            ssid: "home".to_string(),
+            security: WifiSecurity::Wpa2,
+            password: Some("hunter2".to_string()),
+        };
+        let toml = toml::to_string(&wifi).unwrap();
+        assert!(!toml.contains("hunter2"));
+        assert!(!toml.contains("password"));
+    }
+
+    #[test]
+    fn test_wifi_security_defaults_to_wpa2() {
+        let json = r#"{"ssid":"home"}"#;
+        let wifi: WifiNetwork = serde_json::from_str(json).unwrap();
+        assert!(matches!(wifi.security, WifiSecurity::Wpa2));
+    }
+
+    #[test]
+    fn test_escape_wpa_supplicant_string_escapes_quotes_and_backslashes() {
+        let escaped = escape_wpa_supplicant_string("my\"ssid\\").unwrap();
+        assert_eq!(escaped, "my\\\"ssid\\\\");
+    }
+
+    #[test]
+    fn test_escape_wpa_supplicant_string_blocks_quote_breakout() {
+        // Unescaped, a bare `"` would close the `ssid="..."` field early;
+        // escaped, the quote stays part of the SSID's value.
+        let escaped = escape_wpa_supplicant_string("evil\"ssid").unwrap();
+        assert_eq!(escaped, "evil\\\"ssid");
+    }
+
+    #[test]
+    fn test_escape_wpa_supplicant_string_rejects_embedded_newline() {
+        assert!(escape_wpa_supplicant_string("evil\nkey_mgmt=NONE").is_err());
+    }
+
+    #[test]
+    fn test_load_layered_from_missing_file_returns_defaults() {
+        let _guard = env_test_lock().lock().unwrap_or_else(|e| e.into_inner());
+        let path = std::env::temp_dir().join(format!("pibox-layered-test-{}.toml", uuid::Uuid::new_v4()));
+        let config = Config::load_layered_from(&path, &HashMap::new()).unwrap();
+        assert_eq!(config.server.ws_port, ServerConfig::default().ws_port);
+        assert!(config
+            .describe_sources()
+            .iter()
+            .all(|(_, source)| *source == ConfigSource::Default));
+    }
+
+    #[test]
+    fn test_load_layered_from_file_overrides_one_field_without_resetting_siblings() {
+        let _guard = env_test_lock().lock().unwrap_or_else(|e| e.into_inner());
+        let path = std::env::temp_dir().join(format!("pibox-layered-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "[server]\nws_port = 9999\n").unwrap();
+
+        let config = Config::load_layered_from(&path, &HashMap::new()).unwrap();
+
+        assert_eq!(config.server.ws_port, 9999);
+        assert_eq!(config.server.listen_addr, ServerConfig::default().listen_addr);
+
+        let sources: HashMap<_, _> = config.describe_sources().into_iter().collect();
+        assert_eq!(sources.get("server.ws_port"), Some(&ConfigSource::File));
+        assert_eq!(sources.get("server.listen_addr"), Some(&ConfigSource::Default));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_load_layered_from_env_overrides_nested_field() {
+        let _guard = env_test_lock().lock().unwrap_or_else(|e| e.into_inner());
+        let path = std::env::temp_dir().join(format!("pibox-layered-test-{}.toml", uuid::Uuid::new_v4()));
+        std::env::set_var("PIBOX_SERVER__WS_PORT", "9300");
+
+        let config = Config::load_layered_from(&path, &HashMap::new()).unwrap();
+
+        std::env::remove_var("PIBOX_SERVER__WS_PORT");
+
+        assert_eq!(config.server.ws_port, 9300);
+        let sources: HashMap<_, _> = config.describe_sources().into_iter().collect();
+        assert_eq!(sources.get("server.ws_port"), Some(&ConfigSource::Env));
+    }
+
+    #[test]
+    fn test_load_layered_from_cli_override_wins_over_env_and_file() {
+        let _guard = env_test_lock().lock().unwrap_or_else(|e| e.into_inner());
+        let path = std::env::temp_dir().join(format!("pibox-layered-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "[server]\nws_port = 9999\n").unwrap();
+        std::env::set_var("PIBOX_SERVER__WS_PORT", "9300");
+
+        let mut cli_overrides = HashMap::new();
+        cli_overrides.insert("server.ws_port".to_string(), "9301".to_string());
+        let config = Config::load_layered_from(&path, &cli_overrides).unwrap();
+
+        std::env::remove_var("PIBOX_SERVER__WS_PORT");
+
+        assert_eq!(config.server.ws_port, 9301);
+        let sources: HashMap<_, _> = config.describe_sources().into_iter().collect();
+        assert_eq!(sources.get("server.ws_port"), Some(&ConfigSource::Cli));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_merge_tables_is_field_wise_not_section_wise() {
+        let mut base = toml::Value::try_from(Config::default()).unwrap();
+        let overlay: toml::Value = toml::from_str("[server]\nws_port = 1234\n").unwrap();
+
+        merge_tables(&mut base, overlay);
+
+        assert_eq!(base["server"]["ws_port"].as_integer(), Some(1234));
+        assert_eq!(
+            base["server"]["listen_addr"].as_str(),
+            Some(ServerConfig::default().listen_addr.as_str())
+        );
+    }
+
+    #[test]
+    fn test_diff_fields_reports_only_changed_leaves() {
+        let a = Config::default();
+        let mut b = a.clone();
+        b.server.ws_port = 9999;
+
+        let changed = a.diff_fields(&b);
+
+        assert_eq!(changed, vec!["server.ws_port".to_string()]);
+    }
+
+    #[test]
+    fn test_diff_fields_is_empty_for_identical_configs() {
+        let a = Config::default();
+        let b = a.clone();
+        assert!(a.diff_fields(&b).is_empty());
+    }
 }
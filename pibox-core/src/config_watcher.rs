@@ -0,0 +1,157 @@
+//! Live config hot-reload
+//!
+//! `Config` is otherwise read once at startup, so changing a theme,
+//! `max_concurrent_transfers`, or `load_report_interval` requires a full
+//! restart. `ConfigWatcher` watches `Config::config_path()` with `notify`,
+//! debounces rapid writes (a single save often fires several raw events in
+//! quick succession), re-runs [`Config::load_from`]'s migrate+validate
+//! pipeline on change, and publishes the result over a `tokio::sync::watch`
+//! channel alongside a [`ConfigDiff`] of what changed. A parse or validation
+//! error on reload is reported through [`ConfigWatcher::subscribe_events`]
+//! rather than crashing -- the last-good config keeps serving.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{broadcast, mpsc, watch};
+
+use crate::config::Config;
+
+/// Coalescing window: a single editor or config-management tool save often
+/// fires several raw filesystem events for the same file in a row
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Dotted paths of every field whose effective value changed on a reload,
+/// as reported by [`Config::diff_fields`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub changed: Vec<String>,
+}
+
+/// What happened the last time `ConfigWatcher` noticed `config.toml` change
+#[derive(Debug, Clone)]
+pub enum ConfigEvent {
+    /// The file reloaded cleanly and validated; `subscribe()`'s channel
+    /// already carries the new value.
+    Reloaded(ConfigDiff),
+    /// The file failed to parse or failed [`Config::validate_semantics`];
+    /// the previously-loaded config is untouched and keeps serving.
+    ReloadFailed(String),
+}
+
+/// Watches a config file for changes and keeps a live, always-valid
+/// [`Config`] available to any number of subscribers. The underlying
+/// `notify::Watcher` lives for as long as this struct does -- dropping it
+/// stops the watch.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    config_rx: watch::Receiver<Config>,
+    event_tx: broadcast::Sender<ConfigEvent>,
+}
+
+impl ConfigWatcher {
+    /// Start watching `path`, seeded with `initial` -- normally whatever
+    /// `Config::load_from(&path)` already returned at startup, so the first
+    /// reload only fires on an actual subsequent change.
+    pub fn watch(path: PathBuf, initial: Config) -> notify::Result<Self> {
+        let (raw_tx, raw_rx) = mpsc::unbounded_channel();
+        let watched_path = path.clone();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                if event.paths.iter().any(|p| p == &watched_path) {
+                    let _ = raw_tx.send(());
+                }
+            }
+        })?;
+
+        // Watch the parent directory rather than the file itself: editors
+        // commonly save by writing a temp file and renaming it over the
+        // original, which on some platforms invalidates a watch held on the
+        // original inode.
+        if let Some(parent) = path.parent() {
+            watcher.watch(parent, RecursiveMode::NonRecursive)?;
+        }
+
+        let (config_tx, config_rx) = watch::channel(initial);
+        let (event_tx, _) = broadcast::channel(16);
+
+        tokio::spawn(debounce_loop(raw_rx, path, config_tx, event_tx.clone()));
+
+        Ok(Self {
+            _watcher: watcher,
+            config_rx,
+            event_tx,
+        })
+    }
+
+    /// Subscribe to the live config. `watch::Receiver::borrow()` always
+    /// holds the last successfully loaded and validated value.
+    pub fn subscribe(&self) -> watch::Receiver<Config> {
+        self.config_rx.clone()
+    }
+
+    /// Subscribe to reload notifications -- both successful diffs and
+    /// failures -- for a consumer that wants more than "give me the
+    /// latest", e.g. logging every change or surfacing a reload error.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConfigEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// The most recently loaded config.
+    pub fn current(&self) -> Config {
+        self.config_rx.borrow().clone()
+    }
+}
+
+/// Coalesce raw `notify` events into debounced reload attempts
+async fn debounce_loop(
+    mut raw_rx: mpsc::UnboundedReceiver<()>,
+    path: PathBuf,
+    config_tx: watch::Sender<Config>,
+    event_tx: broadcast::Sender<ConfigEvent>,
+) {
+    let mut pending_since: Option<Instant> = None;
+
+    loop {
+        let tick = tokio::time::sleep(Duration::from_millis(50));
+        tokio::pin!(tick);
+
+        tokio::select! {
+            event = raw_rx.recv() => match event {
+                Some(()) => pending_since = Some(Instant::now()),
+                None => break, // Watcher dropped; nothing more will arrive
+            },
+            _ = &mut tick => {}
+        }
+
+        if let Some(since) = pending_since {
+            if since.elapsed() >= DEBOUNCE {
+                pending_since = None;
+                reload(&path, &config_tx, &event_tx);
+            }
+        }
+    }
+}
+
+/// Re-run the load+migrate+validate pipeline and publish the result if
+/// anything actually changed, or report the error if it didn't parse/validate
+fn reload(path: &Path, config_tx: &watch::Sender<Config>, event_tx: &broadcast::Sender<ConfigEvent>) {
+    match Config::load_from(path).and_then(|c| c.validate_semantics().map(|()| c)) {
+        Ok(new_config) => {
+            let changed = {
+                let current = config_tx.borrow();
+                current.diff_fields(&new_config)
+            };
+
+            if !changed.is_empty() {
+                let _ = config_tx.send(new_config);
+                let _ = event_tx.send(ConfigEvent::Reloaded(ConfigDiff { changed }));
+            }
+        }
+        Err(e) => {
+            let _ = event_tx.send(ConfigEvent::ReloadFailed(e.to_string()));
+        }
+    }
+}
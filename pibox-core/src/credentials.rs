@@ -0,0 +1,65 @@
+//! OS keyring integration for device credentials
+//!
+//! `DeviceConfig` is persisted to plain TOML (see [`crate::config`]), which
+//! is fine for everything it holds except a password -- that has to live
+//! somewhere the OS actually protects: Secret Service on Linux, Keychain on
+//! macOS, Credential Manager on Windows. This module wraps the `keyring`
+//! crate so the rest of the codebase never has to touch a platform API
+//! directly.
+
+use keyring::Entry;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    #[error("Keyring error: {0}")]
+    KeyringError(#[from] keyring::Error),
+
+    #[error("No credential found")]
+    NotFound,
+}
+
+/// Store `secret` in the OS keyring under `device`/`user`, overwriting
+/// whatever was previously stored for that pair.
+pub fn store_password(device: &str, user: &str, secret: &str) -> Result<(), CredentialError> {
+    Entry::new(&service_name(device), user)?.set_password(secret)?;
+    Ok(())
+}
+
+/// Retrieve the password previously stored for `device`/`user`, if any.
+/// `Ok(None)` (rather than an error) when nothing has been stored yet, so
+/// callers can fall back to prompting without matching on the keyring's own
+/// not-found error variant.
+pub fn get_password(device: &str, user: &str) -> Result<Option<String>, CredentialError> {
+    match Entry::new(&service_name(device), user)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove the stored password for `device`/`user`, if any. Not an error if
+/// there was nothing to remove.
+pub fn delete_password(device: &str, user: &str) -> Result<(), CredentialError> {
+    match Entry::new(&service_name(device), user)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The keyring "service" name a device's credentials are namespaced under,
+/// so pibox's entries don't collide with an unrelated app's entry for the
+/// same username.
+fn service_name(device: &str) -> String {
+    format!("pibox:{}", device)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_name_is_namespaced() {
+        assert_eq!(service_name("nas"), "pibox:nas");
+    }
+}
@@ -8,12 +8,22 @@
 //! - JWT authentication (separate from Filebrowser's auth)
 //! - Rate limiting and load management
 
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tokio::sync::RwLock;
 
 use crate::state::{FileEntry, FileType};
 
+/// Filebrowser's `/api/login` response carries no expiry, unlike this
+/// server's own `TokenPairResponse`, so a lifetime has to be assumed
+/// rather than read off the server. Matches the default JWT access-token
+/// TTL used elsewhere in this codebase.
+const ASSUMED_TOKEN_LIFETIME: Duration = Duration::from_secs(900);
+
 #[derive(Debug, Error)]
 pub enum FilebrowserError {
     #[error("HTTP request failed: {0}")]
@@ -33,13 +43,97 @@ pub enum FilebrowserError {
 
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+
+    #[error("Resource not modified, but no cached copy is available to serve")]
+    NotModified,
+}
+
+/// A response body plus the HTTP caching metadata needed to make a
+/// conditional request next time (`etag`/`last_modified`) and to tell the
+/// caller how long the body can be considered fresh (`max_age`, parsed from
+/// `Cache-Control`)
+#[derive(Debug, Clone)]
+pub struct CachedResource {
+    pub body: Vec<u8>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub max_age: Option<u64>,
+}
+
+/// Total cached body bytes this client keeps around for conditional
+/// requests before evicting, so a long-running session listing/downloading
+/// many large files doesn't grow without bound on a resource-constrained
+/// device. This is a separate, smaller budget from `pibox-server`'s
+/// disk-backed `ContentCache`: that one serves already-fetched bytes back
+/// to clients, this one only exists to make the *next* request to
+/// Filebrowser conditional.
+const REVALIDATION_CACHE_CAPACITY_BYTES: u64 = 8 * 1024 * 1024;
+
+/// A bounded, least-recently-inserted-evicted cache of [`CachedResource`]s
+/// keyed by namespaced path. Tracks total body bytes and evicts the oldest
+/// entries once `capacity_bytes` is exceeded, so it can't grow without
+/// bound the way a plain `HashMap` would.
+struct BoundedResourceCache {
+    entries: HashMap<String, CachedResource>,
+    order: VecDeque<String>,
+    size_bytes: u64,
+    capacity_bytes: u64,
+}
+
+impl BoundedResourceCache {
+    fn new(capacity_bytes: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            size_bytes: 0,
+            capacity_bytes,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<CachedResource> {
+        self.entries.get(key).cloned()
+    }
+
+    /// Insert or replace `key`, then evict the oldest entries until back
+    /// under `capacity_bytes`.
+    fn insert(&mut self, key: String, resource: CachedResource) {
+        if let Some(old) = self.entries.remove(&key) {
+            self.size_bytes -= old.body.len() as u64;
+            self.order.retain(|k| k != &key);
+        }
+
+        self.size_bytes += resource.body.len() as u64;
+        self.order.push_back(key.clone());
+        self.entries.insert(key, resource);
+
+        while self.size_bytes > self.capacity_bytes {
+            let Some(oldest) = self.order.pop_front() else { break };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.size_bytes -= evicted.body.len() as u64;
+            }
+        }
+    }
 }
 
 /// Filebrowser API client
 pub struct FilebrowserClient {
     client: Client,
     base_url: String,
-    token: Option<String>,
+    token: RwLock<Option<String>>,
+    token_issued_at: RwLock<Option<Instant>>,
+    /// Stored so a mid-session expiry can be recovered from by silently
+    /// logging back in, rather than surfacing `PermissionDenied` for what's
+    /// usually just a stale token. Set once at construction and never
+    /// mutated, so no locking is needed to read it back.
+    credentials: Option<(String, String)>,
+
+    /// Last-seen body plus caching metadata for `download`/`list_dir`,
+    /// keyed by a namespaced path (`"download:{path}"`/`"list:{path}"` so
+    /// the two don't collide), used to make conditional requests and to
+    /// serve a `304 Not Modified` without a round trip for the body.
+    /// Bounded by [`REVALIDATION_CACHE_CAPACITY_BYTES`] so it can't grow
+    /// without bound over a long-running session.
+    cache: RwLock<BoundedResourceCache>,
 }
 
 /// Filebrowser auth response
@@ -76,12 +170,27 @@ impl FilebrowserClient {
         Self {
             client: Client::new(),
             base_url: base_url.trim_end_matches('/').to_string(),
-            token: None,
+            token: RwLock::new(None),
+            token_issued_at: RwLock::new(None),
+            credentials: None,
+            cache: RwLock::new(BoundedResourceCache::new(REVALIDATION_CACHE_CAPACITY_BYTES)),
+        }
+    }
+
+    /// Create a client that stores `username`/`password` so a mid-session
+    /// token expiry (surfaced by Filebrowser as a 401/403) can be recovered
+    /// from with a transparent re-login-and-retry instead of bubbling up
+    /// `PermissionDenied`. Still requires an initial `login()` call; the
+    /// stored credentials are only ever used for automatic retries.
+    pub fn with_credentials(base_url: &str, username: &str, password: &str) -> Self {
+        Self {
+            credentials: Some((username.to_string(), password.to_string())),
+            ..Self::new(base_url)
         }
     }
 
     /// Authenticate with Filebrowser
-    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), FilebrowserError> {
+    pub async fn login(&self, username: &str, password: &str) -> Result<(), FilebrowserError> {
         #[derive(Serialize)]
         struct LoginRequest<'a> {
             username: &'a str,
@@ -100,25 +209,66 @@ impl FilebrowserClient {
         }
 
         let auth: AuthResponse = resp.json().await?;
-        self.token = Some(auth.token);
+        *self.token.write().await = Some(auth.token);
+        *self.token_issued_at.write().await = Some(Instant::now());
         Ok(())
     }
 
     /// Set auth token directly (if already have one)
-    pub fn set_token(&mut self, token: String) {
-        self.token = Some(token);
+    pub async fn set_token(&self, token: String) {
+        *self.token.write().await = Some(token);
+        *self.token_issued_at.write().await = Some(Instant::now());
     }
 
-    /// List directory contents
+    /// How much longer the current token is assumed to remain valid, so a
+    /// caller can proactively refresh instead of waiting for a 401/403.
+    /// `None` if no token has been issued yet; `Some(Duration::ZERO)` once
+    /// the assumed lifetime has elapsed (the token may still work --
+    /// Filebrowser doesn't tell us -- but it's due for a refresh).
+    pub async fn token_lifetime_remaining(&self) -> Option<Duration> {
+        let issued_at = (*self.token_issued_at.read().await)?;
+        Some(ASSUMED_TOKEN_LIFETIME.saturating_sub(issued_at.elapsed()))
+    }
+
+    /// List directory contents. Sends `If-None-Match`/`If-Modified-Since`
+    /// when a previous listing for `path` is cached, and serves that
+    /// cached body back on a `304` instead of re-transferring an unchanged
+    /// directory.
     pub async fn list_dir(&self, path: &str) -> Result<Vec<FileEntry>, FilebrowserError> {
         let path = if path.is_empty() || path == "/" { "" } else { path };
         let url = format!("{}/api/resources{}", self.base_url, path);
+        let cache_key = format!("list:{}", path);
 
-        let resp = self.authed_request(reqwest::Method::GET, &url).send().await?;
+        let cached = self.cache.read().await.get(&cache_key);
 
-        self.handle_error_status(&resp, path).await?;
+        let resp = self
+            .send_with_retry(reqwest::Method::GET, &url, |req| {
+                apply_conditional_headers(req, cached.as_ref())
+            })
+            .await?;
 
-        let resource: ResourceResponse = resp.json().await?;
+        let body = if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+            cached.ok_or(FilebrowserError::NotModified)?.body
+        } else {
+            self.handle_error_status(&resp, path).await?;
+            let (etag, last_modified, max_age) = extract_cache_metadata(&resp);
+            let body = resp.bytes().await?.to_vec();
+
+            self.cache.write().await.insert(
+                cache_key,
+                CachedResource {
+                    body: body.clone(),
+                    etag,
+                    last_modified,
+                    max_age,
+                },
+            );
+
+            body
+        };
+
+        let resource: ResourceResponse =
+            serde_json::from_slice(&body).map_err(|e| FilebrowserError::InvalidResponse(e.to_string()))?;
 
         Ok(resource
             .items
@@ -131,7 +281,7 @@ impl FilebrowserClient {
     pub async fn get_info(&self, path: &str) -> Result<FileEntry, FilebrowserError> {
         let url = format!("{}/api/resources{}", self.base_url, path);
 
-        let resp = self.authed_request(reqwest::Method::GET, &url).send().await?;
+        let resp = self.send_with_retry(reqwest::Method::GET, &url, |req| req).await?;
 
         self.handle_error_status(&resp, path).await?;
 
@@ -139,15 +289,94 @@ impl FilebrowserClient {
         Ok(self.resource_to_entry(resource))
     }
 
-    /// Download file contents
-    pub async fn download(&self, path: &str) -> Result<Vec<u8>, FilebrowserError> {
+    /// Download file contents. Sends `If-None-Match`/`If-Modified-Since`
+    /// when a previous download for `path` is cached, and serves that
+    /// cached body back (along with its original caching metadata) on a
+    /// `304` instead of re-transferring an unchanged file.
+    pub async fn download(&self, path: &str) -> Result<CachedResource, FilebrowserError> {
+        let url = format!("{}/api/raw{}", self.base_url, path);
+        let cache_key = format!("download:{}", path);
+
+        let cached = self.cache.read().await.get(&cache_key);
+
+        let resp = self
+            .send_with_retry(reqwest::Method::GET, &url, |req| {
+                apply_conditional_headers(req, cached.as_ref())
+            })
+            .await?;
+
+        if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return cached.ok_or(FilebrowserError::NotModified);
+        }
+
+        self.handle_error_status(&resp, path).await?;
+        let (etag, last_modified, max_age) = extract_cache_metadata(&resp);
+        let body = resp.bytes().await?.to_vec();
+
+        let resource = CachedResource {
+            body,
+            etag,
+            last_modified,
+            max_age,
+        };
+        self.cache.write().await.insert(cache_key, resource.clone());
+
+        Ok(resource)
+    }
+
+    /// Download a byte range of a file via an HTTP `Range` request, so a
+    /// chunked/resumable transfer never has to buffer the whole file. The
+    /// response is read via `bytes_stream` rather than `.bytes().await`, and
+    /// reading stops as soon as `len` bytes have been collected -- so a
+    /// Filebrowser backend that honors `Range` (answering `206 Partial
+    /// Content`, already scoped to `offset`) streams cheaply, and one that
+    /// ignores it (answering `200 OK` with the whole file) still never
+    /// buffers more than the requested window.
+    pub async fn download_range(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>, FilebrowserError> {
+        use futures::StreamExt;
+
         let url = format!("{}/api/raw{}", self.base_url, path);
+        let last_byte = offset + len.saturating_sub(1);
+        let range_header = format!("bytes={}-{}", offset, last_byte);
 
-        let resp = self.authed_request(reqwest::Method::GET, &url).send().await?;
+        let resp = self
+            .send_with_retry(reqwest::Method::GET, &url, |req| {
+                req.header("Range", range_header.clone())
+            })
+            .await?;
 
         self.handle_error_status(&resp, path).await?;
 
-        Ok(resp.bytes().await?.to_vec())
+        // The status code tells us whether the server actually honored the
+        // request (`Content-Range` itself isn't inspected): a `206` means the
+        // body already starts at
+        // `offset`, a `200` means it's the whole file and our window still
+        // has to be sliced out of the stream ourselves.
+        let partial = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let mut to_skip = if partial { 0 } else { offset };
+        let want = len as usize;
+
+        let mut out = Vec::with_capacity(want.min(8 * 1024 * 1024));
+        let mut stream = resp.bytes_stream();
+
+        while out.len() < want {
+            let Some(chunk) = stream.next().await else { break };
+            let mut chunk = chunk?;
+
+            if to_skip > 0 {
+                let skip = to_skip.min(chunk.len() as u64) as usize;
+                chunk = chunk.slice(skip..);
+                to_skip -= skip as u64;
+                if chunk.is_empty() {
+                    continue;
+                }
+            }
+
+            let take = (want - out.len()).min(chunk.len());
+            out.extend_from_slice(&chunk[..take]);
+        }
+
+        Ok(out)
     }
 
     /// Upload file
@@ -158,9 +387,27 @@ impl FilebrowserClient {
         );
 
         let resp = self
-            .authed_request(reqwest::Method::POST, &url)
-            .body(content.to_vec())
-            .send()
+            .send_with_retry(reqwest::Method::POST, &url, |req| req.body(content.to_vec()))
+            .await?;
+
+        self.handle_error_status(&resp, path).await?;
+        Ok(())
+    }
+
+    /// Append `data` to the file at `path` starting at `offset`, for staging
+    /// a chunked upload across several calls without holding the whole file
+    /// in memory at once. Filebrowser places the bytes at `offset` rather
+    /// than appending blindly, so a chunk retried after a dropped connection
+    /// overwrites itself instead of duplicating.
+    pub async fn upload_chunk(&self, path: &str, offset: u64, data: &[u8]) -> Result<(), FilebrowserError> {
+        let url = format!("{}/api/resources{}", self.base_url, path);
+        let last_byte = offset + data.len().saturating_sub(1) as u64;
+        let content_range = format!("bytes {}-{}/*", offset, last_byte);
+
+        let resp = self
+            .send_with_retry(reqwest::Method::PUT, &url, |req| {
+                req.header("Content-Range", content_range.clone()).body(data.to_vec())
+            })
             .await?;
 
         self.handle_error_status(&resp, path).await?;
@@ -171,12 +418,27 @@ impl FilebrowserClient {
     pub async fn delete(&self, path: &str) -> Result<(), FilebrowserError> {
         let url = format!("{}/api/resources{}", self.base_url, path);
 
-        let resp = self.authed_request(reqwest::Method::DELETE, &url).send().await?;
+        let resp = self.send_with_retry(reqwest::Method::DELETE, &url, |req| req).await?;
 
         self.handle_error_status(&resp, path).await?;
         Ok(())
     }
 
+    /// Move a file or directory into a `.trash` staging dir instead of
+    /// permanently removing it, so the delete can later be undone. Returns
+    /// the path the file now lives at.
+    pub async fn trash(&self, path: &str) -> Result<String, FilebrowserError> {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let trash_path = format!("/.trash/{}__{}", uuid::Uuid::new_v4(), name);
+        self.rename(path, &trash_path).await?;
+        Ok(trash_path)
+    }
+
+    /// Move a previously-trashed file back to its original path
+    pub async fn restore(&self, trash_path: &str, original_path: &str) -> Result<(), FilebrowserError> {
+        self.rename(trash_path, original_path).await
+    }
+
     /// Rename/move file or directory
     pub async fn rename(&self, from: &str, to: &str) -> Result<(), FilebrowserError> {
         let url = format!("{}/api/resources{}", self.base_url, from);
@@ -188,12 +450,12 @@ impl FilebrowserClient {
         }
 
         let resp = self
-            .authed_request(reqwest::Method::PATCH, &url)
-            .json(&RenameRequest {
-                action: "rename",
-                destination: to,
+            .send_with_retry(reqwest::Method::PATCH, &url, |req| {
+                req.json(&RenameRequest {
+                    action: "rename",
+                    destination: to,
+                })
             })
-            .send()
             .await?;
 
         self.handle_error_status(&resp, from).await?;
@@ -205,9 +467,9 @@ impl FilebrowserClient {
         let url = format!("{}/api/resources{}/?override=false", self.base_url, path);
 
         let resp = self
-            .authed_request(reqwest::Method::POST, &url)
-            .header("Content-Length", "0")
-            .send()
+            .send_with_retry(reqwest::Method::POST, &url, |req| {
+                req.header("Content-Length", "0")
+            })
             .await?;
 
         self.handle_error_status(&resp, path).await?;
@@ -216,16 +478,51 @@ impl FilebrowserClient {
 
     // Private helpers
 
-    fn authed_request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+    async fn authed_request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
         let mut req = self.client.request(method, url);
 
-        if let Some(ref token) = self.token {
+        if let Some(ref token) = *self.token.read().await {
             req = req.header("X-Auth", token);
         }
 
         req
     }
 
+    /// Re-authenticate with the stored credentials (if any), mirroring the
+    /// session-id-refresh-then-retry pattern other session-ticket HTTP
+    /// clients use. Returns whether a retry is worth attempting.
+    async fn relogin(&self) -> bool {
+        let Some((username, password)) = self.credentials.as_ref() else {
+            return false;
+        };
+        self.login(username, password).await.is_ok()
+    }
+
+    /// Send a request built by `configure` on top of the current auth
+    /// token, automatically re-logging in and replaying the request once if
+    /// the first attempt comes back 401/403 -- so a token that expired
+    /// mid-session self-heals instead of surfacing `PermissionDenied`.
+    async fn send_with_retry(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        configure: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, FilebrowserError> {
+        let resp = configure(self.authed_request(method.clone(), url).await)
+            .send()
+            .await?;
+
+        if !matches!(
+            resp.status(),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+        ) || !self.relogin().await
+        {
+            return Ok(resp);
+        }
+
+        Ok(configure(self.authed_request(method, url).await).send().await?)
+    }
+
     async fn handle_error_status(&self, resp: &reqwest::Response, path: &str) -> Result<(), FilebrowserError> {
         match resp.status() {
             s if s.is_success() => Ok(()),
@@ -261,6 +558,49 @@ impl FilebrowserClient {
     }
 }
 
+/// Add `If-None-Match`/`If-Modified-Since` headers from a previously cached
+/// response, so an unchanged resource comes back as a cheap `304` instead of
+/// a full body transfer. A no-op when nothing is cached yet.
+fn apply_conditional_headers(req: reqwest::RequestBuilder, cached: Option<&CachedResource>) -> reqwest::RequestBuilder {
+    let Some(cached) = cached else { return req };
+
+    let mut req = req;
+    if let Some(ref etag) = cached.etag {
+        req = req.header("If-None-Match", etag);
+    }
+    if let Some(ref last_modified) = cached.last_modified {
+        req = req.header("If-Modified-Since", last_modified);
+    }
+    req
+}
+
+/// Pull `ETag`/`Last-Modified`/`Cache-Control` out of a response so they can
+/// be stored alongside the body for the next conditional request.
+fn extract_cache_metadata(resp: &reqwest::Response) -> (Option<String>, Option<String>, Option<u64>) {
+    let header_str = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+
+    let etag = header_str("etag");
+    let last_modified = header_str("last-modified");
+    let max_age = header_str("cache-control").and_then(|v| parse_max_age(&v));
+
+    (etag, last_modified, max_age)
+}
+
+/// Parse the `max-age=N` directive out of a `Cache-Control` header value,
+/// e.g. `"public, max-age=3600"` -> `Some(3600)`.
+fn parse_max_age(cache_control: &str) -> Option<u64> {
+    cache_control
+        .split(',')
+        .map(str::trim)
+        .find_map(|part| part.strip_prefix("max-age="))
+        .and_then(|n| n.parse().ok())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -276,4 +616,60 @@ mod tests {
         let client = FilebrowserClient::new("http://localhost:8080/");
         assert_eq!(client.base_url, "http://localhost:8080");
     }
+
+    #[test]
+    fn test_new_has_no_stored_credentials() {
+        let client = FilebrowserClient::new("http://localhost:8080");
+        assert!(client.credentials.is_none());
+    }
+
+    #[test]
+    fn test_with_credentials_stores_username_and_password() {
+        let client = FilebrowserClient::with_credentials("http://localhost:8080", "admin", "hunter2");
+        assert_eq!(
+            client.credentials,
+            Some(("admin".to_string(), "hunter2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_max_age_extracts_value() {
+        assert_eq!(parse_max_age("public, max-age=3600"), Some(3600));
+        assert_eq!(parse_max_age("max-age=60"), Some(60));
+    }
+
+    #[test]
+    fn test_parse_max_age_missing_or_invalid() {
+        assert_eq!(parse_max_age("no-cache"), None);
+        assert_eq!(parse_max_age("max-age=not-a-number"), None);
+    }
+
+    fn resource_of_size(len: usize) -> CachedResource {
+        CachedResource {
+            body: vec![0u8; len],
+            etag: None,
+            last_modified: None,
+            max_age: None,
+        }
+    }
+
+    #[test]
+    fn test_bounded_resource_cache_evicts_oldest_once_over_capacity() {
+        let mut cache = BoundedResourceCache::new(10);
+        cache.insert("a".to_string(), resource_of_size(6));
+        cache.insert("b".to_string(), resource_of_size(6));
+
+        assert!(cache.get("a").is_none(), "oldest entry should have been evicted");
+        assert!(cache.get("b").is_some());
+    }
+
+    #[test]
+    fn test_bounded_resource_cache_replacing_a_key_updates_its_size() {
+        let mut cache = BoundedResourceCache::new(10);
+        cache.insert("a".to_string(), resource_of_size(8));
+        cache.insert("a".to_string(), resource_of_size(2));
+
+        assert_eq!(cache.size_bytes, 2);
+        assert_eq!(cache.get("a").unwrap().body.len(), 2);
+    }
 }
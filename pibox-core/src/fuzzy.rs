@@ -0,0 +1,100 @@
+//! Fuzzy subsequence matching for filtering file entries by search query
+//!
+//! A query matches a candidate if every query character appears, in order
+//! and case-insensitively, as a subsequence of the candidate (so `rdme`
+//! matches `README.md`). Matches are scored rather than just accepted, so
+//! results can be ranked: runs of consecutive matched characters are
+//! rewarded increasingly, matches right after a separator (`_ - . /`) or
+//! at the start of the string are rewarded as word-boundary hits, and the
+//! total gap between matched characters is penalized.
+
+const CONSECUTIVE_BONUS: i32 = 15;
+const BOUNDARY_BONUS: i32 = 30;
+const GAP_PENALTY: i32 = 2;
+
+/// Score `candidate` against `query`, or `None` if `query` is not a
+/// subsequence of `candidate` (case-insensitive). An empty query matches
+/// everything with a score of `0`.
+pub fn score(query: &str, candidate: &str) -> Option<i32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let query_chars: Vec<char> = query.chars().collect();
+
+    let mut total = 0;
+    let mut run = 0;
+    let mut last_match: Option<usize> = None;
+    let mut qi = 0;
+
+    for (ci, &c) in candidate_chars.iter().enumerate() {
+        if qi >= query_chars.len() {
+            break;
+        }
+        if c.to_ascii_lowercase() != query_chars[qi].to_ascii_lowercase() {
+            continue;
+        }
+
+        if let Some(last) = last_match {
+            let gap = ci - last - 1;
+            total -= gap as i32 * GAP_PENALTY;
+            if gap == 0 {
+                run += 1;
+                total += CONSECUTIVE_BONUS * run;
+            } else {
+                run = 0;
+            }
+        }
+
+        let at_boundary = ci == 0 || matches!(candidate_chars[ci - 1], '_' | '-' | '.' | '/');
+        if at_boundary {
+            total += BOUNDARY_BONUS;
+        }
+
+        last_match = Some(ci);
+        qi += 1;
+    }
+
+    if qi == query_chars.len() {
+        Some(total)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsequence_matches() {
+        assert!(score("rdme", "README.md").is_some());
+        assert!(score("RDME", "readme.md").is_some());
+        assert!(score("xyz", "README.md").is_none());
+    }
+
+    #[test]
+    fn test_out_of_order_does_not_match() {
+        assert!(score("emdr", "README.md").is_none());
+    }
+
+    #[test]
+    fn test_empty_query_matches_everything_with_zero_score() {
+        assert_eq!(score("", "anything.txt"), Some(0));
+    }
+
+    #[test]
+    fn test_consecutive_run_scores_higher_than_scattered() {
+        let consecutive = score("read", "README.md").unwrap();
+        let scattered = score("read", "r_e_a_d.md").unwrap();
+        assert!(consecutive > scattered);
+    }
+
+    #[test]
+    fn test_word_boundary_match_scores_higher() {
+        let boundary = score("d", "xx_d.mp4").unwrap();
+        let mid_word = score("d", "xxxd.mp4").unwrap();
+        assert!(boundary > mid_word);
+    }
+}
@@ -6,11 +6,23 @@
 //! - Application state machine with undo/redo
 //! - Filebrowser REST client
 
+pub mod appearance;
 pub mod auth;
+pub mod blurhash;
+pub mod bookmarks;
+pub mod compression;
 pub mod config;
+pub mod config_watcher;
+pub mod credentials;
 pub mod filebrowser;
+pub mod fuzzy;
+pub mod mime_sniff;
+pub mod pairing;
+pub mod preview;
 pub mod protocol;
+pub mod share;
 pub mod state;
+pub mod totp;
 
 pub use auth::{Claims, JwtAuth, TokenPair};
 pub use config::Config;
@@ -0,0 +1,141 @@
+//! Magic-byte MIME sniffing
+//!
+//! Filebrowser reports a `type` derived from the file extension, which is
+//! wrong for renamed/extensionless files. This looks at the first few bytes
+//! of actual file content instead, for the formats this project's demo data
+//! and thumbnail pipeline care about.
+
+/// Sniff `content`'s MIME type from its leading magic bytes. Returns `None`
+/// for anything not recognized, so callers can fall back to an
+/// extension-based guess.
+pub fn sniff(content: &[u8]) -> Option<&'static str> {
+    let sig = |bytes: &[u8]| content.starts_with(bytes);
+
+    if sig(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some("image/png")
+    } else if sig(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if sig(b"GIF87a") || sig(b"GIF89a") {
+        Some("image/gif")
+    } else if content.len() >= 12 && sig(b"RIFF") && &content[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if sig(b"%PDF-") {
+        Some("application/pdf")
+    } else if sig(&[0x50, 0x4B, 0x03, 0x04]) || sig(&[0x50, 0x4B, 0x05, 0x06]) {
+        Some("application/zip")
+    } else if content.len() >= 12 && &content[4..8] == b"ftyp" {
+        // ISO base media file format: MP4, MOV, and friends all share this
+        // header, distinguished only by the `ftyp` brand that follows -- we
+        // only need to tell "it's an MP4-family container" apart from
+        // everything else, so the brand itself isn't inspected.
+        Some("video/mp4")
+    } else {
+        None
+    }
+}
+
+/// Read an image's pixel dimensions straight out of its header, without
+/// decoding the image itself -- just enough of PNG's `IHDR` chunk and
+/// JPEG's baseline `SOF0`/progressive `SOF2` markers to pull out width/height for the
+/// preview pane. Returns `None` for anything else, or content too short/
+/// malformed to contain the fields it's looking for.
+pub fn image_dimensions(content: &[u8]) -> Option<(u32, u32)> {
+    match sniff(content) {
+        Some("image/png") => png_dimensions(content),
+        Some("image/jpeg") => jpeg_dimensions(content),
+        _ => None,
+    }
+}
+
+/// PNG's `IHDR` is always the first chunk, immediately after the 8-byte
+/// signature: 4-byte length, 4-byte type, then 4-byte width + 4-byte height
+fn png_dimensions(content: &[u8]) -> Option<(u32, u32)> {
+    let ihdr = content.get(8..24)?;
+    if &ihdr[4..8] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(ihdr[8..12].try_into().ok()?);
+    let height = u32::from_be_bytes(ihdr[12..16].try_into().ok()?);
+    Some((width, height))
+}
+
+/// Walk JPEG markers looking for a start-of-frame (baseline `0xC0` or
+/// progressive `0xC2`), which carries height/width right after its length
+fn jpeg_dimensions(content: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2; // skip the SOI marker (0xFFD8)
+
+    while pos + 4 <= content.len() {
+        if content[pos] != 0xFF {
+            pos += 1;
+            continue;
+        }
+        let marker = content[pos + 1];
+        if marker == 0xC0 || marker == 0xC2 {
+            let height = u16::from_be_bytes(content.get(pos + 5..pos + 7)?.try_into().ok()?) as u32;
+            let width = u16::from_be_bytes(content.get(pos + 7..pos + 9)?.try_into().ok()?) as u32;
+            return Some((width, height));
+        }
+
+        let segment_len = u16::from_be_bytes(content.get(pos + 2..pos + 4)?.try_into().ok()?) as usize;
+        pos += 2 + segment_len;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniffs_png_by_magic_bytes() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        png.extend_from_slice(b"rest of file");
+        assert_eq!(sniff(&png), Some("image/png"));
+    }
+
+    #[test]
+    fn test_sniffs_jpeg_by_magic_bytes() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        assert_eq!(sniff(&jpeg), Some("image/jpeg"));
+    }
+
+    #[test]
+    fn test_sniffs_mp4_ftyp_box() {
+        let mut mp4 = vec![0x00, 0x00, 0x00, 0x18];
+        mp4.extend_from_slice(b"ftypmp42");
+        assert_eq!(sniff(&mp4), Some("video/mp4"));
+    }
+
+    #[test]
+    fn test_unrecognized_content_returns_none() {
+        assert_eq!(sniff(b"just some plain text"), None);
+    }
+
+    #[test]
+    fn test_png_dimensions_read_from_ihdr() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        png.extend_from_slice(&13u32.to_be_bytes()); // IHDR length
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&1920u32.to_be_bytes());
+        png.extend_from_slice(&1080u32.to_be_bytes());
+        assert_eq!(image_dimensions(&png), Some((1920, 1080)));
+    }
+
+    #[test]
+    fn test_jpeg_dimensions_read_from_sof0() {
+        let mut jpeg = vec![0xFF, 0xD8]; // SOI
+        jpeg.extend_from_slice(&[0xFF, 0xC0]); // SOF0
+        jpeg.extend_from_slice(&17u16.to_be_bytes()); // segment length
+        jpeg.push(8); // precision
+        jpeg.extend_from_slice(&768u16.to_be_bytes()); // height
+        jpeg.extend_from_slice(&1024u16.to_be_bytes()); // width
+        jpeg.extend_from_slice(&[0; 10]); // component data, unused
+        assert_eq!(image_dimensions(&jpeg), Some((1024, 768)));
+    }
+
+    #[test]
+    fn test_non_image_has_no_dimensions() {
+        assert_eq!(image_dimensions(b"just some plain text"), None);
+    }
+}
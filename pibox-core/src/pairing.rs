@@ -0,0 +1,216 @@
+//! Out-of-band device pairing
+//!
+//! `POST /pair/start` mints a short-lived pairing code and hands back a QR
+//! rendering of the `ws://` URI it encodes. A headless client scans or is
+//! given the code and exchanges it for a real token pair -- over HTTP via
+//! `POST /pair/claim`, or over the WebSocket by sending it as a
+//! `ClientMessage::Pair` in place of `ClientMessage::Login` -- without ever
+//! needing a username or password.
+//!
+//! Separately, [`DevicePairingData`] carries a whole `DeviceConfig` (minus
+//! its password, which stays in the OS keyring -- see [`crate::credentials`])
+//! as a scannable QR code, so a client can be pointed at a server without
+//! typing a `ws://` URL in by hand. See `Config::export_device_qr` /
+//! `Config::import_device_from_str`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DeviceConfig, DeviceType};
+
+/// How long a pairing code stays claimable after it's minted
+pub const PAIRING_CODE_TTL_SECS: u64 = 300;
+
+/// A single pairing code and its lifecycle, stored in the server's
+/// `AppState` until it's claimed or expires
+#[derive(Debug, Clone)]
+pub struct PairingCode {
+    pub code: String,
+    pub created_at: u64,
+    pub consumed: bool,
+}
+
+impl PairingCode {
+    pub fn new(code: String, created_at: u64) -> Self {
+        Self {
+            code,
+            created_at,
+            consumed: false,
+        }
+    }
+
+    /// Whether this code is past its TTL, relative to `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.created_at) > PAIRING_CODE_TTL_SECS
+    }
+
+    /// Whether this code can still be claimed
+    pub fn is_claimable(&self, now: u64) -> bool {
+        !self.consumed && !self.is_expired(now)
+    }
+}
+
+/// The URI a pairing code resolves to: the WebSocket endpoint a scanning
+/// device should connect to, with the code in the fragment so it never ends
+/// up in a request path or query string that gets logged along the way.
+pub fn pairing_uri(host: &str, code: &str) -> String {
+    format!("ws://{}/#{}", host, code)
+}
+
+/// Render `data` as a terminal-printable QR code using the `qrencode` crate.
+pub fn render_qr(data: &str) -> String {
+    use qrencode::{render::unicode, QrCode};
+
+    match QrCode::new(data.as_bytes()) {
+        Ok(code) => code
+            .render::<unicode::Dense1x2>()
+            .quiet_zone(false)
+            .build(),
+        Err(_) => render_qr_placeholder(data),
+    }
+}
+
+/// Fallback block-glyph rendering for data too large to fit a QR code (or,
+/// in principle, any other encoder failure). Kept as `render_qr`'s error
+/// path rather than removed, since a broken pairing flow is worse than an
+/// ugly one.
+pub fn render_qr_placeholder(data: &str) -> String {
+    format!("[QR placeholder] {}", data)
+}
+
+/// The fields of a `DeviceConfig` that are safe to hand to a scanning
+/// device. Deliberately excludes the password -- that's paired separately
+/// via the OS keyring (see [`crate::credentials`]) and must never appear in
+/// a QR code, which can linger on a screen or in a photo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevicePairingData {
+    pub name: String,
+    pub url: String,
+    pub username: Option<String>,
+    pub device_type: DeviceType,
+}
+
+impl From<&DeviceConfig> for DevicePairingData {
+    fn from(device: &DeviceConfig) -> Self {
+        Self {
+            name: device.name.clone(),
+            url: device.url.clone(),
+            username: device.username.clone(),
+            device_type: device.device_type.clone(),
+        }
+    }
+}
+
+impl From<DevicePairingData> for DeviceConfig {
+    fn from(data: DevicePairingData) -> Self {
+        Self {
+            name: data.name,
+            url: data.url,
+            username: data.username,
+            device_type: data.device_type,
+        }
+    }
+}
+
+/// Serialize `device` into the compact JSON blob a pairing QR code encodes.
+pub fn encode_device(device: &DeviceConfig) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&DevicePairingData::from(device))
+}
+
+/// Parse a blob produced by [`encode_device`] (scanned from a QR code) back
+/// into a `DeviceConfig`.
+pub fn decode_device(blob: &str) -> Result<DeviceConfig, serde_json::Error> {
+    serde_json::from_str::<DevicePairingData>(blob).map(DeviceConfig::from)
+}
+
+/// Build the pairing blob a server prints for itself at boot, so a phone or
+/// second client can scan its way to a working `DeviceConfig` instead of
+/// being told a `ws://` URL to type in. `host` should already be resolved to
+/// something reachable from off-box -- `listen_addr` is frequently `0.0.0.0`,
+/// which isn't dialable, so the caller is expected to substitute a real
+/// interface address first.
+pub fn server_pairing_blob(host: &str, port: u16, device_type: DeviceType) -> Result<String, serde_json::Error> {
+    encode_device(&DeviceConfig {
+        name: host.to_string(),
+        url: format!("ws://{}:{}", host, port),
+        username: None,
+        device_type,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fresh_code_is_claimable() {
+        let code = PairingCode::new("abc".to_string(), 1_000);
+        assert!(code.is_claimable(1_010));
+    }
+
+    #[test]
+    fn test_code_expires_after_ttl() {
+        let code = PairingCode::new("abc".to_string(), 1_000);
+        let past_ttl = 1_000 + PAIRING_CODE_TTL_SECS + 1;
+        assert!(code.is_expired(past_ttl));
+        assert!(!code.is_claimable(past_ttl));
+    }
+
+    #[test]
+    fn test_consumed_code_is_not_claimable() {
+        let mut code = PairingCode::new("abc".to_string(), 1_000);
+        code.consumed = true;
+        assert!(!code.is_claimable(1_001));
+    }
+
+    #[test]
+    fn test_pairing_uri_embeds_host_and_code_in_fragment() {
+        let uri = pairing_uri("pibox.local:9280", "deadbeef");
+        assert_eq!(uri, "ws://pibox.local:9280/#deadbeef");
+    }
+
+    #[test]
+    fn test_encode_decode_device_round_trips() {
+        let device = DeviceConfig {
+            name: "nas".to_string(),
+            url: "ws://192.0.2.10:9280".to_string(),
+            username: Some("admin".to_string()),
+            device_type: DeviceType::Nas,
+        };
+
+        let blob = encode_device(&device).unwrap();
+        let decoded = decode_device(&blob).unwrap();
+
+        assert_eq!(decoded.name, device.name);
+        assert_eq!(decoded.url, device.url);
+        assert_eq!(decoded.username, device.username);
+    }
+
+    #[test]
+    fn test_encoded_device_never_contains_a_password() {
+        // DevicePairingData has no password field at all, so there's
+        // nothing for encode_device to leak even if a caller tried.
+        let device = DeviceConfig {
+            name: "nas".to_string(),
+            url: "ws://192.0.2.10:9280".to_string(),
+            username: Some("admin".to_string()),
+            device_type: DeviceType::Nas,
+        };
+
+        let blob = encode_device(&device).unwrap();
+        assert!(!blob.contains("password"));
+    }
+
+    #[test]
+    fn test_server_pairing_blob_builds_reachable_url() {
+        let blob = server_pairing_blob("192.0.2.5", 9280, DeviceType::Generic).unwrap();
+        let decoded = decode_device(&blob).unwrap();
+        assert_eq!(decoded.url, "ws://192.0.2.5:9280");
+        assert!(decoded.username.is_none());
+    }
+
+    #[test]
+    fn test_render_qr_produces_nonempty_output() {
+        let rendered = render_qr("ws://192.0.2.5:9280");
+        assert!(!rendered.is_empty());
+    }
+}
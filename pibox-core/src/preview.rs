@@ -0,0 +1,175 @@
+//! Preview generation for the focused file-tree entry
+//!
+//! [`crate::state::AppState::current_entry`] drives what the TUI's preview
+//! pane shows: a highlighted dump of a text file, a child listing for a
+//! directory, or summary info for anything else. Highlighting here is a
+//! small, dependency-free scanner keyed off file extension (keywords,
+//! strings, comments, numbers); it produces the same [`HighlightedSpan`]
+//! shape a real tokenizer would, so the TUI rendering doesn't need to
+//! change if the scanner is later swapped for something more thorough.
+
+use crate::state::FileEntry;
+
+/// Semantic class of a highlighted span. The TUI layer maps these to actual
+/// colors for the active theme, so adding a theme doesn't touch this module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+/// One styled run of text within a highlighted line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightedSpan {
+    pub text: String,
+    pub kind: HighlightKind,
+}
+
+/// What the preview pane should render for the focused entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Preview {
+    Text { highlighted_lines: Vec<Vec<HighlightedSpan>> },
+    Directory { entries: Vec<FileEntry> },
+    Binary { info: String },
+}
+
+const KEYWORDS: &[&str] = &[
+    "fn", "let", "mut", "if", "else", "match", "struct", "enum", "impl", "pub", "return", "for",
+    "while", "loop", "use", "mod", "const", "async", "await", "trait", "self", "Self", "true",
+    "false", "def", "class", "import", "from", "in", "as", "function", "var", "const",
+];
+
+/// Whether `mime_type` (as reported by [`FileEntry::mime_type`]) looks like
+/// text worth syntax-highlighting rather than treating as opaque binary
+pub fn is_probably_text(mime_type: Option<&str>) -> bool {
+    match mime_type {
+        Some(mime) => mime.starts_with("text/") || matches!(mime, "application/json" | "application/toml" | "application/x-yaml"),
+        None => false,
+    }
+}
+
+/// Build a human-readable summary for a binary entry (images, video,
+/// archives, anything not worth highlighting as text). `dimensions`, when
+/// known, is the image's pixel width/height (see
+/// [`crate::mime_sniff::image_dimensions`]) -- `None` for non-images or
+/// when the content needed to read them hasn't been fetched
+pub fn describe_binary(entry: &FileEntry, dimensions: Option<(u32, u32)>) -> String {
+    match (entry.mime_type.as_deref(), dimensions) {
+        (Some(mime), Some((width, height))) => {
+            format!("{} {}x{} ({} bytes)", mime, width, height, entry.size)
+        }
+        (Some(mime), None) => format!("{} ({} bytes)", mime, entry.size),
+        (None, _) => format!("{} bytes", entry.size),
+    }
+}
+
+/// Highlight `content` as `extension`-flavored source, one span list per line
+pub fn build_text_preview(content: &str, extension: &str) -> Preview {
+    let highlighted_lines = content.lines().map(|line| highlight_line(line, extension)).collect();
+    Preview::Text { highlighted_lines }
+}
+
+fn highlight_line(line: &str, extension: &str) -> Vec<HighlightedSpan> {
+    if let Some(prefix) = comment_prefix(extension) {
+        if line.trim_start().starts_with(prefix) {
+            return vec![HighlightedSpan { text: line.to_string(), kind: HighlightKind::Comment }];
+        }
+    }
+
+    let mut spans = Vec::new();
+    let mut rest = line;
+
+    while !rest.is_empty() {
+        if rest.starts_with('"') {
+            let end = rest[1..].find('"').map(|i| i + 2).unwrap_or(rest.len());
+            spans.push(HighlightedSpan { text: rest[..end].to_string(), kind: HighlightKind::String });
+            rest = &rest[end..];
+            continue;
+        }
+
+        let word_len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+        if word_len > 0 {
+            let word = &rest[..word_len];
+            let kind = if KEYWORDS.contains(&word) {
+                HighlightKind::Keyword
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                HighlightKind::Number
+            } else {
+                HighlightKind::Plain
+            };
+            spans.push(HighlightedSpan { text: word.to_string(), kind });
+            rest = &rest[word_len..];
+            continue;
+        }
+
+        let punct_len = rest
+            .find(|c: char| c.is_alphanumeric() || c == '_' || c == '"')
+            .unwrap_or(rest.len())
+            .max(1);
+        spans.push(HighlightedSpan { text: rest[..punct_len].to_string(), kind: HighlightKind::Plain });
+        rest = &rest[punct_len..];
+    }
+
+    spans
+}
+
+fn comment_prefix(extension: &str) -> Option<&'static str> {
+    match extension {
+        "rs" | "js" | "ts" | "go" | "c" | "cpp" | "h" | "java" => Some("//"),
+        "py" | "sh" | "toml" | "yaml" | "yml" => Some("#"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::FileType;
+
+    #[test]
+    fn test_is_probably_text_matches_text_and_structured_mimes() {
+        assert!(is_probably_text(Some("text/plain")));
+        assert!(is_probably_text(Some("application/json")));
+        assert!(!is_probably_text(Some("image/png")));
+        assert!(!is_probably_text(None));
+    }
+
+    #[test]
+    fn test_highlight_line_tags_keyword_string_and_number() {
+        let spans = highlight_line(r#"let x = "hi" + 42"#, "rs");
+        assert_eq!(spans[0], HighlightedSpan { text: "let".to_string(), kind: HighlightKind::Keyword });
+        assert!(spans.iter().any(|s| s.kind == HighlightKind::String && s.text == "\"hi\""));
+        assert!(spans.iter().any(|s| s.kind == HighlightKind::Number && s.text == "42"));
+    }
+
+    #[test]
+    fn test_highlight_line_tags_whole_comment_line() {
+        let spans = highlight_line("  // a note", "rs");
+        assert_eq!(spans, vec![HighlightedSpan { text: "  // a note".to_string(), kind: HighlightKind::Comment }]);
+    }
+
+    #[test]
+    fn test_build_text_preview_produces_one_span_list_per_line() {
+        match build_text_preview("let a = 1\nlet b = 2", "rs") {
+            Preview::Text { highlighted_lines } => assert_eq!(highlighted_lines.len(), 2),
+            other => panic!("expected Text preview, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_describe_binary_includes_mime_and_size() {
+        let entry = FileEntry {
+            name: "photo.jpg".to_string(),
+            path: "/photo.jpg".to_string(),
+            file_type: FileType::File,
+            size: 2048,
+            modified: 0,
+            mime_type: Some("image/jpeg".to_string()),
+        };
+        assert_eq!(describe_binary(&entry, None), "image/jpeg (2048 bytes)");
+        assert_eq!(describe_binary(&entry, Some((800, 600))), "image/jpeg 800x600 (2048 bytes)");
+    }
+}
@@ -12,44 +12,152 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
-    /// Authenticate with username/password
-    Login { username: String, password: String },
+    /// Authenticate with username/password, plus a TOTP code if the account
+    /// has 2FA enrolled
+    Login {
+        username: String,
+        password: String,
+        #[serde(default)]
+        totp_code: Option<String>,
+    },
 
     /// Refresh access token using refresh token
     RefreshToken { refresh_token: String },
 
+    /// Claim a pairing code minted by `POST /pair/start`, in place of
+    /// `Login` for clients that can't prompt for a username/password
+    Pair { code: String },
+
     /// List directory contents
     ListDir { path: String },
 
     /// Download file (server sends FileContent response)
     Download { path: String },
 
-    /// Upload file
+    /// Download a byte range of a file as a stream of `FileChunk`s rather
+    /// than one buffered `FileContent`, so large files don't have to be held
+    /// in memory whole and a dropped transfer can resume by re-requesting
+    /// from the last acked `offset`
+    DownloadRange {
+        path: String,
+        offset: u64,
+        len: u64,
+        transfer_id: String,
+    },
+
+    /// Upload file in one message. Simple and fine for small files, but a
+    /// dropped connection mid-transfer leaves nothing written and the whole
+    /// file has to be resent -- see `BeginUpload` for large/resumable files
     Upload {
         path: String,
         #[serde(with = "base64_bytes")]
         content: Vec<u8>,
+        /// Codec `content` was compressed with before base64-encoding, if
+        /// any. Absent (or `Identity`) means `content` is the raw file
+        /// bytes; the receiver decompresses before writing it to disk.
+        #[serde(default)]
+        encoding: Option<TransferEncoding>,
     },
 
-    /// Delete file or directory
+    /// Start a resumable chunked upload. The server stages bytes at a temp
+    /// path derived from `upload_id` rather than writing directly to `path`,
+    /// so a dropped connection never leaves a truncated file at the
+    /// destination; follow with one or more `UploadChunk`s and a final
+    /// `CommitUpload`
+    BeginUpload {
+        path: String,
+        total_size: u64,
+        upload_id: String,
+    },
+
+    /// Append `data` to the upload started by `upload_id`, at `offset` bytes
+    /// into the file. `offset` must match the number of bytes already
+    /// staged -- a mismatch (e.g. after a reconnect) is rejected with a
+    /// `ChunkAck` reporting the authoritative `next_offset` to resume from,
+    /// rather than silently accepting out-of-order data
+    UploadChunk {
+        upload_id: String,
+        offset: u64,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+
+    /// Finish the upload started by `upload_id`: atomically rename the
+    /// staged temp file onto its final path and broadcast `FsEvent::Created`.
+    /// Fails if fewer bytes were staged than `total_size` declared in
+    /// `BeginUpload`
+    CommitUpload { upload_id: String },
+
+    /// Move file or directory to the trash (reversible; see `Restore`)
     Delete { path: String },
 
     /// Rename/move file or directory
     Rename { from: String, to: String },
 
+    /// Move a previously-trashed file back to its original path
+    Restore { trash_path: String, original_path: String },
+
     /// Create directory
     Mkdir { path: String },
 
     /// Report client capabilities (for adaptive offloading)
     Capabilities(ClientCapabilities),
 
-    /// Response to offload request
+    /// Ask the server to route `task` to some other capable, connected
+    /// client rather than doing it locally. The server replies with either
+    /// a `ServerMessage::OffloadComplete` once a worker finishes it, or a
+    /// `ServerMessage::Error` if no worker is available or it times out.
+    OffloadRequest { task_id: String, task: OffloadTask },
+
+    /// A worker's response to the `ServerMessage::OffloadRequest` it was
+    /// dispatched, routed back to whichever client originally sent the
+    /// matching `ClientMessage::OffloadRequest`
     OffloadResult {
         task_id: String,
         #[serde(with = "base64_bytes")]
         result: Vec<u8>,
+        /// Codec `result` was compressed with before base64-encoding, if any
+        #[serde(default)]
+        encoding: Option<TransferEncoding>,
     },
 
+    /// Scope `FsEvent` broadcasts to paths under `path`. A client that's
+    /// never sent `Watch` receives no `FsEvent`s at all, rather than the
+    /// firehose of every change on the server.
+    Watch { path: String },
+
+    /// Stop scoping `FsEvent` broadcasts to `path`
+    Unwatch { path: String },
+
+    /// Mint a revocable, unauthenticated download link for a single file,
+    /// good for `expires_in` seconds and/or `download_limit` downloads
+    CreateShare {
+        path: String,
+        expires_in: u64,
+        #[serde(default)]
+        download_limit: Option<u32>,
+    },
+
+    /// Invalidate a share link created by this client, before it would
+    /// otherwise expire or run out of downloads
+    RevokeShare { token: String },
+
+    /// List this client's outstanding share links
+    ListShares,
+
+    /// Request a downscaled preview of an image, or a representative frame
+    /// of a video, bounded to `max_dim` on its longest side
+    Thumbnail { path: String, max_dim: u32 },
+
+    /// Enroll the authenticated account in TOTP 2FA. The server replies with
+    /// `ServerMessage::TotpEnrolled` carrying an `otpauth://` URI for an
+    /// authenticator app to scan or import; the enrollment takes effect
+    /// immediately, so the next `Login` for this account requires a code.
+    EnrollTotp,
+
+    /// Remove the authenticated account's TOTP enrollment, disabling 2FA
+    DisableTotp,
+
     /// Ping for keepalive
     Ping,
 }
@@ -64,6 +172,9 @@ pub enum ServerMessage {
     /// Authentication failed
     AuthError { message: String },
 
+    /// Password was correct but a TOTP code is also required to finish login
+    TwoFactorRequired,
+
     /// Directory listing
     DirListing {
         path: String,
@@ -76,23 +187,105 @@ pub enum ServerMessage {
         #[serde(with = "base64_bytes")]
         content: Vec<u8>,
         mime_type: Option<String>,
+        /// Codec `content` was compressed with before base64-encoding, if
+        /// any. Negotiated via `ClientCapabilities::can_compress` and
+        /// chosen by the server based on load; the receiver decompresses
+        /// `content` before handing it to anything that cares about the
+        /// file's actual bytes.
+        #[serde(default)]
+        encoding: Option<TransferEncoding>,
+
+        /// `max-age` (seconds) the Filebrowser backend's `Cache-Control`
+        /// response header reported for this file, if any, so a client can
+        /// set its own local expiry instead of treating every download as
+        /// immediately stale.
+        #[serde(default)]
+        cache_max_age: Option<u64>,
+    },
+
+    /// Reported once, up front, at the start of a `DownloadRange` transfer
+    /// so the client can size a progress bar and knows what to ask for on
+    /// resume, before any `FileChunk`s arrive
+    FileContentMeta {
+        transfer_id: String,
+        size: u64,
+        mime_type: Option<String>,
+    },
+
+    /// Acknowledges a `BeginUpload` or `UploadChunk`. `next_offset` is the
+    /// authoritative number of bytes staged so far -- the client sends its
+    /// next chunk starting there, whether or not the chunk it just sent
+    /// (if any) was accepted
+    ChunkAck { upload_id: String, next_offset: u64 },
+
+    /// One chunk of a `DownloadRange` transfer. `offset` is the byte offset
+    /// of `data` within the file, monotonically increasing; `eof` is set on
+    /// the chunk that reaches the end of the file (not just the end of the
+    /// requested window), so the client knows there's nothing left to resume
+    FileChunk {
+        transfer_id: String,
+        offset: u64,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+        eof: bool,
     },
 
     /// Operation completed successfully
     OpSuccess { op: String, path: String },
 
+    /// A `Delete` moved the file to the trash rather than unlinking it;
+    /// the client keeps `trash_path` so it can issue a `Restore` later
+    Trashed { original_path: String, trash_path: String },
+
     /// Operation failed
     OpError { op: String, path: String, message: String },
 
     /// Server load report (for adaptive behavior)
     Load(ServerLoad),
 
+    /// Progress update for an in-flight chunked transfer, so the client can
+    /// render a progress bar while `FileContent` is still being assembled
+    TransferProgress {
+        path: String,
+        bytes_sent: u64,
+        total_size: u64,
+    },
+
     /// Request client to handle a task (offloading)
     OffloadRequest {
         task_id: String,
         task: OffloadTask,
     },
 
+    /// Delivered to the client that sent the matching `ClientMessage::OffloadRequest`,
+    /// once the worker it was routed to replies with an `OffloadResult`
+    OffloadComplete {
+        task_id: String,
+        #[serde(with = "base64_bytes")]
+        result: Vec<u8>,
+    },
+
+    /// A `ClientMessage::CreateShare` succeeded; `url` is the path a client
+    /// hits unauthenticated (relative to this server) to download the file
+    ShareCreated { token: String, url: String },
+
+    /// Response to `ClientMessage::ListShares`
+    ShareList { shares: Vec<ShareSummary> },
+
+    /// Response to `ClientMessage::Thumbnail`
+    ThumbnailData {
+        path: String,
+        mime_type: String,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+
+    /// Response to `ClientMessage::EnrollTotp`
+    TotpEnrolled { provisioning_uri: String },
+
+    /// Response to `ClientMessage::DisableTotp`
+    TotpDisabled,
+
     /// File system event (real-time sync)
     FsEvent(FsEvent),
 
@@ -122,12 +315,23 @@ pub struct FileEntryResponse {
     pub mime_type: Option<String>,
 }
 
+/// A single outstanding share link, as reported by `ServerMessage::ShareList`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareSummary {
+    pub token: String,
+    pub path: String,
+    pub expires_at: u64,
+    pub downloads_remaining: Option<u32>,
+}
+
 /// Server resource load for adaptive behavior
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerLoad {
     pub cpu_percent: f32,
     pub ram_free_mb: u64,
     pub io_busy: bool,
+    /// CPU temperature in Celsius, when a thermal zone could be read
+    pub cpu_temp_c: Option<f32>,
     /// Suggested actions based on load
     pub hints: Vec<LoadHint>,
 }
@@ -144,6 +348,33 @@ pub enum LoadHint {
     SearchLocally,
     /// Server is recovering, operations may be slow
     Recovering,
+    /// Server is thermal-throttling, heavy ops should move to the client
+    ThermalThrottle,
+    /// Server has CPU headroom to spare and would rather trade it for
+    /// bandwidth -- clients should set `encoding` on `Upload`/`OffloadResult`
+    /// payloads rather than sending them raw
+    PreferCompression,
+}
+
+/// Compression codec applied to a transfer payload (see
+/// `ServerMessage::FileContent`, `ClientMessage::Upload`,
+/// `ClientMessage::OffloadResult`) before it's base64-encoded, negotiated via
+/// `ClientCapabilities::can_compress`. Travels alongside the payload rather
+/// than being inferred, since the receiver has to know the codec before it
+/// can decode `content`/`result`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferEncoding {
+    /// Payload is the raw, uncompressed bytes
+    Identity,
+    /// DEFLATE, via `flate2` -- the same codec `pibox-server::compress` uses
+    /// for whole-frame compression, just applied to one message's payload
+    Deflate,
+    /// Accepted for forward compatibility but not implemented in this tree:
+    /// no `zstd` crate is available here (the same constraint noted in
+    /// `pibox-server::thumbnail`), so it's currently produced and consumed
+    /// identically to `Identity`.
+    Zstd,
 }
 
 /// Client capabilities for offload decisions
@@ -176,6 +407,16 @@ pub enum OffloadTask {
         query: String,
         paths: Vec<String>,
     },
+    /// Generate a tiny BlurHash placeholder string for progressive image
+    /// loading. `components_x`/`components_y` (1-9 each) control how many
+    /// frequency components the hash encodes along each axis.
+    BlurHash {
+        path: String,
+        #[serde(with = "base64_bytes")]
+        source: Vec<u8>,
+        components_x: u32,
+        components_y: u32,
+    },
 }
 
 /// File system event for real-time sync
@@ -188,6 +429,19 @@ pub enum FsEvent {
     Renamed { from: String, to: String },
 }
 
+impl FsEvent {
+    /// Path(s) this event touches, used to match it against a client's
+    /// watched paths. `Renamed` touches both its old and new location.
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            FsEvent::Created { path, .. } => vec![path.as_str()],
+            FsEvent::Modified { path } => vec![path.as_str()],
+            FsEvent::Deleted { path } => vec![path.as_str()],
+            FsEvent::Renamed { from, to } => vec![from.as_str(), to.as_str()],
+        }
+    }
+}
+
 /// Helper module for base64 encoding of byte arrays in JSON
 mod base64_bytes {
     use serde::{Deserialize, Deserializer, Serializer};
@@ -243,4 +497,121 @@ mod tests {
         assert!(json.contains("load"));
         assert!(json.contains("throttle_transfers"));
     }
+
+    #[test]
+    fn test_fs_event_paths_covers_both_sides_of_a_rename() {
+        let event = FsEvent::Renamed {
+            from: "/a.txt".to_string(),
+            to: "/b.txt".to_string(),
+        };
+        assert_eq!(event.paths(), vec!["/a.txt", "/b.txt"]);
+    }
+
+    #[test]
+    fn test_offload_request_round_trips_through_json() {
+        let msg = ClientMessage::OffloadRequest {
+            task_id: "t1".to_string(),
+            task: OffloadTask::Search {
+                query: "foo".to_string(),
+                paths: vec!["/docs".to_string()],
+            },
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        match serde_json::from_str::<ClientMessage>(&json).unwrap() {
+            ClientMessage::OffloadRequest { task_id, task } => {
+                assert_eq!(task_id, "t1");
+                assert!(matches!(task, OffloadTask::Search { .. }));
+            }
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_create_share_defaults_to_unlimited_downloads() {
+        let json = r#"{"type":"create_share","path":"/a.txt","expires_in":60}"#;
+        match serde_json::from_str::<ClientMessage>(json).unwrap() {
+            ClientMessage::CreateShare { download_limit, .. } => assert_eq!(download_limit, None),
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_thumbnail_request_round_trips_through_json() {
+        let json = serde_json::to_string(&ClientMessage::Thumbnail {
+            path: "/photo.jpg".to_string(),
+            max_dim: 256,
+        })
+        .unwrap();
+        assert!(matches!(
+            serde_json::from_str::<ClientMessage>(&json).unwrap(),
+            ClientMessage::Thumbnail { max_dim: 256, .. }
+        ));
+    }
+
+    #[test]
+    fn test_upload_chunk_round_trips_offset_and_data() {
+        let json = serde_json::to_string(&ClientMessage::UploadChunk {
+            upload_id: "u1".to_string(),
+            offset: 4096,
+            data: vec![1, 2, 3],
+        })
+        .unwrap();
+        match serde_json::from_str::<ClientMessage>(&json).unwrap() {
+            ClientMessage::UploadChunk { upload_id, offset, data } => {
+                assert_eq!(upload_id, "u1");
+                assert_eq!(offset, 4096);
+                assert_eq!(data, vec![1, 2, 3]);
+            }
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_upload_defaults_to_no_encoding() {
+        let json = r#"{"type":"upload","path":"/a.txt","content":"aGk="}"#;
+        match serde_json::from_str::<ClientMessage>(json).unwrap() {
+            ClientMessage::Upload { encoding, .. } => assert_eq!(encoding, None),
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_file_content_round_trips_with_encoding() {
+        let json = serde_json::to_string(&ServerMessage::FileContent {
+            path: "/a.txt".to_string(),
+            content: vec![1, 2, 3],
+            mime_type: Some("text/plain".to_string()),
+            encoding: Some(TransferEncoding::Deflate),
+            cache_max_age: Some(3600),
+        })
+        .unwrap();
+        assert!(matches!(
+            serde_json::from_str::<ServerMessage>(&json).unwrap(),
+            ServerMessage::FileContent { encoding: Some(TransferEncoding::Deflate), .. }
+        ));
+    }
+
+    #[test]
+    fn test_file_content_defaults_to_no_cache_max_age() {
+        let json = r#"{"type":"file_content","path":"/a.txt","content":"aGk="}"#;
+        match serde_json::from_str::<ServerMessage>(json).unwrap() {
+            ServerMessage::FileContent { cache_max_age, .. } => assert_eq!(cache_max_age, None),
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_watch_and_unwatch_round_trip_through_json() {
+        let json = serde_json::to_string(&ClientMessage::Watch { path: "/Music".to_string() }).unwrap();
+        assert!(matches!(
+            serde_json::from_str::<ClientMessage>(&json).unwrap(),
+            ClientMessage::Watch { path } if path == "/Music"
+        ));
+
+        let json = serde_json::to_string(&ClientMessage::Unwatch { path: "/Music".to_string() }).unwrap();
+        assert!(matches!(
+            serde_json::from_str::<ClientMessage>(&json).unwrap(),
+            ClientMessage::Unwatch { path } if path == "/Music"
+        ));
+    }
 }
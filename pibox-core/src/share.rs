@@ -0,0 +1,70 @@
+//! Time-limited, capability-scoped public share links for individual files
+//!
+//! A share link lets an authenticated client hand out a single-file
+//! download URL that works without authentication, expires on its own, and
+//! can be capped to a fixed number of downloads -- see
+//! `ClientMessage::CreateShare` / `GET /share/:token`.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub token: String,
+    pub path: String,
+    /// Connection id of the client that created the link, so only it can
+    /// revoke or list it
+    pub owner: String,
+    pub expires_at: u64,
+    /// Downloads left before the link stops working, or `None` for unlimited
+    pub downloads_remaining: Option<u32>,
+}
+
+impl ShareLink {
+    pub fn new(token: String, path: String, owner: String, created_at: u64, expires_in: u64, download_limit: Option<u32>) -> Self {
+        Self {
+            token,
+            path,
+            owner,
+            expires_at: created_at + expires_in,
+            downloads_remaining: download_limit,
+        }
+    }
+
+    /// Whether the link can still serve a download right now: not expired,
+    /// and (if capped) still has downloads left
+    pub fn is_usable(&self, now: u64) -> bool {
+        now < self.expires_at && self.downloads_remaining != Some(0)
+    }
+
+    /// Consume one download if the link is still usable, returning whether
+    /// it succeeded
+    pub fn consume(&mut self, now: u64) -> bool {
+        if !self.is_usable(now) {
+            return false;
+        }
+        if let Some(remaining) = self.downloads_remaining.as_mut() {
+            *remaining -= 1;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unexpired_unlimited_link_is_usable() {
+        let link = ShareLink::new("tok".into(), "/a.txt".into(), "owner".into(), 1000, 60, None);
+        assert!(link.is_usable(1030));
+        assert!(!link.is_usable(1100));
+    }
+
+    #[test]
+    fn test_limited_link_expires_after_download_limit() {
+        let mut link = ShareLink::new("tok".into(), "/a.txt".into(), "owner".into(), 1000, 60, Some(1));
+        assert!(link.consume(1010));
+        assert!(!link.is_usable(1020));
+        assert!(!link.consume(1020));
+    }
+}
@@ -6,7 +6,9 @@
 //! - Virtual file tree for memory efficiency
 
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+
+use crate::preview::Preview;
 
 /// Maximum undo history size (to bound memory usage)
 const MAX_UNDO_HISTORY: usize = 50;
@@ -20,7 +22,7 @@ pub enum FileType {
 }
 
 /// A file or directory entry
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FileEntry {
     pub name: String,
     pub path: String,
@@ -67,6 +69,9 @@ pub enum InputMode {
     Rename,
     /// Confirmation prompt
     Confirm(ConfirmAction),
+    /// Bookmark quick-jump overlay, waiting for the key naming the
+    /// bookmark to set or jump to
+    Bookmark(BookmarkAction),
 }
 
 /// Actions requiring confirmation
@@ -76,6 +81,13 @@ pub enum ConfirmAction {
     Overwrite(String),
 }
 
+/// Which bookmark operation the overlay is waiting on a key for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarkAction {
+    Set,
+    Jump,
+}
+
 /// Connection state to server
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum ConnectionState {
@@ -86,6 +98,59 @@ pub enum ConnectionState {
     Reconnecting { attempt: u32 },
 }
 
+/// A vim-style operator waiting for a motion to apply to (`d`/`y`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Yank,
+}
+
+/// A cursor motion, composed from [`AppState::feed_key`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Down,
+    Up,
+    Top,
+    Bottom,
+    /// The entry under the cursor, i.e. what `dd`/`yy` act on
+    Line,
+    WordForward,
+    WordBack,
+    WordEnd,
+}
+
+/// Result of feeding one key through the operator-pending state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    /// Still accumulating a count, `g`-prefix, register prefix, or operator;
+    /// nothing to do yet
+    Pending,
+    /// A bare motion, to be applied `count` times
+    Move { motion: Motion, count: usize },
+    /// An operator applied to the range `motion` covers, repeated `count`
+    /// times, targeting `register` (the unnamed register if `None`, e.g. no
+    /// `"a`-style prefix was given)
+    Operate {
+        operator: Operator,
+        motion: Motion,
+        count: usize,
+        register: Option<char>,
+    },
+}
+
+/// Register key used when no explicit `"<name>` prefix is given, mirroring
+/// vim's unnamed register
+pub const UNNAMED_REGISTER: char = '"';
+
+/// One named clipboard slot: the paths it holds, and whether they were cut
+/// (moved) or yanked (copied) -- paste uses this to decide whether to
+/// duplicate the files or move them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    pub paths: Vec<String>,
+    pub cut: bool,
+}
+
 /// Application state
 #[derive(Debug, Clone)]
 pub struct AppState {
@@ -115,9 +180,45 @@ pub struct AppState {
     // Pending operations
     pub pending_ops: Vec<PendingOp>,
 
+    // Operator-pending input (vim-style counts/operators/motions/registers),
+    // fed one key at a time via `feed_key`
+    pending_count: Option<usize>,
+    pending_operator: Option<(Operator, usize)>,
+    pending_g: bool,
+    awaiting_register: bool,
+    pending_register: Option<char>,
+
+    // Named yank/paste registers (vim's `"a`-style prefix), keyed by
+    // register name; `UNNAMED_REGISTER` holds whatever was last yanked or
+    // deleted without an explicit prefix
+    registers: HashMap<char, Register>,
+
     // Undo/redo
     undo_stack: VecDeque<UndoEntry>,
     redo_stack: Vec<UndoEntry>,
+
+    /// Unfiltered directory listing; `entries` holds the fuzzy-filtered view
+    /// while `input_mode` is `Search`
+    pub all_entries: Vec<FileEntry>,
+
+    /// Whether the preview pane is shown alongside the listing
+    pub show_preview: bool,
+    /// Preview for the entry it was last loaded for, so the TUI can tell
+    /// whether a reload is due without re-deriving it every frame
+    pub preview: Option<PreviewState>,
+
+    /// Child listing of the current directory's parent, for the
+    /// Miller-column parent pane. Empty until a parent listing is fetched.
+    pub parent_entries: Vec<FileEntry>,
+}
+
+/// A loaded [`Preview`], tagged with the entry it was built for. Compared
+/// against `current_entry()` to decide whether the preview is stale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewState {
+    pub path: String,
+    pub modified: i64,
+    pub preview: Preview,
 }
 
 /// Status message severity
@@ -143,6 +244,22 @@ pub enum OpType {
     Download { path: String, size: u64 },
     Delete { paths: Vec<String> },
     Rename { from: String, to: String },
+    /// Moving previously-trashed files back to their original paths
+    TrashRestore { entries: Vec<TrashEntry> },
+}
+
+/// One trashed file: where it used to live, and where the trash now holds it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub original_path: String,
+    pub trash_path: String,
+}
+
+/// Enough information to reverse a batch delete that trashed rather than
+/// unlinked files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestorePlan {
+    pub entries: Vec<TrashEntry>,
 }
 
 /// Entry in undo history
@@ -157,7 +274,9 @@ enum UndoAction {
     Navigate { from: String },
     CursorMove { from: usize },
     Selection { previous: Vec<usize> },
-    // File ops are not undoable (would require server-side support)
+    /// A batch delete that trashed files instead of unlinking them; undo
+    /// restores them, redo re-trashes them
+    FileOp { restore: RestorePlan },
 }
 
 impl Default for AppState {
@@ -184,8 +303,21 @@ impl Default for AppState {
 
             pending_ops: Vec::new(),
 
+            pending_count: None,
+            pending_operator: None,
+            pending_g: false,
+            awaiting_register: false,
+            pending_register: None,
+            registers: HashMap::new(),
+
             undo_stack: VecDeque::new(),
             redo_stack: Vec::new(),
+
+            all_entries: Vec::new(),
+
+            show_preview: true,
+            preview: None,
+            parent_entries: Vec::new(),
         }
     }
 }
@@ -205,11 +337,34 @@ impl AppState {
         }, "navigate");
 
         self.current_path = path;
+        self.all_entries = entries.clone();
         self.entries = entries;
         self.cursor = 0;
         self.scroll_offset = 0;
         self.selected.clear();
         self.selection_anchor = None;
+        self.parent_entries.clear();
+    }
+
+    /// Replace the listing for the directory already being viewed, as
+    /// opposed to `set_entries`, which is for navigating to a new one.
+    /// Preserves the cursor on whichever entry shares the focused entry's
+    /// name if it still exists in the new listing, so an external change
+    /// picked up by the filesystem watcher doesn't yank the cursor back to
+    /// the top.
+    pub fn refresh_entries(&mut self, entries: Vec<FileEntry>) {
+        let focused_name = self.current_entry().map(|e| e.name.clone());
+
+        self.all_entries = entries.clone();
+        self.entries = entries;
+
+        self.cursor = focused_name
+            .and_then(|name| self.entries.iter().position(|e| e.name == name))
+            .unwrap_or(0)
+            .min(self.entries.len().saturating_sub(1));
+
+        self.selected.retain(|&i| i < self.entries.len());
+        self.ensure_cursor_visible();
     }
 
     /// Move cursor down
@@ -263,6 +418,248 @@ impl AppState {
         self.ensure_cursor_visible();
     }
 
+    /// Move forward to the next entry that starts a new "word" group, where
+    /// a group is a run of entries sharing the same leading alphanumeric
+    /// token (e.g. `photo1.jpg`, `photo2.jpg` are one word) — vim's `w`
+    /// applied to entry names instead of text.
+    pub fn cursor_word_forward(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.push_undo(UndoAction::CursorMove { from: self.cursor }, "cursor");
+        self.cursor = self.next_word_boundary(self.cursor);
+        self.ensure_cursor_visible();
+    }
+
+    /// Move back to the previous word group (vim's `b`)
+    pub fn cursor_word_back(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.push_undo(UndoAction::CursorMove { from: self.cursor }, "cursor");
+        self.cursor = self.prev_word_boundary(self.cursor);
+        self.ensure_cursor_visible();
+    }
+
+    /// Move to the end of the current word group, or the next one if
+    /// already there (vim's `e`)
+    pub fn cursor_word_end(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.push_undo(UndoAction::CursorMove { from: self.cursor }, "cursor");
+        self.cursor = self.word_end_boundary(self.cursor);
+        self.ensure_cursor_visible();
+    }
+
+    /// Feed one normal-mode key through the operator-pending state machine:
+    /// digits accumulate a count, `"` waits for a register name, `g` waits
+    /// for a second `g`, and `d`/`y` wait for a motion to apply to. Returns
+    /// what the caller should now do.
+    pub fn feed_key(&mut self, c: char) -> KeyOutcome {
+        if self.awaiting_register {
+            self.awaiting_register = false;
+            if c.is_ascii_alphabetic() {
+                self.pending_register = Some(c);
+                return KeyOutcome::Pending;
+            }
+            // Not a valid register name; abandon the prefix and fall
+            // through to handle `c` as an ordinary key.
+        }
+
+        if c == '"' && self.pending_operator.is_none() {
+            self.awaiting_register = true;
+            return KeyOutcome::Pending;
+        }
+
+        if let Some(d) = c.to_digit(10) {
+            if d > 0 || self.pending_count.is_some() {
+                self.pending_count = Some(self.pending_count.unwrap_or(0) * 10 + d as usize);
+                return KeyOutcome::Pending;
+            }
+        }
+
+        if c == 'g' {
+            return if self.pending_g {
+                self.pending_g = false;
+                self.resolve_motion(Motion::Top)
+            } else {
+                self.pending_g = true;
+                KeyOutcome::Pending
+            };
+        }
+        self.pending_g = false;
+
+        if c == 'd' || c == 'y' {
+            let op = if c == 'd' { Operator::Delete } else { Operator::Yank };
+
+            if let Some((pending_op, op_count)) = self.pending_operator {
+                if pending_op == op {
+                    self.pending_operator = None;
+                    let count = op_count * self.pending_count.take().unwrap_or(1);
+                    return KeyOutcome::Operate {
+                        operator: op,
+                        motion: Motion::Line,
+                        count,
+                        register: self.pending_register.take(),
+                    };
+                }
+            }
+
+            let count = self.pending_count.take().unwrap_or(1);
+            self.pending_operator = Some((op, count));
+            return KeyOutcome::Pending;
+        }
+
+        let motion = match c {
+            'j' => Motion::Down,
+            'k' => Motion::Up,
+            'G' => Motion::Bottom,
+            'w' => Motion::WordForward,
+            'b' => Motion::WordBack,
+            'e' => Motion::WordEnd,
+            _ => {
+                self.reset_pending();
+                return KeyOutcome::Pending;
+            }
+        };
+
+        self.resolve_motion(motion)
+    }
+
+    /// Abandon any pending count, operator, register prefix, or `g`-prefix
+    /// (e.g. on Esc, or an unrecognized key arriving mid-sequence)
+    pub fn reset_pending(&mut self) {
+        self.pending_count = None;
+        self.pending_operator = None;
+        self.pending_g = false;
+        self.awaiting_register = false;
+        self.pending_register = None;
+    }
+
+    /// Consume any register armed via a `"` prefix (e.g. the `a` in `"ap`)
+    /// that hasn't yet been claimed by an operator. Used by actions that
+    /// resolve outside the vim-grammar state machine, like paste.
+    pub fn take_pending_register(&mut self) -> Option<char> {
+        self.pending_register.take()
+    }
+
+    /// Store `paths` into `register` (or the unnamed register if `None`),
+    /// tagged as cut or yanked so a later paste can decide whether to move
+    /// or duplicate them.
+    pub fn yank_to_register(&mut self, register: Option<char>, paths: Vec<String>, cut: bool) {
+        self.registers.insert(register.unwrap_or(UNNAMED_REGISTER), Register { paths, cut });
+    }
+
+    /// Look up what a register currently holds
+    pub fn register_contents(&self, register: Option<char>) -> Option<&Register> {
+        self.registers.get(&register.unwrap_or(UNNAMED_REGISTER))
+    }
+
+    /// Format all non-empty registers for the `:reg` command
+    pub fn format_registers(&self) -> String {
+        if self.registers.is_empty() {
+            return "No registers".to_string();
+        }
+
+        let mut names: Vec<&char> = self.registers.keys().collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let reg = &self.registers[name];
+                let kind = if reg.cut { "cut" } else { "yank" };
+                format!("\"{} ({}): {}", name, kind, reg.paths.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// Compute the entry index a motion would land on after `count` repeats,
+    /// without moving the cursor — used by operators to select the range a
+    /// motion covers before deleting/yanking it.
+    pub fn motion_target(&self, motion: Motion, count: usize) -> usize {
+        if self.entries.is_empty() {
+            return self.cursor;
+        }
+        let last = self.entries.len() - 1;
+
+        match motion {
+            Motion::Line => (self.cursor + count.saturating_sub(1)).min(last),
+            Motion::Down => (self.cursor + count).min(last),
+            Motion::Up => self.cursor.saturating_sub(count),
+            Motion::Top => 0,
+            Motion::Bottom => last,
+            Motion::WordForward => (0..count).fold(self.cursor, |i, _| self.next_word_boundary(i)),
+            Motion::WordBack => (0..count).fold(self.cursor, |i, _| self.prev_word_boundary(i)),
+            Motion::WordEnd => (0..count).fold(self.cursor, |i, _| self.word_end_boundary(i)),
+        }
+    }
+
+    /// Select the inclusive range of entries between two cursor positions,
+    /// as an operator + motion command (e.g. `d3j`) needs to act on
+    pub fn select_range(&mut self, a: usize, b: usize) {
+        self.push_undo(UndoAction::Selection { previous: self.selected.clone() }, "selection");
+        let start = a.min(b);
+        let end = a.max(b);
+        self.selected = (start..=end).collect();
+    }
+
+    fn resolve_motion(&mut self, motion: Motion) -> KeyOutcome {
+        let motion_count = self.pending_count.take().unwrap_or(1);
+
+        if let Some((operator, op_count)) = self.pending_operator.take() {
+            KeyOutcome::Operate {
+                operator,
+                motion,
+                count: op_count * motion_count,
+                register: self.pending_register.take(),
+            }
+        } else {
+            KeyOutcome::Move {
+                motion,
+                count: motion_count,
+            }
+        }
+    }
+
+    fn next_word_boundary(&self, from: usize) -> usize {
+        let start_word = first_word(&self.entries[from].name);
+        let mut i = from;
+        while i + 1 < self.entries.len() {
+            i += 1;
+            if first_word(&self.entries[i].name) != start_word {
+                break;
+            }
+        }
+        i
+    }
+
+    fn prev_word_boundary(&self, from: usize) -> usize {
+        if from == 0 {
+            return from;
+        }
+        let mut i = from - 1;
+        let word = first_word(&self.entries[i].name);
+        while i > 0 && first_word(&self.entries[i - 1].name) == word {
+            i -= 1;
+        }
+        i
+    }
+
+    fn word_end_boundary(&self, from: usize) -> usize {
+        let mut i = from;
+        if i + 1 < self.entries.len() && first_word(&self.entries[i + 1].name) != first_word(&self.entries[i].name) {
+            i += 1;
+        }
+        let word = first_word(&self.entries[i].name);
+        while i + 1 < self.entries.len() && first_word(&self.entries[i + 1].name) == word {
+            i += 1;
+        }
+        i
+    }
+
     /// Toggle selection on current entry
     pub fn toggle_selection(&mut self) {
         self.push_undo(
@@ -314,6 +711,7 @@ impl AppState {
     pub fn enter_search_mode(&mut self) {
         self.input_mode = InputMode::Search;
         self.search_query.clear();
+        self.update_search_filter();
     }
 
     /// Exit current input mode
@@ -323,6 +721,36 @@ impl AppState {
         self.selection_anchor = None;
     }
 
+    /// Cancel search mode, discarding the filter and restoring the full
+    /// directory listing (used on `Esc`)
+    pub fn cancel_search(&mut self) {
+        self.entries = self.all_entries.clone();
+        self.clamp_cursor();
+        self.exit_input_mode();
+    }
+
+    /// Re-filter `entries` from `all_entries` using `search_query` as a
+    /// fuzzy subsequence query, sorted by descending match score. Called
+    /// after every edit to `search_query` so search narrows as you type.
+    pub fn update_search_filter(&mut self) {
+        let query = self.search_query.clone();
+        let mut scored: Vec<(i32, &FileEntry)> = self
+            .all_entries
+            .iter()
+            .filter_map(|entry| crate::fuzzy::score(&query, &entry.name).map(|s| (s, entry)))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+
+        self.entries = scored.into_iter().map(|(_, entry)| entry.clone()).collect();
+        self.clamp_cursor();
+    }
+
+    fn clamp_cursor(&mut self) {
+        if self.cursor >= self.entries.len() {
+            self.cursor = self.entries.len().saturating_sub(1);
+        }
+    }
+
     /// Set status message
     pub fn set_status(&mut self, message: impl Into<String>, level: StatusLevel) {
         self.status_message = Some((message.into(), level));
@@ -349,11 +777,63 @@ impl AppState {
         }
     }
 
+    /// Record a batch trash operation (files moved to `.trash` rather than
+    /// unlinked) so it can later be undone (restored) or redone (re-trashed)
+    pub fn record_trash(&mut self, restore: RestorePlan) {
+        self.push_undo(UndoAction::FileOp { restore }, "delete");
+    }
+
+    /// Queue a delete of `paths` as a pending op, same as the path `redo()`
+    /// takes for a re-delete. The op's completion handler is expected to
+    /// call `record_trash` with the server-reported trash paths once the
+    /// delete actually happens, so undo/redo have a correct `RestorePlan`
+    /// to work from.
+    pub fn queue_delete(&mut self, paths: Vec<String>) {
+        self.pending_ops.push(PendingOp {
+            id: format!("trash-{}", uuid::Uuid::new_v4()),
+            op_type: OpType::Delete { paths },
+            progress: None,
+        });
+    }
+
     /// Get entry at cursor
     pub fn current_entry(&self) -> Option<&FileEntry> {
         self.entries.get(self.cursor)
     }
 
+    /// Show/hide the preview pane
+    pub fn toggle_preview(&mut self) {
+        self.show_preview = !self.show_preview;
+    }
+
+    /// Whether `preview` needs to be (re)loaded for `current_entry()`: either
+    /// there's no preview yet, it belongs to a different entry, or the entry
+    /// has since been modified. A loader keys its debounce on this rather
+    /// than reloading on every cursor step.
+    pub fn preview_stale(&self) -> bool {
+        match (&self.preview, self.current_entry()) {
+            (Some(loaded), Some(entry)) => loaded.path != entry.path || loaded.modified != entry.modified,
+            (None, Some(_)) => true,
+            (_, None) => self.preview.is_some(),
+        }
+    }
+
+    /// Record a freshly-built preview for the entry at `path`/`modified`
+    pub fn set_preview(&mut self, path: String, modified: i64, preview: Preview) {
+        self.preview = Some(PreviewState { path, modified, preview });
+    }
+
+    /// Drop the current preview (e.g. the cursor moved off the listing entirely)
+    pub fn clear_preview(&mut self) {
+        self.preview = None;
+    }
+
+    /// Record the parent directory's child listing, for the Miller-column
+    /// parent pane
+    pub fn set_parent_entries(&mut self, entries: Vec<FileEntry>) {
+        self.parent_entries = entries;
+    }
+
     /// Parent path
     pub fn parent_path(&self) -> Option<String> {
         if self.current_path == "/" {
@@ -390,6 +870,16 @@ impl AppState {
                     self.selected = previous.clone();
                     UndoAction::Selection { previous: current }
                 }
+                UndoAction::FileOp { restore } => {
+                    self.pending_ops.push(PendingOp {
+                        id: format!("restore-{}", uuid::Uuid::new_v4()),
+                        op_type: OpType::TrashRestore {
+                            entries: restore.entries.clone(),
+                        },
+                        progress: None,
+                    });
+                    UndoAction::FileOp { restore: restore.clone() }
+                }
             };
 
             self.redo_stack.push(UndoEntry {
@@ -404,34 +894,55 @@ impl AppState {
 
     /// Redo last undone action
     pub fn redo(&mut self) -> bool {
-        if let Some(entry) = self.redo_stack.pop() {
-            let undo_action = match &entry.action {
-                UndoAction::Navigate { from } => {
-                    let current = self.current_path.clone();
-                    self.current_path = from.clone();
-                    UndoAction::Navigate { from: current }
-                }
-                UndoAction::CursorMove { from } => {
-                    let current = self.cursor;
-                    self.cursor = *from;
-                    self.ensure_cursor_visible();
-                    UndoAction::CursorMove { from: current }
-                }
-                UndoAction::Selection { previous } => {
-                    let current = self.selected.clone();
-                    self.selected = previous.clone();
-                    UndoAction::Selection { previous: current }
-                }
-            };
-
-            self.undo_stack.push_back(UndoEntry {
-                action: undo_action,
-                description: entry.description,
-            });
-            true
-        } else {
-            false
+        let Some(entry) = self.redo_stack.pop() else {
+            return false;
+        };
+
+        match &entry.action {
+            UndoAction::Navigate { from } => {
+                let current = self.current_path.clone();
+                self.current_path = from.clone();
+                self.undo_stack.push_back(UndoEntry {
+                    action: UndoAction::Navigate { from: current },
+                    description: entry.description,
+                });
+            }
+            UndoAction::CursorMove { from } => {
+                let current = self.cursor;
+                self.cursor = *from;
+                self.ensure_cursor_visible();
+                self.undo_stack.push_back(UndoEntry {
+                    action: UndoAction::CursorMove { from: current },
+                    description: entry.description,
+                });
+            }
+            UndoAction::Selection { previous } => {
+                let current = self.selected.clone();
+                self.selected = previous.clone();
+                self.undo_stack.push_back(UndoEntry {
+                    action: UndoAction::Selection { previous: current },
+                    description: entry.description,
+                });
+            }
+            UndoAction::FileOp { restore } => {
+                let paths = restore.entries.iter().map(|e| e.original_path.clone()).collect();
+                self.pending_ops.push(PendingOp {
+                    id: format!("trash-{}", uuid::Uuid::new_v4()),
+                    op_type: OpType::Delete { paths },
+                    progress: None,
+                });
+                // Deliberately not pushed back onto `undo_stack` here: this
+                // re-delete will generate a fresh trash path server-side,
+                // which `restore` (captured before the *original* delete)
+                // doesn't know. Re-pushing it would let a later `undo()`
+                // try to restore from a trash path the re-delete never
+                // produced. `record_trash` gets called again -- same as
+                // after the original delete -- once this re-delete
+                // completes and the real trash path is known.
+            }
         }
+
+        true
     }
 
     // Private helpers
@@ -465,6 +976,14 @@ impl AppState {
     }
 }
 
+/// The leading alphanumeric token of a filename, used to group near-duplicate
+/// entries (`photo1.jpg`, `photo2.jpg`) into a single "word" for `w`/`b`/`e`
+fn first_word(name: &str) -> &str {
+    name.split(|c: char| !c.is_alphanumeric())
+        .find(|s| !s.is_empty())
+        .unwrap_or(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -543,4 +1062,342 @@ mod tests {
 
         assert_eq!(state.selected, vec![2, 3, 4, 5]);
     }
+
+    #[test]
+    fn test_feed_key_count_then_motion() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        let outcome = state.feed_key('3');
+        assert_eq!(outcome, KeyOutcome::Pending);
+        let outcome = state.feed_key('j');
+        assert_eq!(
+            outcome,
+            KeyOutcome::Move {
+                motion: Motion::Down,
+                count: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_feed_key_gg_jumps_top() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        assert_eq!(state.feed_key('g'), KeyOutcome::Pending);
+        assert_eq!(
+            state.feed_key('g'),
+            KeyOutcome::Move {
+                motion: Motion::Top,
+                count: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_feed_key_dd_with_count() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        assert_eq!(state.feed_key('3'), KeyOutcome::Pending);
+        assert_eq!(state.feed_key('d'), KeyOutcome::Pending);
+        assert_eq!(
+            state.feed_key('d'),
+            KeyOutcome::Operate {
+                operator: Operator::Delete,
+                motion: Motion::Line,
+                count: 3,
+                register: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_feed_key_operator_then_motion() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        assert_eq!(state.feed_key('d'), KeyOutcome::Pending);
+        assert_eq!(
+            state.feed_key('j'),
+            KeyOutcome::Operate {
+                operator: Operator::Delete,
+                motion: Motion::Down,
+                count: 1,
+                register: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_feed_key_resets_on_unknown_key() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        state.feed_key('d');
+        state.feed_key('?');
+        // The pending operator should have been abandoned, so a bare
+        // motion afterward is just a move, not an operate
+        assert_eq!(
+            state.feed_key('j'),
+            KeyOutcome::Move {
+                motion: Motion::Down,
+                count: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_word_motions_group_similar_names() {
+        let mut state = AppState::default();
+        state.entries = vec!["photo1.jpg", "photo1.thumb.jpg", "photo2.jpg"]
+            .into_iter()
+            .map(|n| FileEntry {
+                name: n.to_string(),
+                path: format!("/test/{}", n),
+                file_type: FileType::File,
+                size: 0,
+                modified: 0,
+                mime_type: None,
+            })
+            .collect();
+
+        // "photo1.jpg" and "photo1.thumb.jpg" share the word "photo1"
+        state.cursor_word_forward();
+        assert_eq!(state.cursor, 2);
+
+        state.cursor_word_back();
+        assert_eq!(state.cursor, 0);
+    }
+
+    #[test]
+    fn test_motion_target_for_operate_range() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+        state.cursor = 2;
+
+        assert_eq!(state.motion_target(Motion::Down, 3), 5);
+        assert_eq!(state.motion_target(Motion::Line, 3), 4);
+    }
+
+    #[test]
+    fn test_search_filters_entries_by_fuzzy_subsequence() {
+        let mut state = AppState::default();
+        state.set_entries(
+            "/".to_string(),
+            vec!["README.md", "main.rs", "reducer.rs"]
+                .into_iter()
+                .map(|n| FileEntry {
+                    name: n.to_string(),
+                    path: format!("/{}", n),
+                    file_type: FileType::File,
+                    size: 0,
+                    modified: 0,
+                    mime_type: None,
+                })
+                .collect(),
+        );
+
+        state.enter_search_mode();
+        state.search_query = "rdme".to_string();
+        state.update_search_filter();
+
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.entries[0].name, "README.md");
+        assert_eq!(state.all_entries.len(), 3, "all_entries keeps the unfiltered list");
+    }
+
+    #[test]
+    fn test_cancel_search_restores_full_listing() {
+        let mut state = AppState::default();
+        state.set_entries(
+            "/".to_string(),
+            vec!["a.txt", "b.txt"]
+                .into_iter()
+                .map(|n| FileEntry {
+                    name: n.to_string(),
+                    path: format!("/{}", n),
+                    file_type: FileType::File,
+                    size: 0,
+                    modified: 0,
+                    mime_type: None,
+                })
+                .collect(),
+        );
+
+        state.enter_search_mode();
+        state.search_query = "a".to_string();
+        state.update_search_filter();
+        assert_eq!(state.entries.len(), 1);
+
+        state.cancel_search();
+        assert_eq!(state.entries.len(), 2);
+        assert_eq!(state.input_mode, InputMode::Normal);
+    }
+
+    #[test]
+    fn test_undo_trash_queues_a_restore_pending_op() {
+        let mut state = AppState::default();
+        state.record_trash(RestorePlan {
+            entries: vec![TrashEntry {
+                original_path: "/a.txt".to_string(),
+                trash_path: "/.trash/1__a.txt".to_string(),
+            }],
+        });
+
+        assert!(state.undo());
+        assert_eq!(state.pending_ops.len(), 1);
+        match &state.pending_ops[0].op_type {
+            OpType::TrashRestore { entries } => {
+                assert_eq!(entries[0].original_path, "/a.txt");
+            }
+            other => panic!("expected TrashRestore, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_redo_trash_queues_a_re_delete_pending_op() {
+        let mut state = AppState::default();
+        state.record_trash(RestorePlan {
+            entries: vec![TrashEntry {
+                original_path: "/a.txt".to_string(),
+                trash_path: "/.trash/1__a.txt".to_string(),
+            }],
+        });
+        state.undo();
+
+        assert!(state.redo());
+        // undo's restore and redo's re-delete are both queued as pending ops
+        assert_eq!(state.pending_ops.len(), 2);
+        match &state.pending_ops[1].op_type {
+            OpType::Delete { paths } => assert_eq!(paths, &vec!["/a.txt".to_string()]),
+            other => panic!("expected Delete, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_redo_does_not_repush_stale_restore_plan() {
+        // The re-delete queued by `redo()` will get a fresh trash path
+        // server-side, so a further `undo()` shouldn't be left able to
+        // fire with the *original* delete's now-stale trash path.
+        let mut state = AppState::default();
+        state.record_trash(RestorePlan {
+            entries: vec![TrashEntry {
+                original_path: "/a.txt".to_string(),
+                trash_path: "/.trash/1__a.txt".to_string(),
+            }],
+        });
+        state.undo();
+        state.redo();
+
+        assert!(!state.undo(), "no FileOp should have been re-queued onto undo_stack");
+    }
+
+    #[test]
+    fn test_queue_delete_pushes_a_pending_delete_op() {
+        let mut state = AppState::default();
+        state.queue_delete(vec!["/a.txt".to_string()]);
+
+        assert_eq!(state.pending_ops.len(), 1);
+        match &state.pending_ops[0].op_type {
+            OpType::Delete { paths } => assert_eq!(paths, &vec!["/a.txt".to_string()]),
+            other => panic!("expected Delete, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_preview_stale_until_loaded_for_current_entry() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        assert!(state.preview_stale());
+        state.set_preview(
+            state.current_entry().unwrap().path.clone(),
+            state.current_entry().unwrap().modified,
+            crate::preview::Preview::Binary { info: "test".to_string() },
+        );
+        assert!(!state.preview_stale());
+
+        state.cursor_down();
+        assert!(state.preview_stale(), "moving to a different entry should stale the preview");
+    }
+
+    #[test]
+    fn test_toggle_preview_flips_show_preview() {
+        let mut state = AppState::default();
+        assert!(state.show_preview);
+        state.toggle_preview();
+        assert!(!state.show_preview);
+    }
+
+    #[test]
+    fn test_register_prefix_tags_the_following_operator() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        assert_eq!(state.feed_key('"'), KeyOutcome::Pending);
+        assert_eq!(state.feed_key('a'), KeyOutcome::Pending);
+        assert_eq!(state.feed_key('y'), KeyOutcome::Pending);
+        assert_eq!(
+            state.feed_key('y'),
+            KeyOutcome::Operate {
+                operator: Operator::Yank,
+                motion: Motion::Line,
+                count: 1,
+                register: Some('a'),
+            }
+        );
+    }
+
+    #[test]
+    fn test_operator_without_register_prefix_targets_unnamed() {
+        let mut state = AppState::default();
+        state.entries = sample_entries();
+
+        assert_eq!(state.feed_key('d'), KeyOutcome::Pending);
+        assert_eq!(
+            state.feed_key('d'),
+            KeyOutcome::Operate {
+                operator: Operator::Delete,
+                motion: Motion::Line,
+                count: 1,
+                register: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_yank_to_register_then_read_back() {
+        let mut state = AppState::default();
+        state.yank_to_register(Some('a'), vec!["/x.txt".to_string()], false);
+
+        let reg = state.register_contents(Some('a')).unwrap();
+        assert_eq!(reg.paths, vec!["/x.txt".to_string()]);
+        assert!(!reg.cut);
+
+        // The unnamed register is untouched by a named yank
+        assert!(state.register_contents(None).is_none());
+    }
+
+    #[test]
+    fn test_yank_to_unnamed_register_is_default_paste_source() {
+        let mut state = AppState::default();
+        state.yank_to_register(None, vec!["/y.txt".to_string()], true);
+
+        let reg = state.register_contents(None).unwrap();
+        assert_eq!(reg.paths, vec!["/y.txt".to_string()]);
+        assert!(reg.cut);
+    }
+
+    #[test]
+    fn test_take_pending_register_consumes_quote_prefix_for_non_grammar_actions() {
+        let mut state = AppState::default();
+        state.feed_key('"');
+        state.feed_key('a');
+
+        assert_eq!(state.take_pending_register(), Some('a'));
+        // Already consumed; a second take finds nothing armed
+        assert_eq!(state.take_pending_register(), None);
+    }
 }
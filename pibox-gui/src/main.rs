@@ -5,16 +5,89 @@
 //! - Thumbnail preview
 //! - Adaptive rendering (GPU when available)
 
+use std::path::Path;
+use std::time::{Duration, Instant};
+
 use iced::{
     keyboard::{self, Key},
-    widget::{column, container, row, scrollable, text, Column},
+    widget::{column, container, row, scrollable, text, Column, Stack},
     Alignment, Color, Element, Length, Subscription, Task, Theme,
 };
+use notify::{RecursiveMode, Watcher};
 use pibox_core::{
-    state::{FileEntry, FileType, InputMode, StatusLevel},
+    appearance::Appearance,
+    bookmarks::Bookmarks,
+    state::{BookmarkAction, FileEntry, FileType, InputMode, StatusLevel},
     Config,
 };
 
+/// Every color the GUI draws with, grouped so picking a theme is one
+/// struct swap instead of touching each `view_*` method
+#[derive(Debug, Clone, Copy)]
+struct Palette {
+    toolbar_bg: Color,
+    status_bg: Color,
+    connected: Color,
+    offline: Color,
+    cursor_bg: Color,
+    selected_bg: Color,
+    muted: Color,
+    info: Color,
+    success: Color,
+    warning: Color,
+    error: Color,
+}
+
+impl Palette {
+    fn for_appearance(appearance: Appearance) -> Self {
+        match appearance {
+            Appearance::Dark => Self::dark(),
+            Appearance::Light => Self::light(),
+        }
+    }
+
+    fn dark() -> Self {
+        Self {
+            toolbar_bg: Color::from_rgb(0.15, 0.15, 0.15),
+            status_bg: Color::from_rgb(0.12, 0.12, 0.12),
+            connected: Color::from_rgb(0.2, 0.8, 0.2),
+            offline: Color::from_rgb(0.8, 0.8, 0.2),
+            cursor_bg: Color::from_rgb(0.2, 0.4, 0.6),
+            selected_bg: Color::from_rgb(0.25, 0.25, 0.3),
+            muted: Color::from_rgb(0.5, 0.5, 0.5),
+            info: Color::from_rgb(0.4, 0.6, 0.9),
+            success: Color::from_rgb(0.3, 0.8, 0.3),
+            warning: Color::from_rgb(0.9, 0.8, 0.2),
+            error: Color::from_rgb(0.9, 0.3, 0.3),
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            toolbar_bg: Color::from_rgb(0.9, 0.9, 0.9),
+            status_bg: Color::from_rgb(0.85, 0.85, 0.85),
+            connected: Color::from_rgb(0.1, 0.5, 0.1),
+            offline: Color::from_rgb(0.6, 0.5, 0.0),
+            cursor_bg: Color::from_rgb(0.7, 0.85, 1.0),
+            selected_bg: Color::from_rgb(0.8, 0.8, 0.85),
+            muted: Color::from_rgb(0.4, 0.4, 0.4),
+            info: Color::from_rgb(0.1, 0.3, 0.7),
+            success: Color::from_rgb(0.1, 0.5, 0.1),
+            warning: Color::from_rgb(0.6, 0.5, 0.0),
+            error: Color::from_rgb(0.7, 0.1, 0.1),
+        }
+    }
+}
+
+/// Detect the OS's light/dark appearance preference. No appearance-query
+/// crate is available in this tree, so this defaults to dark
+/// until one is added -- `pibox_core::appearance` already carries the
+/// shared luminance math the TUI uses for its own (terminal-based)
+/// detection, ready for whatever OS query replaces this stub.
+fn detect_os_appearance() -> Appearance {
+    Appearance::Dark
+}
+
 fn main() -> iced::Result {
     iced::application("pibox", PiboxGui::update, PiboxGui::view)
         .subscription(PiboxGui::subscription)
@@ -24,7 +97,6 @@ fn main() -> iced::Result {
 }
 
 /// Main application state
-#[derive(Default)]
 struct PiboxGui {
     entries: Vec<FileEntry>,
     cursor: usize,
@@ -34,6 +106,36 @@ struct PiboxGui {
     input_mode: InputMode,
     search_query: String,
     connected: bool,
+    appearance: Appearance,
+    palette: Palette,
+    bookmarks: Bookmarks,
+    command_input: String,
+    command_output: Vec<String>,
+    command_running: bool,
+    command_exit: Option<i32>,
+}
+
+impl Default for PiboxGui {
+    fn default() -> Self {
+        let appearance = detect_os_appearance();
+        Self {
+            entries: Vec::new(),
+            cursor: 0,
+            selected: Vec::new(),
+            current_path: String::new(),
+            status_message: None,
+            input_mode: InputMode::default(),
+            search_query: String::new(),
+            connected: false,
+            appearance,
+            palette: Palette::for_appearance(appearance),
+            bookmarks: Bookmarks::load(),
+            command_input: String::new(),
+            command_output: Vec::new(),
+            command_running: false,
+            command_exit: None,
+        }
+    }
 }
 
 /// Application messages
@@ -62,10 +164,32 @@ enum Message {
     EnterSearch,
     ExitMode,
 
+    // Bookmarks
+    EnterBookmarkSet,
+    EnterBookmarkJump,
+    SetBookmark(char),
+    JumpBookmark(char),
+
+    // Filesystem
+    DirChanged,
+
+    // Command execution
+    EnterCommandMode,
+    CommandInputChar(String),
+    CommandBackspace,
+    SubmitCommand,
+    CommandOutputChunk(String),
+    CommandFinished(i32),
+
     // Misc
     KeyPressed(keyboard::Key, keyboard::Modifiers),
 }
 
+/// Coalescing window for the directory watcher subscription: a burst of
+/// raw events (e.g. a large `cp` into the directory) within this interval
+/// collapses into a single `Message::DirChanged`
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
 impl PiboxGui {
     fn new() -> (Self, Task<Message>) {
         let mut app = Self {
@@ -146,6 +270,63 @@ impl PiboxGui {
                 self.input_mode = InputMode::Normal;
                 self.search_query.clear();
             }
+            Message::EnterBookmarkSet => {
+                self.input_mode = InputMode::Bookmark(BookmarkAction::Set);
+            }
+            Message::EnterBookmarkJump => {
+                self.input_mode = InputMode::Bookmark(BookmarkAction::Jump);
+            }
+            Message::SetBookmark(c) => {
+                self.bookmarks.set(c, self.current_path.clone());
+                if let Err(e) = self.bookmarks.save() {
+                    self.set_status(format!("Bookmark saved but not persisted: {e}"), StatusLevel::Warning);
+                } else {
+                    self.set_status(format!("Bookmarked '{}' as {}", self.current_path, c), StatusLevel::Success);
+                }
+                self.input_mode = InputMode::Normal;
+            }
+            Message::JumpBookmark(c) => {
+                self.input_mode = InputMode::Normal;
+                match self.bookmarks.get(c).map(str::to_string) {
+                    Some(path) => {
+                        self.set_status(format!("Navigate to: {}", path), StatusLevel::Info);
+                        self.current_path = path;
+                    }
+                    None => self.set_status(format!("No bookmark at '{}'", c), StatusLevel::Error),
+                }
+            }
+            Message::EnterCommandMode => {
+                self.input_mode = InputMode::Command;
+                self.command_input.clear();
+            }
+            Message::CommandInputChar(c) => {
+                self.command_input.push_str(&c);
+            }
+            Message::CommandBackspace => {
+                self.command_input.pop();
+            }
+            Message::SubmitCommand => {
+                let cmd = self.command_input.clone();
+                self.input_mode = InputMode::Normal;
+                return self.execute_command(&cmd);
+            }
+            Message::CommandOutputChunk(line) => {
+                self.command_output.push(line);
+            }
+            Message::CommandFinished(code) => {
+                self.command_running = false;
+                self.command_exit = Some(code);
+                let level = if code == 0 { StatusLevel::Success } else { StatusLevel::Error };
+                self.set_status(format!("Command exited with status {}", code), level);
+            }
+            Message::DirChanged => {
+                // TODO: re-fetch the listing via FilebrowserClient once a
+                // real connection is wired up; there's no content source
+                // to re-read from yet, but `AppState::refresh_entries`'s
+                // TUI equivalent is ready to take the fetched entries and
+                // preserve the cursor once this is.
+                self.set_status(format!("{} changed externally", self.current_path), StatusLevel::Info);
+            }
             Message::KeyPressed(key, modifiers) => {
                 return self.handle_key(key, modifiers);
             }
@@ -155,26 +336,88 @@ impl PiboxGui {
     }
 
     fn view(&self) -> Element<Message> {
-        // Main layout: file list + status bar
+        // A running/finished command takes over the file list's spot --
+        // it was explicitly requested, so there's no toggle to check here
+        // the way the TUI checks `show_preview`.
+        let middle: Element<Message> = if self.command_running || !self.command_output.is_empty() {
+            self.view_command_output()
+        } else {
+            self.view_file_list()
+        };
+
         let content = column![
             self.view_toolbar(),
-            self.view_file_list(),
+            middle,
             self.view_status_bar(),
         ]
         .spacing(0);
 
-        container(content)
+        let base: Element<Message> = container(content)
             .width(Length::Fill)
             .height(Length::Fill)
+            .into();
+
+        match self.input_mode {
+            InputMode::Bookmark(action) => Stack::with_children(vec![base, self.view_bookmarks(action)]).into(),
+            _ => base,
+        }
+    }
+
+    /// Modal overlay listing saved bookmarks, shown over the file list while
+    /// waiting for the key naming the slot to set or jump to
+    fn view_bookmarks(&self, action: BookmarkAction) -> Element<Message> {
+        let title = match action {
+            BookmarkAction::Set => "Set bookmark (press a key)",
+            BookmarkAction::Jump => "Jump to bookmark (press a key)",
+        };
+
+        let entries = self.bookmarks.entries();
+        let list: Element<Message> = if entries.is_empty() {
+            text("(no bookmarks set)").color(self.palette.muted).into()
+        } else {
+            Column::with_children(
+                entries
+                    .iter()
+                    .map(|(key, path)| text(format!("{}  {}", key, path)).into())
+                    .collect::<Vec<Element<Message>>>(),
+            )
+            .spacing(4)
+            .into()
+        };
+
+        let modal_bg = self.palette.toolbar_bg;
+        let modal = container(column![text(title).size(16), list].spacing(10).padding(16))
+            .style(move |_theme| container::Style {
+                background: Some(iced::Background::Color(modal_bg)),
+                border: iced::Border { color: Color::WHITE, width: 1.0, radius: 4.0.into() },
+                ..Default::default()
+            })
+            .width(Length::Fixed(360.0));
+
+        container(modal)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x(Length::Fill)
+            .center_y(Length::Fill)
+            .style(|_theme| container::Style {
+                background: Some(iced::Background::Color(Color::from_rgba(0.0, 0.0, 0.0, 0.5))),
+                ..Default::default()
+            })
             .into()
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        keyboard::on_key_press(|key, modifiers| Some(Message::KeyPressed(key, modifiers)))
+        Subscription::batch([
+            keyboard::on_key_press(|key, modifiers| Some(Message::KeyPressed(key, modifiers))),
+            watch_subscription(self.current_path.clone()),
+        ])
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        match self.appearance {
+            Appearance::Dark => Theme::Dark,
+            Appearance::Light => Theme::Light,
+        }
     }
 
     fn load_demo_data(&mut self) {
@@ -243,7 +486,108 @@ impl PiboxGui {
         self.status_message = Some((message.into(), level));
     }
 
+    /// Execute a command-mode command: a couple of built-ins are
+    /// special-cased because they act on the current selection rather
+    /// than taking an explicit path the way shelling out would require;
+    /// anything else -- and anything after an explicit `!` passthrough
+    /// prefix -- is spawned for real through the shell, streaming its
+    /// output into `command_output`.
+    fn execute_command(&mut self, cmd: &str) -> Task<Message> {
+        let cmd = cmd.trim();
+
+        if let Some(shell_cmd) = cmd.strip_prefix('!') {
+            return self.start_command(shell_cmd.trim().to_string());
+        }
+
+        let parts: Vec<&str> = cmd.split_whitespace().collect();
+        match parts.first().copied() {
+            Some("q") | Some("quit") | Some("w") | Some("write") | Some("wq") => {
+                self.set_status("Nothing to save", StatusLevel::Info);
+            }
+            Some("mkdir") => match parts.get(1) {
+                Some(name) => self.mkdir(name),
+                None => self.set_status("mkdir: missing directory name", StatusLevel::Error),
+            },
+            Some("rename") => match parts.get(1) {
+                Some(new_name) => self.rename_current(new_name),
+                None => self.set_status("rename: missing new name", StatusLevel::Error),
+            },
+            Some(_) => return self.start_command(cmd.to_string()),
+            None => {}
+        }
+
+        Task::none()
+    }
+
+    /// Run `command` through the shell in the focused directory, replacing
+    /// whatever output the previous command left behind
+    fn start_command(&mut self, command: String) -> Task<Message> {
+        self.command_output.clear();
+        self.command_exit = None;
+        self.command_running = true;
+        run_command(command, self.current_path.clone())
+    }
+
+    /// `mkdir` built-in: create a directory inside the focused one
+    /// directly rather than shelling out, since there's nothing to stream
+    /// and it should feel instant
+    fn mkdir(&mut self, name: &str) {
+        let path = Path::new(&self.current_path).join(name);
+        match std::fs::create_dir(&path) {
+            Ok(()) => self.set_status(format!("Created {}", path.display()), StatusLevel::Success),
+            Err(e) => self.set_status(format!("mkdir failed: {e}"), StatusLevel::Error),
+        }
+    }
+
+    /// `rename` built-in: rename the focused entry to `new_name`, acting
+    /// on the current selection rather than taking an explicit source path
+    fn rename_current(&mut self, new_name: &str) {
+        let Some(entry) = self.entries.get(self.cursor) else {
+            self.set_status("rename: no entry focused", StatusLevel::Error);
+            return;
+        };
+
+        let from = Path::new(&entry.path).to_path_buf();
+        let to = match from.parent() {
+            Some(parent) => parent.join(new_name),
+            None => std::path::PathBuf::from(new_name),
+        };
+
+        match std::fs::rename(&from, &to) {
+            Ok(()) => self.set_status(format!("Renamed to {}", to.display()), StatusLevel::Success),
+            Err(e) => self.set_status(format!("rename failed: {e}"), StatusLevel::Error),
+        }
+    }
+
     fn handle_key(&mut self, key: Key, modifiers: keyboard::Modifiers) -> Task<Message> {
+        if let InputMode::Bookmark(action) = self.input_mode {
+            if let Key::Character(ref c) = key {
+                if let Some(ch) = c.chars().next() {
+                    return Task::done(match action {
+                        BookmarkAction::Set => Message::SetBookmark(ch),
+                        BookmarkAction::Jump => Message::JumpBookmark(ch),
+                    });
+                }
+            }
+            if matches!(key, Key::Named(keyboard::key::Named::Escape)) {
+                return Task::done(Message::ExitMode);
+            }
+            return Task::none();
+        }
+
+        if self.input_mode == InputMode::Command {
+            return match key {
+                Key::Character(ref c) => Task::done(Message::CommandInputChar(c.as_str().to_string())),
+                Key::Named(keyboard::key::Named::Space) => {
+                    Task::done(Message::CommandInputChar(" ".to_string()))
+                }
+                Key::Named(keyboard::key::Named::Backspace) => Task::done(Message::CommandBackspace),
+                Key::Named(keyboard::key::Named::Enter) => Task::done(Message::SubmitCommand),
+                Key::Named(keyboard::key::Named::Escape) => Task::done(Message::ExitMode),
+                _ => Task::none(),
+            };
+        }
+
         if self.input_mode != InputMode::Normal {
             // In input mode, only handle Escape
             if matches!(key, Key::Named(keyboard::key::Named::Escape)) {
@@ -269,6 +613,9 @@ impl PiboxGui {
                     "p" => return Task::done(Message::Paste),
                     "r" => return Task::done(Message::Rename),
                     "/" => return Task::done(Message::EnterSearch),
+                    ":" => return Task::done(Message::EnterCommandMode),
+                    "m" => return Task::done(Message::EnterBookmarkSet),
+                    "`" => return Task::done(Message::EnterBookmarkJump),
                     "a" if modifiers.control() => return Task::done(Message::SelectAll),
                     _ => {}
                 }
@@ -294,18 +641,19 @@ impl PiboxGui {
     fn view_toolbar(&self) -> Element<Message> {
         let path_text = text(&self.current_path).size(16);
         let connection_status = if self.connected {
-            text("Connected").color(Color::from_rgb(0.2, 0.8, 0.2))
+            text("Connected").color(self.palette.connected)
         } else {
-            text("Offline").color(Color::from_rgb(0.8, 0.8, 0.2))
+            text("Offline").color(self.palette.offline)
         };
 
+        let toolbar_bg = self.palette.toolbar_bg;
         container(
             row![path_text, iced::widget::horizontal_space(), connection_status]
                 .spacing(10)
                 .padding(8),
         )
-        .style(|_theme| container::Style {
-            background: Some(iced::Background::Color(Color::from_rgb(0.15, 0.15, 0.15))),
+        .style(move |_theme| container::Style {
+            background: Some(iced::Background::Color(toolbar_bg)),
             ..Default::default()
         })
         .width(Length::Fill)
@@ -366,9 +714,9 @@ impl PiboxGui {
         .align_y(Alignment::Center);
 
         let bg_color = if is_cursor {
-            Color::from_rgb(0.2, 0.4, 0.6)
+            self.palette.cursor_bg
         } else if is_selected {
-            Color::from_rgb(0.25, 0.25, 0.3)
+            self.palette.selected_bg
         } else {
             Color::TRANSPARENT
         };
@@ -383,23 +731,55 @@ impl PiboxGui {
             .into()
     }
 
+    /// Captured stdout/stderr of the most recently run `:`/`!` command,
+    /// shown in place of the file list until another command replaces it
+    fn view_command_output(&self) -> Element<Message> {
+        let status = if self.command_running {
+            "running…".to_string()
+        } else {
+            match self.command_exit {
+                Some(code) => format!("exit {}", code),
+                None => "no command run yet".to_string(),
+            }
+        };
+
+        let lines: Vec<Element<Message>> = self
+            .command_output
+            .iter()
+            .map(|line| text(line.clone()).into())
+            .collect();
+
+        let body = scrollable(
+            container(Column::with_children(lines).spacing(2))
+                .width(Length::Fill)
+                .padding(8),
+        )
+        .height(Length::Fill);
+
+        column![
+            container(text(status).color(self.palette.muted)).padding(4),
+            body,
+        ]
+        .into()
+    }
+
     fn view_status_bar(&self) -> Element<Message> {
         let status_text = if let Some((ref msg, ref level)) = self.status_message {
             let color = match level {
-                StatusLevel::Info => Color::from_rgb(0.4, 0.6, 0.9),
-                StatusLevel::Success => Color::from_rgb(0.3, 0.8, 0.3),
-                StatusLevel::Warning => Color::from_rgb(0.9, 0.8, 0.2),
-                StatusLevel::Error => Color::from_rgb(0.9, 0.3, 0.3),
+                StatusLevel::Info => self.palette.info,
+                StatusLevel::Success => self.palette.success,
+                StatusLevel::Warning => self.palette.warning,
+                StatusLevel::Error => self.palette.error,
             };
             text(msg).color(color)
         } else {
-            text("j/k:move h/l:nav space:select d:del y:copy p:paste /:search")
-                .color(Color::from_rgb(0.5, 0.5, 0.5))
+            text("j/k:move h/l:nav space:select d:del y:copy p:paste /:search").color(self.palette.muted)
         };
 
+        let status_bg = self.palette.status_bg;
         container(status_text)
-            .style(|_theme| container::Style {
-                background: Some(iced::Background::Color(Color::from_rgb(0.12, 0.12, 0.12))),
+            .style(move |_theme| container::Style {
+                background: Some(iced::Background::Color(status_bg)),
                 ..Default::default()
             })
             .width(Length::Fill)
@@ -408,6 +788,139 @@ impl PiboxGui {
     }
 }
 
+/// Subscribe to external changes to `path`: a background thread runs the
+/// blocking `notify` watcher and coalesces raw events, forwarding one
+/// `Message::DirChanged` once `WATCH_DEBOUNCE` has elapsed since the first
+/// event of a burst rather than per raw event. The window is not extended
+/// by later events, so a long-running burst yields one `DirChanged` per
+/// window.
+fn watch_subscription(path: String) -> Subscription<Message> {
+    Subscription::run_with_id(
+        path.clone(),
+        iced::stream::channel(16, move |mut output| async move {
+            use iced::futures::{SinkExt, StreamExt};
+
+            let (tx, mut rx) = iced::futures::channel::mpsc::unbounded::<()>();
+            let watch_path = path.clone();
+
+            std::thread::spawn(move || {
+                let (raw_tx, raw_rx) = std::sync::mpsc::channel::<()>();
+
+                let mut watcher = match notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                    if res.is_ok() {
+                        let _ = raw_tx.send(());
+                    }
+                }) {
+                    Ok(w) => w,
+                    Err(_) => return,
+                };
+
+                if watcher.watch(Path::new(&watch_path), RecursiveMode::NonRecursive).is_err() {
+                    return;
+                }
+
+                let mut pending_since: Option<Instant> = None;
+                loop {
+                    match raw_rx.recv_timeout(Duration::from_millis(50)) {
+                        Ok(()) => {
+                            pending_since.get_or_insert_with(Instant::now);
+                        }
+                        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+                    }
+
+                    if let Some(since) = pending_since {
+                        if since.elapsed() >= WATCH_DEBOUNCE {
+                            pending_since = None;
+                            if tx.unbounded_send(()).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            });
+
+            while rx.next().await.is_some() {
+                if output.send(Message::DirChanged).await.is_err() {
+                    break;
+                }
+            }
+        }),
+    )
+}
+
+/// Run `command` through `$SHELL -c` (falling back to `/bin/sh`) in `cwd`,
+/// streaming each output line as a `Message::CommandOutputChunk` and
+/// finishing with `Message::CommandFinished`, the same background-thread-
+/// plus-channel shape `watch_subscription` uses for the directory watcher.
+/// stdout and stderr are read on their own threads so a command that
+/// writes a lot to both at once can't deadlock waiting for the other
+/// pipe's buffer to drain.
+fn run_command(command: String, cwd: String) -> Task<Message> {
+    Task::stream(iced::stream::channel(16, move |mut output| async move {
+        use iced::futures::{SinkExt, StreamExt};
+
+        let (tx, mut rx) = iced::futures::channel::mpsc::unbounded::<Message>();
+
+        std::thread::spawn(move || {
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+            let child = std::process::Command::new(shell)
+                .arg("-c")
+                .arg(&command)
+                .current_dir(&cwd)
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn();
+
+            let mut child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = tx.unbounded_send(Message::CommandOutputChunk(format!("failed to start: {e}")));
+                    let _ = tx.unbounded_send(Message::CommandFinished(-1));
+                    return;
+                }
+            };
+
+            let stdout = child.stdout.take();
+            let stderr = child.stderr.take();
+
+            let out_tx = tx.clone();
+            let out_handle = stdout.map(|stdout| {
+                std::thread::spawn(move || {
+                    for line in std::io::BufRead::lines(std::io::BufReader::new(stdout)).flatten() {
+                        let _ = out_tx.unbounded_send(Message::CommandOutputChunk(line));
+                    }
+                })
+            });
+
+            let err_tx = tx.clone();
+            let err_handle = stderr.map(|stderr| {
+                std::thread::spawn(move || {
+                    for line in std::io::BufRead::lines(std::io::BufReader::new(stderr)).flatten() {
+                        let _ = err_tx.unbounded_send(Message::CommandOutputChunk(format!("! {line}")));
+                    }
+                })
+            });
+
+            if let Some(handle) = out_handle {
+                let _ = handle.join();
+            }
+            if let Some(handle) = err_handle {
+                let _ = handle.join();
+            }
+
+            let code = child.wait().ok().and_then(|s| s.code()).unwrap_or(-1);
+            let _ = tx.unbounded_send(Message::CommandFinished(code));
+        });
+
+        while let Some(message) = rx.next().await {
+            if output.send(message).await.is_err() {
+                break;
+            }
+        }
+    }))
+}
+
 fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
@@ -0,0 +1,257 @@
+//! Sled-backed, path-keyed cache for downloaded file bytes and
+//! generated thumbnails
+//!
+//! Keyed by path, stamped with the Filebrowser `modified` timestamp at
+//! write time so a stale hit can be detected without a round trip: a caller
+//! passes the freshly fetched `modified` from `get_info`/`FileEntry`, and
+//! the cached blob is only returned if it still matches. A `notify`-driven
+//! `FsEvent` invalidates a path outright instead of waiting for the next
+//! freshness check, so edits picked up by the watcher are reflected
+//! immediately.
+//!
+//! Every key is `path` followed by a `\0` separator and an optional
+//! discriminator (e.g. a thumbnail's `max_dim`), so `invalidate_prefix`/
+//! `rename_prefix` can address "every entry for this path" with
+//! `format!("{path}\0")` regardless of what, if anything, follows.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    modified: i64,
+    mime_type: String,
+    data: Vec<u8>,
+}
+
+/// A cache hit, after the caller's freshness check has already passed
+pub struct CachedContent {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A single sled tree with a byte-size cap and least-recently-used eviction
+pub struct ContentCache {
+    tree: sled::Tree,
+    capacity_bytes: u64,
+    current_size: AtomicU64,
+    /// Access order for eviction, kept in memory since it's cheap to rebuild
+    /// from `tree` at startup and doesn't need sled's durability
+    lru: Mutex<BTreeMap<u64, String>>,
+    lru_of_key: Mutex<HashMap<String, u64>>,
+    next_seq: AtomicU64,
+}
+
+impl ContentCache {
+    pub fn new(tree: sled::Tree, capacity_bytes: u64) -> sled::Result<Self> {
+        let mut current_size = 0u64;
+        let mut lru = BTreeMap::new();
+        let mut lru_of_key = HashMap::new();
+        let mut seq = 0u64;
+
+        for item in tree.iter() {
+            let (key, value) = item?;
+            let key = String::from_utf8_lossy(&key).into_owned();
+            if let Ok(entry) = serde_json::from_slice::<CacheEntry>(&value) {
+                current_size += entry.data.len() as u64;
+            }
+            lru.insert(seq, key.clone());
+            lru_of_key.insert(key, seq);
+            seq += 1;
+        }
+
+        Ok(Self {
+            tree,
+            capacity_bytes,
+            current_size: AtomicU64::new(current_size),
+            lru: Mutex::new(lru),
+            lru_of_key: Mutex::new(lru_of_key),
+            next_seq: AtomicU64::new(seq),
+        })
+    }
+
+    /// Look up `key`, returning the cached bytes only if `modified` still
+    /// matches what was stored -- otherwise the entry is stale and is
+    /// dropped.
+    pub fn get(&self, key: &str, modified: i64) -> Option<CachedContent> {
+        let raw = self.tree.get(key).ok().flatten()?;
+        let entry: CacheEntry = serde_json::from_slice(&raw).ok()?;
+
+        if entry.modified != modified {
+            self.remove(key);
+            return None;
+        }
+
+        self.touch(key);
+        Some(CachedContent {
+            mime_type: entry.mime_type,
+            data: entry.data,
+        })
+    }
+
+    /// Insert or replace `key`, then evict the least-recently-touched
+    /// entries until back under `capacity_bytes`.
+    pub fn put(&self, key: &str, modified: i64, mime_type: String, data: Vec<u8>) {
+        let size = data.len() as u64;
+        let entry = CacheEntry { modified, mime_type, data };
+        let Ok(encoded) = serde_json::to_vec(&entry) else {
+            return;
+        };
+
+        if let Ok(Some(old)) = self.tree.insert(key, encoded) {
+            self.forget_size(&old);
+        }
+        self.current_size.fetch_add(size, Ordering::Relaxed);
+        self.touch(key);
+        self.evict_to_capacity();
+    }
+
+    /// Drop every entry whose key starts with `prefix`, e.g.
+    /// `format!("{path}\0")` in response to an `FsEvent::Modified`/
+    /// `Deleted`.
+    pub fn invalidate_prefix(&self, prefix: &str) {
+        let keys: Vec<String> = self
+            .tree
+            .scan_prefix(prefix)
+            .keys()
+            .filter_map(|k| k.ok())
+            .map(|k| String::from_utf8_lossy(&k).into_owned())
+            .collect();
+
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    /// Move every entry under `old_prefix` to the same key with `new_prefix`
+    /// spliced in, in response to an `FsEvent::Renamed`, so a still-valid
+    /// cached blob isn't dropped just because its path moved.
+    pub fn rename_prefix(&self, old_prefix: &str, new_prefix: &str) {
+        let keys: Vec<String> = self
+            .tree
+            .scan_prefix(old_prefix)
+            .keys()
+            .filter_map(|k| k.ok())
+            .map(|k| String::from_utf8_lossy(&k).into_owned())
+            .collect();
+
+        for old_key in keys {
+            let Ok(Some(raw)) = self.tree.remove(&old_key) else {
+                continue;
+            };
+            let new_key = format!("{}{}", new_prefix, &old_key[old_prefix.len()..]);
+            let _ = self.tree.insert(&new_key, raw);
+
+            let mut lru_of_key = self.lru_of_key.lock().unwrap();
+            if let Some(seq) = lru_of_key.remove(&old_key) {
+                self.lru.lock().unwrap().insert(seq, new_key.clone());
+                lru_of_key.insert(new_key, seq);
+            }
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        if let Ok(Some(old)) = self.tree.remove(key) {
+            self.forget_size(&old);
+        }
+        if let Some(seq) = self.lru_of_key.lock().unwrap().remove(key) {
+            self.lru.lock().unwrap().remove(&seq);
+        }
+    }
+
+    fn forget_size(&self, old_encoded: &[u8]) {
+        if let Ok(old_entry) = serde_json::from_slice::<CacheEntry>(old_encoded) {
+            self.current_size
+                .fetch_sub(old_entry.data.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn touch(&self, key: &str) {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let mut lru_of_key = self.lru_of_key.lock().unwrap();
+        let mut lru = self.lru.lock().unwrap();
+        if let Some(old_seq) = lru_of_key.insert(key.to_string(), seq) {
+            lru.remove(&old_seq);
+        }
+        lru.insert(seq, key.to_string());
+    }
+
+    fn evict_to_capacity(&self) {
+        while self.current_size.load(Ordering::Relaxed) > self.capacity_bytes {
+            let oldest = self.lru.lock().unwrap().iter().next().map(|(seq, key)| (*seq, key.clone()));
+            let Some((_seq, key)) = oldest else { break };
+            self.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cache(capacity_bytes: u64) -> ContentCache {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        ContentCache::new(db.open_tree("test").unwrap(), capacity_bytes).unwrap()
+    }
+
+    #[test]
+    fn test_put_then_get_with_matching_modified() {
+        let cache = test_cache(1024 * 1024);
+        cache.put("/a.txt\0", 100, "text/plain".to_string(), b"hello".to_vec());
+
+        let hit = cache.get("/a.txt\0", 100).unwrap();
+        assert_eq!(hit.mime_type, "text/plain");
+        assert_eq!(hit.data, b"hello");
+    }
+
+    #[test]
+    fn test_get_with_stale_modified_is_a_miss_and_drops_the_entry() {
+        let cache = test_cache(1024 * 1024);
+        cache.put("/a.txt\0", 100, "text/plain".to_string(), b"hello".to_vec());
+
+        assert!(cache.get("/a.txt\0", 200).is_none());
+        assert!(cache.get("/a.txt\0", 100).is_none());
+    }
+
+    #[test]
+    fn test_invalidate_prefix_drops_all_discriminators() {
+        let cache = test_cache(1024 * 1024);
+        cache.put("/a.jpg\0128", 1, "image/jpeg".to_string(), vec![1]);
+        cache.put("/a.jpg\0256", 1, "image/jpeg".to_string(), vec![2]);
+        cache.put("/b.jpg\0128", 1, "image/jpeg".to_string(), vec![3]);
+
+        cache.invalidate_prefix("/a.jpg\0");
+
+        assert!(cache.get("/a.jpg\0128", 1).is_none());
+        assert!(cache.get("/a.jpg\0256", 1).is_none());
+        assert!(cache.get("/b.jpg\0128", 1).is_some());
+    }
+
+    #[test]
+    fn test_rename_prefix_preserves_discriminators() {
+        let cache = test_cache(1024 * 1024);
+        cache.put("/a.jpg\0128", 1, "image/jpeg".to_string(), vec![1]);
+        cache.put("/a.jpg\0256", 1, "image/jpeg".to_string(), vec![2]);
+
+        cache.rename_prefix("/a.jpg\0", "/renamed.jpg\0");
+
+        assert!(cache.get("/a.jpg\0128", 1).is_none());
+        assert_eq!(cache.get("/renamed.jpg\0128", 1).unwrap().data, vec![1]);
+        assert_eq!(cache.get("/renamed.jpg\0256", 1).unwrap().data, vec![2]);
+    }
+
+    #[test]
+    fn test_eviction_drops_least_recently_touched_entries() {
+        let cache = test_cache(10);
+        cache.put("/a\0", 1, "text/plain".to_string(), vec![0; 6]);
+        cache.put("/b\0", 1, "text/plain".to_string(), vec![0; 6]);
+
+        // Inserting /b pushed total size to 12 > capacity 10, so /a (never
+        // touched since) should have been evicted.
+        assert!(cache.get("/a\0", 1).is_none());
+        assert!(cache.get("/b\0", 1).is_some());
+    }
+}
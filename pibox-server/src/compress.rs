@@ -0,0 +1,91 @@
+//! Adaptive DEFLATE compression for outbound `ServerMessage` frames
+//!
+//! Broadcasting JSON over WebSocket (directory listings, search results) is
+//! bandwidth-bound, but compression is CPU-bound -- exactly the resource
+//! `load.rs` watches. We only compress when it's likely to pay off: the
+//! payload is big enough to matter, the client has advertised it can
+//! decompress (via `ClientCapabilities::can_compress`), and the server isn't
+//! already CPU constrained. The load monitor drives the shared level to 0
+//! while `cpu_percent` is at or above `CPU_HIGH_THRESHOLD` and restores it
+//! once load recovers, so the device never spends scarce cycles compressing
+//! while overloaded.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+/// Minimum serialized payload size before compression is considered
+pub(crate) const COMPRESSION_THRESHOLD_BYTES: usize = 1024;
+
+/// Default DEFLATE level used while the server isn't under load
+pub const DEFAULT_COMPRESSION_LEVEL: u8 = 6;
+
+/// Shared, lock-free compression level, updated by the load monitor and read
+/// by every client's broadcast forwarder
+#[derive(Debug)]
+pub struct CompressionLevel(AtomicU8);
+
+impl CompressionLevel {
+    pub fn new(level: u8) -> Self {
+        Self(AtomicU8::new(level))
+    }
+
+    pub fn set(&self, level: u8) {
+        self.0.store(level, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u8 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for CompressionLevel {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+/// DEFLATE-compress `json` if the level is nonzero, the client supports it,
+/// and the payload clears the size threshold. Returns `None` when the
+/// message should be sent raw.
+pub fn maybe_compress(json: &[u8], level: u8, client_supports_compression: bool) -> Option<Vec<u8>> {
+    if level == 0 || !client_supports_compression || json.len() < COMPRESSION_THRESHOLD_BYTES {
+        return None;
+    }
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level as u32));
+    encoder.write_all(json).ok()?;
+    encoder.finish().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_payload_not_compressed() {
+        let json = b"{\"type\":\"pong\"}";
+        assert!(maybe_compress(json, DEFAULT_COMPRESSION_LEVEL, true).is_none());
+    }
+
+    #[test]
+    fn test_disabled_level_skips_compression() {
+        let json = vec![b'a'; 4096];
+        assert!(maybe_compress(&json, 0, true).is_none());
+    }
+
+    #[test]
+    fn test_unsupported_client_skips_compression() {
+        let json = vec![b'a'; 4096];
+        assert!(maybe_compress(&json, DEFAULT_COMPRESSION_LEVEL, false).is_none());
+    }
+
+    #[test]
+    fn test_large_payload_compressed() {
+        let json = vec![b'a'; 4096];
+        let compressed = maybe_compress(&json, DEFAULT_COMPRESSION_LEVEL, true).unwrap();
+        assert!(compressed.len() < json.len());
+    }
+}
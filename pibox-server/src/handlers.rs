@@ -5,9 +5,9 @@ use std::sync::Arc;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Path, State,
     },
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
@@ -15,12 +15,23 @@ use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use pibox_core::{ClientMessage, ServerMessage, TokenPair};
+use pibox_core::auth::{AuthError, Credentials, Identity};
+use pibox_core::pairing;
+use pibox_core::protocol::TransferEncoding;
+use pibox_core::{ClientMessage, ServerMessage};
 
+use crate::compress::maybe_compress;
+use crate::metrics;
 use crate::state::AppState;
+use crate::thumbnail;
+use crate::transfer;
 
 pub type SharedState = Arc<RwLock<AppState>>;
 
+/// How long to wait for a worker's `OffloadResult` before giving up (or
+/// retrying once on a different worker)
+const OFFLOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 /// Health check endpoint
 pub async fn health_handler() -> impl IntoResponse {
     Json(serde_json::json!({
@@ -29,43 +40,143 @@ pub async fn health_handler() -> impl IntoResponse {
     }))
 }
 
+/// Prometheus text-format metrics, scraped by an external Prometheus server
+/// (or periodically pushed to a Pushgateway, see `metrics::push_loop`)
+pub async fn metrics_handler(State(state): State<SharedState>) -> impl IntoResponse {
+    let body = state.read().await.metrics.render();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        body,
+    )
+}
+
 /// Login request body
 #[derive(Deserialize)]
 pub struct LoginRequest {
     username: String,
     password: String,
+    #[serde(default)]
+    totp_code: Option<String>,
 }
 
-/// Login response
+/// Login response. A distinct `two_factor_required` status lets the TUI
+/// prompt for a code and resubmit, rather than treating it as a hard failure.
 #[derive(Serialize)]
-pub struct LoginResponse {
-    access_token: String,
-    refresh_token: String,
-    expires_in: u64,
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginResponse {
+    Ok {
+        access_token: String,
+        refresh_token: String,
+        expires_in: u64,
+    },
+    TwoFactorRequired,
 }
 
 /// HTTP login endpoint (alternative to WebSocket login)
-pub async fn login_handler(
-    State(state): State<SharedState>,
-    Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
-    // TODO: In production, validate against actual user database
-    // For now, accept any credentials for testing
-    if req.username.is_empty() || req.password.is_empty() {
-        return Err(StatusCode::UNAUTHORIZED);
+pub async fn login_handler(State(state): State<SharedState>, Json(req): Json<LoginRequest>) -> impl IntoResponse {
+    let s = state.read().await;
+    let identity = match s
+        .auth
+        .authenticate(Credentials::Password {
+            username: req.username,
+            password: req.password,
+            totp_code: req.totp_code,
+        })
+        .await
+    {
+        Ok(identity) => identity,
+        Err(AuthError::TwoFactorRequired) => return (StatusCode::UNAUTHORIZED, Json(LoginResponse::TwoFactorRequired)).into_response(),
+        Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
+    };
+
+    match s.auth.issue_tokens(&identity) {
+        Ok(tokens) => (
+            StatusCode::OK,
+            Json(LoginResponse::Ok {
+                access_token: tokens.access_token,
+                refresh_token: tokens.refresh_token,
+                expires_in: tokens.expires_in,
+            }),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Response to `POST /pair/start`: the code a client enters or scans (as a
+/// QR rendering of the `ws://` URI it encodes), good for
+/// `pairing::PAIRING_CODE_TTL_SECS`
+#[derive(Serialize)]
+pub struct PairStartResponse {
+    code: String,
+    expires_in: u64,
+    qr: String,
+}
+
+/// Mint a pairing code for out-of-band device enrollment: a headless client
+/// scans/enters the code and exchanges it for a real token pair via
+/// `pair_claim_handler` or `ClientMessage::Pair`, without ever needing a
+/// username or password.
+pub async fn pair_start_handler(State(state): State<SharedState>, headers: HeaderMap) -> impl IntoResponse {
+    let pairing = {
+        let mut s = state.write().await;
+        s.start_pairing()
+    };
+
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    let qr = pairing::render_qr(&pairing::pairing_uri(host, &pairing.code));
+
+    Json(PairStartResponse {
+        code: pairing.code,
+        expires_in: pairing::PAIRING_CODE_TTL_SECS,
+        qr,
+    })
+}
+
+/// Pairing claim request body
+#[derive(Deserialize)]
+pub struct PairClaimRequest {
+    code: String,
+}
+
+/// HTTP counterpart to `ClientMessage::Pair`, for clients that claim a
+/// pairing code without holding a WebSocket open while it's scanned/entered
+pub async fn pair_claim_handler(State(state): State<SharedState>, Json(req): Json<PairClaimRequest>) -> impl IntoResponse {
+    let claimed = {
+        let mut s = state.write().await;
+        s.claim_pairing(&req.code)
+    };
+
+    if !claimed {
+        return StatusCode::UNAUTHORIZED.into_response();
     }
 
     let s = state.read().await;
-    let tokens = s
-        .jwt_auth
-        .generate_tokens(&req.username, None)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(LoginResponse {
-        access_token: tokens.access_token,
-        refresh_token: tokens.refresh_token,
-        expires_in: tokens.expires_in,
-    }))
+    match s.auth.issue_tokens(&paired_device_identity(&req.code)) {
+        Ok(tokens) => (
+            StatusCode::OK,
+            Json(LoginResponse::Ok {
+                access_token: tokens.access_token,
+                refresh_token: tokens.refresh_token,
+                expires_in: tokens.expires_in,
+            }),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// The identity a claimed pairing code resolves to. There's no username to
+/// authenticate with, so the code itself (which is already a single-use
+/// secret) doubles as the device identifier.
+fn paired_device_identity(code: &str) -> Identity {
+    Identity {
+        username: format!("paired-device-{}", &code[..8.min(code.len())]),
+        device_id: Some(code.to_string()),
+    }
 }
 
 /// WebSocket upgrade handler
@@ -73,6 +184,31 @@ pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<SharedState>)
     ws.on_upgrade(|socket| handle_websocket(socket, state))
 }
 
+/// Unauthenticated download of a file via a token minted by
+/// `ClientMessage::CreateShare`. 404s on an unknown, expired, or
+/// downloads-exhausted token rather than distinguishing the reason, so a
+/// guessed token can't be used to probe which case applies.
+pub async fn share_handler(State(state): State<SharedState>, Path(token): Path<String>) -> impl IntoResponse {
+    let path = {
+        let mut s = state.write().await;
+        s.consume_share(&token)
+    };
+
+    let Some(path) = path else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let content = {
+        let s = state.read().await;
+        s.fb_client.download(&path).await
+    };
+
+    match content {
+        Ok(resource) => resource.body.into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 /// Handle WebSocket connection
 async fn handle_websocket(socket: WebSocket, state: SharedState) {
     let (mut sender, mut receiver) = socket.split();
@@ -80,9 +216,10 @@ async fn handle_websocket(socket: WebSocket, state: SharedState) {
     // Generate client ID
     let client_id = uuid::Uuid::new_v4().to_string();
     tracing::info!("New WebSocket connection: {}", client_id);
+    state.read().await.metrics.connection_opened();
 
     // Wait for authentication
-    let username = match wait_for_auth(&mut receiver, &state).await {
+    let username = match wait_for_auth(&mut receiver, &mut sender, &state).await {
         Some(u) => u,
         None => {
             tracing::warn!("Client {} failed authentication", client_id);
@@ -94,11 +231,13 @@ async fn handle_websocket(socket: WebSocket, state: SharedState) {
                     .unwrap().into(),
                 ))
                 .await;
+            state.read().await.metrics.connection_closed();
             return;
         }
     };
 
     tracing::info!("Client {} authenticated as {}", client_id, username);
+    state.read().await.metrics.session_authenticated();
 
     // Register client and get event receiver
     let mut event_rx = {
@@ -112,17 +251,65 @@ async fn handle_websocket(socket: WebSocket, state: SharedState) {
         s.event_tx.subscribe()
     };
 
-    // Spawn task to forward broadcast events to client
+    // Spawn task to forward both broadcast events and this client's own
+    // direct messages (e.g. transfer progress) to its socket
     let sender_clone = Arc::new(tokio::sync::Mutex::new(sender));
     let sender_for_broadcast = Arc::clone(&sender_clone);
+    let broadcast_state = state.clone();
+    let broadcast_client_id = client_id.clone();
     let broadcast_handle = tokio::spawn(async move {
-        let mut rx = broadcast_rx;
-        while let Ok(msg) = rx.recv().await {
-            let mut s = sender_for_broadcast.lock().await;
-            if let Ok(json) = serde_json::to_string(&msg) {
-                if s.send(Message::Text(json.into())).await.is_err() {
-                    break;
+        loop {
+            let msg = tokio::select! {
+                msg = broadcast_rx.recv() => match msg {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                },
+                msg = event_rx.recv() => match msg {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                },
+            };
+
+            if let ServerMessage::FsEvent(ref event) = msg {
+                let s = broadcast_state.read().await;
+                let in_scope = s
+                    .clients
+                    .get(&broadcast_client_id)
+                    .map(|c| {
+                        c.watched_paths
+                            .iter()
+                            .any(|watched| event.paths().iter().any(|p| p.starts_with(watched.as_str())))
+                    })
+                    .unwrap_or(false);
+                drop(s);
+                if !in_scope {
+                    continue;
+                }
+            }
+
+            let Ok(json) = serde_json::to_string(&msg) else {
+                continue;
+            };
+
+            let wire = {
+                let s = broadcast_state.read().await;
+                let level = s.compression_level.get();
+                let client_supports_compression = s
+                    .clients
+                    .get(&broadcast_client_id)
+                    .and_then(|c| c.capabilities.as_ref())
+                    .map(|caps| caps.can_compress)
+                    .unwrap_or(false);
+
+                match maybe_compress(json.as_bytes(), level, client_supports_compression) {
+                    Some(compressed) => Message::Binary(compressed.into()),
+                    None => Message::Text(json.into()),
                 }
+            };
+
+            let mut s = sender_for_broadcast.lock().await;
+            if s.send(wire).await.is_err() {
+                break;
             }
         }
     });
@@ -164,26 +351,70 @@ async fn handle_websocket(socket: WebSocket, state: SharedState) {
     broadcast_handle.abort();
     {
         let mut s = state.write().await;
-        s.unregister_client(&client_id);
+        let orphaned = s.unregister_client(&client_id);
+        for (task_id, requester) in orphaned {
+            s.send_to_client(
+                &requester,
+                ServerMessage::Error {
+                    message: format!("Offload {} failed: worker disconnected", task_id),
+                },
+            );
+        }
+        s.metrics.connection_closed();
     }
     tracing::info!("Client {} cleaned up", client_id);
 }
 
-/// Wait for client to authenticate
+/// Wait for client to authenticate. On a 2FA-enrolled account that didn't
+/// send a code, sends back `ServerMessage::TwoFactorRequired` and keeps
+/// waiting for the client to resubmit `Login` with the code filled in.
 async fn wait_for_auth(
     receiver: &mut futures::stream::SplitStream<WebSocket>,
+    sender: &mut futures::stream::SplitSink<WebSocket, Message>,
     state: &SharedState,
 ) -> Option<String> {
     // Give client 30 seconds to authenticate
     let timeout = tokio::time::timeout(std::time::Duration::from_secs(30), async {
         while let Some(Ok(msg)) = receiver.next().await {
-            if let Message::Text(text) = msg {
-                if let Ok(ClientMessage::Login { username, password }) = serde_json::from_str(&text) {
-                    // TODO: Validate against actual user store
-                    if !username.is_empty() && !password.is_empty() {
-                        return Some(username);
+            let Message::Text(text) = msg else { continue };
+            let Ok(parsed) = serde_json::from_str::<ClientMessage>(&text) else { continue };
+
+            match parsed {
+                ClientMessage::Login {
+                    username,
+                    password,
+                    totp_code,
+                } => {
+                    let outcome = {
+                        let s = state.read().await;
+                        s.auth.authenticate(Credentials::Password { username, password, totp_code }).await
+                    };
+
+                    match outcome {
+                        Ok(identity) => return Some(identity.username),
+                        Err(AuthError::TwoFactorRequired) => {
+                            let _ = sender
+                                .send(Message::Text(
+                                    serde_json::to_string(&ServerMessage::TwoFactorRequired).unwrap().into(),
+                                ))
+                                .await;
+                        }
+                        Err(_) => {}
                     }
                 }
+                // Same onboarding path as `pair_claim_handler`, but over the
+                // WebSocket for a client that's already connected and has no
+                // username/password to offer
+                ClientMessage::Pair { code } => {
+                    let claimed = {
+                        let mut s = state.write().await;
+                        s.claim_pairing(&code)
+                    };
+                    if claimed {
+                        return Some(paired_device_identity(&code).username);
+                    }
+                }
+                _ => {}
             }
         }
         None
@@ -192,6 +423,270 @@ async fn wait_for_auth(
     timeout.await.ok().flatten()
 }
 
+/// Above this size, a download's content is streamed straight through to
+/// the client without also being accumulated for the content cache -- so a
+/// transfer of a file far larger than this never holds more than one
+/// chunk's worth of it in memory, at the cost of that one transfer not
+/// being cached for next time.
+const STREAM_CACHE_LIMIT_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Fetch `path`'s bytes, serving a cache hit when the content cache's
+/// stored `modified` still matches the freshly fetched `FileEntry`, and
+/// populating the cache on a miss -- the same freshness-check pattern
+/// `ClientMessage::Thumbnail` already uses for its own cache.
+async fn download_cached(
+    path: &str,
+    state: &SharedState,
+) -> Result<(i64, String, Vec<u8>, Option<u64>), pibox_core::filebrowser::FilebrowserError> {
+    let entry = {
+        let s = state.read().await;
+        s.fb_client.get_info(path).await?
+    };
+
+    if let Some((mime_type, data)) = {
+        let s = state.read().await;
+        s.cached_download(path, entry.modified)
+    } {
+        return Ok((entry.modified, mime_type, data, None));
+    }
+
+    let resource = {
+        let s = state.read().await;
+        s.fb_client.download(path).await?
+    };
+    let mime_type = pibox_core::mime_sniff::sniff(&resource.body)
+        .map(str::to_string)
+        .or_else(|| entry.mime_type.clone())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    {
+        let s = state.read().await;
+        s.cache_download(path, entry.modified, mime_type.clone(), resource.body.clone());
+    }
+
+    Ok((entry.modified, mime_type, resource.body, resource.max_age))
+}
+
+/// Stream `path` to `client_id` in load-adaptive windows -- each one fetched
+/// via `FilebrowserClient::download_range` and sent on as a real `FileChunk`
+/// -- so a download never requires holding the whole file in memory at
+/// once, the way a single `ClientMessage::Download` buffered through
+/// `download_cached` used to. Falls back to the content cache, same as
+/// `download_cached`, when one is warm for this path.
+///
+/// Returns the mime type and total size sent, for `ClientMessage::Download`'s
+/// metrics/response bookkeeping.
+async fn stream_download(
+    client_id: &str,
+    path: &str,
+    transfer_id: &str,
+    state: &SharedState,
+) -> Result<(String, u64), pibox_core::filebrowser::FilebrowserError> {
+    let entry = {
+        let s = state.read().await;
+        s.fb_client.get_info(path).await?
+    };
+
+    {
+        let s = state.read().await;
+        s.send_to_client(
+            client_id,
+            ServerMessage::FileContentMeta {
+                transfer_id: transfer_id.to_string(),
+                size: entry.size,
+                mime_type: entry.mime_type.clone(),
+            },
+        );
+    }
+
+    if let Some((mime_type, data)) = {
+        let s = state.read().await;
+        s.cached_download(path, entry.modified)
+    } {
+        let size = data.len() as u64;
+        send_file_chunks(client_id, transfer_id, 0, &data, size, state).await;
+        return Ok((mime_type, size));
+    }
+
+    let mut mime_type: Option<String> = None;
+    let mut cached_copy = if entry.size <= STREAM_CACHE_LIMIT_BYTES {
+        Some(Vec::with_capacity(entry.size as usize))
+    } else {
+        None
+    };
+
+    if entry.size == 0 {
+        let s = state.read().await;
+        s.send_to_client(
+            client_id,
+            ServerMessage::FileChunk {
+                transfer_id: transfer_id.to_string(),
+                offset: 0,
+                data: Vec::new(),
+                eof: true,
+            },
+        );
+    }
+
+    let mut offset = 0u64;
+    while offset < entry.size {
+        let chunk_size = state.read().await.chunk_sizer.get() as u64;
+        let window = chunk_size.min(entry.size - offset).max(1);
+
+        let data = {
+            let s = state.read().await;
+            s.fb_client.download_range(path, offset, window).await?
+        };
+        if data.is_empty() {
+            break;
+        }
+
+        if mime_type.is_none() {
+            mime_type = pibox_core::mime_sniff::sniff(&data).map(str::to_string);
+        }
+        if let Some(buf) = cached_copy.as_mut() {
+            buf.extend_from_slice(&data);
+        }
+
+        let eof = offset + data.len() as u64 >= entry.size;
+
+        let (io_busy, cpu_percent) = {
+            let s = state.read().await;
+            s.send_to_client(
+                client_id,
+                ServerMessage::FileChunk {
+                    transfer_id: transfer_id.to_string(),
+                    offset,
+                    data: data.clone(),
+                    eof,
+                },
+            );
+            (s.load.io_busy, s.load.cpu_percent)
+        };
+
+        offset += data.len() as u64;
+
+        if eof {
+            break;
+        }
+        if let Some(delay) = transfer::inter_chunk_delay(io_busy, cpu_percent) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    let mime_type = mime_type.or(entry.mime_type).unwrap_or_else(|| "application/octet-stream".to_string());
+
+    if let Some(buf) = cached_copy {
+        let s = state.read().await;
+        s.cache_download(path, entry.modified, mime_type.clone(), buf);
+    }
+
+    Ok((mime_type, offset))
+}
+
+/// Cache a completed `OffloadTask::Thumbnail` result under the same
+/// (path, mtime, max_dim) key a locally-generated thumbnail would use, so a
+/// later request for the same path/size -- offloaded or not -- is a cache
+/// hit. Other task kinds have nothing worth caching here.
+async fn cache_offload_result(task: &pibox_core::protocol::OffloadTask, result: &[u8], state: &SharedState) {
+    let pibox_core::protocol::OffloadTask::Thumbnail { path, width, height, .. } = task else {
+        return;
+    };
+
+    let Ok(entry) = ({
+        let s = state.read().await;
+        s.fb_client.get_info(path).await
+    }) else {
+        return;
+    };
+
+    let max_dim = (*width).max(*height);
+    let mime_type = pibox_core::mime_sniff::sniff(result)
+        .map(str::to_string)
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    let s = state.read().await;
+    s.cache_thumbnail(path, entry.modified, max_dim, mime_type, result.to_vec());
+}
+
+/// Walk a downloaded range (already just the requested `[offset, offset +
+/// data.len())` window) in load-adaptive chunks, sending each as a
+/// `FileChunk` to `client_id`. `eof` is set once a chunk reaches
+/// `total_size` -- the actual end of the file, not just of this requested
+/// window -- so the client knows when a resumed transfer is complete.
+async fn send_file_chunks(client_id: &str, transfer_id: &str, base_offset: u64, data: &[u8], total_size: u64, state: &SharedState) {
+    let chunk_size = state.read().await.chunk_sizer.get();
+
+    for (rel_offset, chunk, _) in transfer::chunks(data, chunk_size) {
+        let offset = base_offset + rel_offset;
+        let eof = offset + chunk.len() as u64 >= total_size;
+
+        let (io_busy, cpu_percent) = {
+            let s = state.read().await;
+            s.send_to_client(
+                client_id,
+                ServerMessage::FileChunk {
+                    transfer_id: transfer_id.to_string(),
+                    offset,
+                    data: chunk.to_vec(),
+                    eof,
+                },
+            );
+            (s.load.io_busy, s.load.cpu_percent)
+        };
+
+        if eof {
+            break;
+        }
+
+        if let Some(delay) = transfer::inter_chunk_delay(io_busy, cpu_percent) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// After `OFFLOAD_TIMEOUT`, if `task_id` is still pending on `worker`, try
+/// once to redispatch it to a different eligible worker; if that also fails
+/// (or it's still pending after the retry's own timeout), give up and tell
+/// the requester.
+fn spawn_offload_timeout(state: SharedState, task_id: String, worker: String) {
+    tokio::spawn(async move {
+        tokio::time::sleep(OFFLOAD_TIMEOUT).await;
+
+        let still_pending = state.read().await.is_offload_pending(&task_id);
+        if !still_pending {
+            return;
+        }
+
+        let redispatched = state.write().await.redispatch_offload(&task_id, &worker);
+        match redispatched {
+            Ok(new_worker) => {
+                tracing::warn!("Offload {} timed out on {}, retrying on {}", task_id, worker, new_worker);
+                tokio::time::sleep(OFFLOAD_TIMEOUT).await;
+
+                let mut s = state.write().await;
+                if s.is_offload_pending(&task_id) {
+                    if let Some(requester) = s.complete_offload(&task_id) {
+                        s.send_to_client(
+                            &requester,
+                            ServerMessage::Error {
+                                message: format!("Offload {} timed out", task_id),
+                            },
+                        );
+                    }
+                }
+            }
+            Err(message) => {
+                tracing::warn!("Offload {} timed out on {}: {}", task_id, worker, message);
+                let mut s = state.write().await;
+                if let Some(requester) = s.complete_offload(&task_id) {
+                    s.send_to_client(&requester, ServerMessage::Error { message });
+                }
+            }
+        }
+    });
+}
+
 /// Handle a client message and return optional response
 async fn handle_client_message(
     client_id: &str,
@@ -203,7 +698,7 @@ async fn handle_client_message(
 
         ClientMessage::RefreshToken { refresh_token } => {
             let s = state.read().await;
-            match s.jwt_auth.refresh_tokens(&refresh_token) {
+            match s.auth.refresh(&refresh_token) {
                 Ok(tokens) => Some(ServerMessage::AuthSuccess(pibox_core::protocol::TokenPairResponse {
                     access_token: tokens.access_token,
                     refresh_token: tokens.refresh_token,
@@ -215,9 +710,36 @@ async fn handle_client_message(
             }
         }
 
+        ClientMessage::EnrollTotp => {
+            let s = state.read().await;
+            let Some(username) = s.clients.get(client_id).map(|c| c.username.clone()) else {
+                return Some(ServerMessage::Error {
+                    message: "Not authenticated".to_string(),
+                });
+            };
+            let provisioning_uri = s.auth.enroll_totp(&username);
+            Some(ServerMessage::TotpEnrolled { provisioning_uri })
+        }
+
+        ClientMessage::DisableTotp => {
+            let s = state.read().await;
+            let Some(username) = s.clients.get(client_id).map(|c| c.username.clone()) else {
+                return Some(ServerMessage::Error {
+                    message: "Not authenticated".to_string(),
+                });
+            };
+            s.auth.remove_totp(&username);
+            Some(ServerMessage::TotpDisabled)
+        }
+
         ClientMessage::ListDir { path } => {
+            let start = std::time::Instant::now();
             let s = state.read().await;
-            match s.fb_client.list_dir(&path).await {
+            let result = s.fb_client.list_dir(&path).await;
+            drop(s);
+            let ok = result.is_ok();
+
+            let resp = match result {
                 Ok(entries) => Some(ServerMessage::DirListing {
                     path,
                     entries: entries
@@ -240,14 +762,20 @@ async fn handle_client_message(
                     path,
                     message: e.to_string(),
                 }),
-            }
+            };
+
+            state.read().await.metrics.record_op(metrics::Op::List, ok, start.elapsed());
+            resp
         }
 
         ClientMessage::Download { path } => {
+            let start = std::time::Instant::now();
+
             // Check rate limit
             {
                 let mut s = state.write().await;
                 if !s.start_transfer() {
+                    s.metrics.transfer_rejected();
                     return Some(ServerMessage::OpError {
                         op: "download".to_string(),
                         path,
@@ -256,10 +784,8 @@ async fn handle_client_message(
                 }
             }
 
-            let result = {
-                let s = state.read().await;
-                s.fb_client.download(&path).await
-            };
+            let transfer_id = uuid::Uuid::new_v4().to_string();
+            let result = stream_download(client_id, &path, &transfer_id, state).await;
 
             // End transfer
             {
@@ -267,25 +793,100 @@ async fn handle_client_message(
                 s.end_transfer();
             }
 
-            match result {
-                Ok(content) => Some(ServerMessage::FileContent {
+            let ok = result.is_ok();
+            let resp = match result {
+                Ok((_, bytes_sent)) => {
+                    state.read().await.metrics.add_bytes_sent(bytes_sent);
+                    None
+                }
+                Err(e) => Some(ServerMessage::OpError {
+                    op: "download".to_string(),
                     path,
-                    content,
-                    mime_type: None, // TODO: detect mime type
+                    message: e.to_string(),
                 }),
+            };
+
+            state.read().await.metrics.record_op(metrics::Op::Download, ok, start.elapsed());
+            resp
+        }
+
+        ClientMessage::DownloadRange { path, offset, len, transfer_id } => {
+            // Check rate limit
+            {
+                let mut s = state.write().await;
+                if !s.start_transfer() {
+                    s.metrics.transfer_rejected();
+                    return Some(ServerMessage::OpError {
+                        op: "download_range".to_string(),
+                        path,
+                        message: "Too many concurrent transfers".to_string(),
+                    });
+                }
+            }
+
+            let info = {
+                let s = state.read().await;
+                s.fb_client.get_info(&path).await
+            };
+
+            let entry = match info {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let mut s = state.write().await;
+                    s.end_transfer();
+                    return Some(ServerMessage::OpError {
+                        op: "download_range".to_string(),
+                        path,
+                        message: e.to_string(),
+                    });
+                }
+            };
+
+            {
+                let s = state.read().await;
+                s.send_to_client(
+                    client_id,
+                    ServerMessage::FileContentMeta {
+                        transfer_id: transfer_id.clone(),
+                        size: entry.size,
+                        mime_type: entry.mime_type.clone(),
+                    },
+                );
+            }
+
+            let result = {
+                let s = state.read().await;
+                s.fb_client.download_range(&path, offset, len).await
+            };
+
+            let response = match result {
+                Ok(content) => {
+                    send_file_chunks(client_id, &transfer_id, offset, &content, entry.size, state).await;
+                    None
+                }
                 Err(e) => Some(ServerMessage::OpError {
-                    op: "download".to_string(),
+                    op: "download_range".to_string(),
                     path,
                     message: e.to_string(),
                 }),
+            };
+
+            {
+                let mut s = state.write().await;
+                s.end_transfer();
             }
+
+            response
         }
 
-        ClientMessage::Upload { path, content } => {
+        ClientMessage::Upload { path, content, encoding } => {
+            let start = std::time::Instant::now();
+
             // Check rate limit
             {
                 let mut s = state.write().await;
                 if !s.start_transfer() {
+                    s.metrics.transfer_rejected();
                     return Some(ServerMessage::OpError {
                         op: "upload".to_string(),
                         path,
@@ -294,6 +895,19 @@ async fn handle_client_message(
                 }
             }
 
+            let content_len = content.len() as u64;
+            let content = match pibox_core::compression::decompress(&content, encoding.unwrap_or(TransferEncoding::Identity)) {
+                Ok(content) => content,
+                Err(e) => {
+                    let mut s = state.write().await;
+                    s.end_transfer();
+                    return Some(ServerMessage::OpError {
+                        op: "upload".to_string(),
+                        path,
+                        message: e.to_string(),
+                    });
+                }
+            };
             let result = {
                 let s = state.read().await;
                 s.fb_client.upload(&path, &content, true).await
@@ -305,7 +919,12 @@ async fn handle_client_message(
                 s.end_transfer();
             }
 
-            match result {
+            let ok = result.is_ok();
+            if ok {
+                state.read().await.metrics.add_bytes_received(content_len);
+            }
+
+            let resp = match result {
                 Ok(()) => {
                     // Broadcast file created event
                     let s = state.read().await;
@@ -323,41 +942,183 @@ async fn handle_client_message(
                     path,
                     message: e.to_string(),
                 }),
+            };
+
+            state.read().await.metrics.record_op(metrics::Op::Upload, ok, start.elapsed());
+            resp
+        }
+
+        ClientMessage::BeginUpload { path, total_size, upload_id } => {
+            let mut s = state.write().await;
+            s.begin_upload(path, total_size, upload_id.clone());
+            Some(ServerMessage::ChunkAck { upload_id, next_offset: 0 })
+        }
+
+        ClientMessage::UploadChunk { upload_id, offset, data } => {
+            let progress = state.read().await.upload_progress(&upload_id);
+            let Some((tmp_path, expected)) = progress else {
+                return Some(ServerMessage::Error {
+                    message: format!("Unknown upload {}", upload_id),
+                });
+            };
+
+            // A chunk that doesn't pick up where the last acked one left
+            // off (e.g. resent after a reconnect) is rejected rather than
+            // applied out of order; the ack tells the client where to
+            // actually resume from
+            if offset != expected {
+                return Some(ServerMessage::ChunkAck { upload_id, next_offset: expected });
+            }
+
+            {
+                let mut s = state.write().await;
+                if !s.start_transfer() {
+                    s.metrics.transfer_rejected();
+                    return Some(ServerMessage::Error {
+                        message: "Too many concurrent transfers".to_string(),
+                    });
+                }
+            }
+
+            let data_len = data.len() as u64;
+            let result = {
+                let s = state.read().await;
+                s.fb_client.upload_chunk(&tmp_path, offset, &data).await
+            };
+
+            {
+                let mut s = state.write().await;
+                s.end_transfer();
+            }
+
+            match result {
+                Ok(()) => {
+                    let next_offset = {
+                        let mut s = state.write().await;
+                        s.record_upload_chunk(&upload_id, offset, data_len)
+                            .unwrap_or(expected + data_len)
+                    };
+                    state.read().await.metrics.add_bytes_received(data_len);
+                    Some(ServerMessage::ChunkAck { upload_id, next_offset })
+                }
+                Err(e) => Some(ServerMessage::Error {
+                    message: format!("Upload chunk failed: {}", e),
+                }),
             }
         }
 
-        ClientMessage::Delete { path } => {
+        ClientMessage::CommitUpload { upload_id } => {
+            let finished = {
+                let mut s = state.write().await;
+                s.finish_upload(&upload_id)
+            };
+
+            let Some((path, tmp_path, total_size, received)) = finished else {
+                return Some(ServerMessage::Error {
+                    message: format!("Unknown upload {}", upload_id),
+                });
+            };
+
+            if received != total_size {
+                return Some(ServerMessage::OpError {
+                    op: "commit_upload".to_string(),
+                    path,
+                    message: format!("Received {} of {} expected bytes", received, total_size),
+                });
+            }
+
             let result = {
                 let s = state.read().await;
-                s.fb_client.delete(&path).await
+                s.fb_client.rename(&tmp_path, &path).await
             };
 
             match result {
                 Ok(()) => {
                     let s = state.read().await;
-                    s.broadcast(ServerMessage::FsEvent(pibox_core::protocol::FsEvent::Deleted {
+                    s.broadcast(ServerMessage::FsEvent(pibox_core::protocol::FsEvent::Created {
                         path: path.clone(),
+                        is_dir: false,
                     }));
                     Some(ServerMessage::OpSuccess {
-                        op: "delete".to_string(),
+                        op: "commit_upload".to_string(),
                         path,
                     })
                 }
+                Err(e) => Some(ServerMessage::OpError {
+                    op: "commit_upload".to_string(),
+                    path,
+                    message: e.to_string(),
+                }),
+            }
+        }
+
+        ClientMessage::Delete { path } => {
+            let start = std::time::Instant::now();
+
+            // Trash rather than unlink, so the client can undo the delete
+            let result = {
+                let s = state.read().await;
+                s.fb_client.trash(&path).await
+            };
+            let ok = result.is_ok();
+
+            let resp = match result {
+                Ok(trash_path) => {
+                    let s = state.read().await;
+                    s.broadcast(ServerMessage::FsEvent(pibox_core::protocol::FsEvent::Deleted {
+                        path: path.clone(),
+                    }));
+                    Some(ServerMessage::Trashed {
+                        original_path: path,
+                        trash_path,
+                    })
+                }
                 Err(e) => Some(ServerMessage::OpError {
                     op: "delete".to_string(),
                     path,
                     message: e.to_string(),
                 }),
+            };
+
+            state.read().await.metrics.record_op(metrics::Op::Delete, ok, start.elapsed());
+            resp
+        }
+
+        ClientMessage::Restore { trash_path, original_path } => {
+            let result = {
+                let s = state.read().await;
+                s.fb_client.restore(&trash_path, &original_path).await
+            };
+
+            match result {
+                Ok(()) => {
+                    let s = state.read().await;
+                    s.broadcast(ServerMessage::FsEvent(pibox_core::protocol::FsEvent::Created {
+                        path: original_path.clone(),
+                        is_dir: false,
+                    }));
+                    Some(ServerMessage::OpSuccess {
+                        op: "restore".to_string(),
+                        path: original_path,
+                    })
+                }
+                Err(e) => Some(ServerMessage::OpError {
+                    op: "restore".to_string(),
+                    path: original_path,
+                    message: e.to_string(),
+                }),
             }
         }
 
         ClientMessage::Rename { from, to } => {
+            let start = std::time::Instant::now();
             let result = {
                 let s = state.read().await;
                 s.fb_client.rename(&from, &to).await
             };
+            let ok = result.is_ok();
 
-            match result {
+            let resp = match result {
                 Ok(()) => {
                     let s = state.read().await;
                     s.broadcast(ServerMessage::FsEvent(pibox_core::protocol::FsEvent::Renamed {
@@ -374,16 +1135,21 @@ async fn handle_client_message(
                     path: from,
                     message: e.to_string(),
                 }),
-            }
+            };
+
+            state.read().await.metrics.record_op(metrics::Op::Rename, ok, start.elapsed());
+            resp
         }
 
         ClientMessage::Mkdir { path } => {
+            let start = std::time::Instant::now();
             let result = {
                 let s = state.read().await;
                 s.fb_client.mkdir(&path).await
             };
+            let ok = result.is_ok();
 
-            match result {
+            let resp = match result {
                 Ok(()) => {
                     let s = state.read().await;
                     s.broadcast(ServerMessage::FsEvent(pibox_core::protocol::FsEvent::Created {
@@ -400,23 +1166,202 @@ async fn handle_client_message(
                     path,
                     message: e.to_string(),
                 }),
+            };
+
+            state.read().await.metrics.record_op(metrics::Op::Mkdir, ok, start.elapsed());
+            resp
+        }
+
+        ClientMessage::Watch { path } => {
+            let mut s = state.write().await;
+            s.watch_path(client_id, path);
+            None
+        }
+
+        ClientMessage::Unwatch { path } => {
+            let mut s = state.write().await;
+            s.unwatch_path(client_id, &path);
+            None
+        }
+
+        ClientMessage::CreateShare { path, expires_in, download_limit } => {
+            let mut s = state.write().await;
+            let Some(username) = s.clients.get(client_id).map(|c| c.username.clone()) else {
+                return Some(ServerMessage::Error {
+                    message: "Not authenticated".to_string(),
+                });
+            };
+            let share = s.create_share(&username, path, expires_in, download_limit);
+            Some(ServerMessage::ShareCreated {
+                url: format!("/share/{}", share.token),
+                token: share.token,
+            })
+        }
+
+        ClientMessage::RevokeShare { token } => {
+            let mut s = state.write().await;
+            let Some(username) = s.clients.get(client_id).map(|c| c.username.clone()) else {
+                return Some(ServerMessage::Error {
+                    message: "Not authenticated".to_string(),
+                });
+            };
+            if s.revoke_share(&username, &token) {
+                Some(ServerMessage::OpSuccess {
+                    op: "revoke_share".to_string(),
+                    path: token,
+                })
+            } else {
+                Some(ServerMessage::OpError {
+                    op: "revoke_share".to_string(),
+                    path: token,
+                    message: "Share not found".to_string(),
+                })
             }
         }
 
+        ClientMessage::Thumbnail { path, max_dim } => {
+            {
+                let mut s = state.write().await;
+                if !s.start_transfer() {
+                    s.metrics.transfer_rejected();
+                    return Some(ServerMessage::OpError {
+                        op: "thumbnail".to_string(),
+                        path,
+                        message: "Too many concurrent transfers".to_string(),
+                    });
+                }
+            }
+
+            let info = {
+                let s = state.read().await;
+                s.fb_client.get_info(&path).await
+            };
+
+            let entry = match info {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let mut s = state.write().await;
+                    s.end_transfer();
+                    return Some(ServerMessage::OpError {
+                        op: "thumbnail".to_string(),
+                        path,
+                        message: e.to_string(),
+                    });
+                }
+            };
+
+            let cached = {
+                let s = state.read().await;
+                s.cached_thumbnail(&path, entry.modified, max_dim)
+            };
+
+            let resp = if let Some((mime_type, data)) = cached {
+                Some(ServerMessage::ThumbnailData { path, mime_type, data })
+            } else {
+                let content = download_cached(&path, state).await;
+
+                match content {
+                    Ok((_, mime_type, content, _)) => match thumbnail::generate(&content, &mime_type, max_dim) {
+                        Ok(data) => {
+                            {
+                                let s = state.read().await;
+                                s.cache_thumbnail(&path, entry.modified, max_dim, mime_type.clone(), data.clone());
+                            }
+
+                            Some(ServerMessage::ThumbnailData { path, mime_type, data })
+                        }
+                        Err(e) => Some(ServerMessage::OpError {
+                            op: "thumbnail".to_string(),
+                            path,
+                            message: e.to_string(),
+                        }),
+                    },
+                    Err(e) => Some(ServerMessage::OpError {
+                        op: "thumbnail".to_string(),
+                        path,
+                        message: e.to_string(),
+                    }),
+                }
+            };
+
+            {
+                let mut s = state.write().await;
+                s.end_transfer();
+            }
+
+            resp
+        }
+
+        ClientMessage::ListShares => {
+            let s = state.read().await;
+            let Some(username) = s.clients.get(client_id).map(|c| c.username.clone()) else {
+                return Some(ServerMessage::Error {
+                    message: "Not authenticated".to_string(),
+                });
+            };
+            let shares = s
+                .list_shares(&username)
+                .into_iter()
+                .map(|share| pibox_core::protocol::ShareSummary {
+                    token: share.token.clone(),
+                    path: share.path.clone(),
+                    expires_at: share.expires_at,
+                    downloads_remaining: share.downloads_remaining,
+                })
+                .collect();
+            Some(ServerMessage::ShareList { shares })
+        }
+
         ClientMessage::Capabilities(caps) => {
             let mut s = state.write().await;
             s.update_client_capabilities(client_id, caps);
             None // No response needed
         }
 
-        ClientMessage::OffloadResult { task_id, result } => {
-            // Handle offload result from client
-            tracing::info!("Received offload result for task {}", task_id);
-            // TODO: Route result to original requester
+        ClientMessage::OffloadRequest { task_id, task } => {
+            let dispatched = {
+                let mut s = state.write().await;
+                s.dispatch_offload(client_id, task_id.clone(), task)
+            };
+
+            match dispatched {
+                Ok(worker) => {
+                    tracing::info!("Dispatched offload {} to {}", task_id, worker);
+                    spawn_offload_timeout(state.clone(), task_id, worker);
+                    None
+                }
+                Err(message) => Some(ServerMessage::Error { message }),
+            }
+        }
+
+        ClientMessage::OffloadResult { task_id, result, encoding } => {
+            let result = match pibox_core::compression::decompress(&result, encoding.unwrap_or(TransferEncoding::Identity)) {
+                Ok(result) => result,
+                Err(e) => {
+                    tracing::warn!("Failed to decompress offload result for {}: {}", task_id, e);
+                    return None;
+                }
+            };
+
+            let completed = {
+                let mut s = state.write().await;
+                s.complete_offload(&task_id)
+            };
+
+            match completed {
+                Some((requester, task)) => {
+                    cache_offload_result(&task, &result, state).await;
+
+                    let s = state.read().await;
+                    s.send_to_client(&requester, ServerMessage::OffloadComplete { task_id, result });
+                }
+                None => tracing::warn!("Offload result for unknown or already-resolved task {}", task_id),
+            }
             None
         }
 
         // Already handled in wait_for_auth
         ClientMessage::Login { .. } => None,
+        ClientMessage::Pair { .. } => None,
     }
 }
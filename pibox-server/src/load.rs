@@ -1,29 +1,89 @@
 //! Server load monitoring for adaptive behavior
 //!
-//! Monitors CPU, RAM, and I/O to:
+//! Monitors CPU, RAM, disk I/O, and temperature to:
 //! - Throttle operations when overloaded
 //! - Suggest clients handle heavy tasks locally
 //! - Offload work to capable clients
 
 use std::sync::Arc;
-use sysinfo::System;
+use sysinfo::{Disks, System};
 use tokio::sync::RwLock;
 use tokio::time::{interval, Duration};
 
 use pibox_core::protocol::{LoadHint, ServerLoad};
 use pibox_core::ServerMessage;
 
+use crate::compress::DEFAULT_COMPRESSION_LEVEL;
 use crate::state::AppState;
 
 /// Load thresholds for adaptive behavior
-const CPU_HIGH_THRESHOLD: f32 = 80.0;
+pub(crate) const CPU_HIGH_THRESHOLD: f32 = 80.0;
 const CPU_CRITICAL_THRESHOLD: f32 = 95.0;
+/// Hysteresis exit threshold: a throttle hint set at `CPU_HIGH_THRESHOLD`
+/// only clears once the smoothed CPU load drops below this, so the hint
+/// doesn't flap on and off around 80%
+const CPU_EXIT_THRESHOLD: f32 = 65.0;
 const RAM_LOW_MB: u64 = 100;
 const RAM_CRITICAL_MB: u64 = 50;
+/// Minimum CPU temperature in Celsius to consider the device thermal-throttling
+const THERMAL_THROTTLE_CELSIUS: f32 = 80.0;
+/// Disk throughput above which we consider the device I/O busy
+const IO_BUSY_BYTES_PER_TICK: u64 = 5 * 1024 * 1024;
+
+/// Smoothing factor for the CPU/RAM exponential moving averages. Lower is
+/// smoother; 0.3 tracks real trends in a few ticks without reacting to a
+/// single noisy sample.
+const EMA_ALPHA: f32 = 0.3;
+
+/// Ticks a hint must stay below its exit threshold before it's cleared, so
+/// a momentary dip doesn't cause clients to flip back and forth
+const HYSTERESIS_DEBOUNCE_TICKS: u32 = 2;
+
+/// Smoothed load metrics plus the hysteresis state needed to debounce hints
+struct LoadTracker {
+    cpu_ema: f32,
+    ram_free_ema: f32,
+    throttling: bool,
+    ticks_below_exit: u32,
+}
+
+impl LoadTracker {
+    fn new() -> Self {
+        Self {
+            cpu_ema: 0.0,
+            ram_free_ema: 0.0,
+            throttling: false,
+            ticks_below_exit: 0,
+        }
+    }
+
+    /// Fold in a new sample and update the hysteresis state, returning the
+    /// smoothed (cpu_percent, ram_free_mb) pair to report
+    fn update(&mut self, cpu_sample: f32, ram_free_sample: u64) -> (f32, u64) {
+        self.cpu_ema = EMA_ALPHA * cpu_sample + (1.0 - EMA_ALPHA) * self.cpu_ema;
+        self.ram_free_ema = EMA_ALPHA * ram_free_sample as f32 + (1.0 - EMA_ALPHA) * self.ram_free_ema;
+
+        if self.cpu_ema >= CPU_HIGH_THRESHOLD {
+            self.throttling = true;
+            self.ticks_below_exit = 0;
+        } else if self.throttling && self.cpu_ema < CPU_EXIT_THRESHOLD {
+            self.ticks_below_exit += 1;
+            if self.ticks_below_exit >= HYSTERESIS_DEBOUNCE_TICKS {
+                self.throttling = false;
+            }
+        } else {
+            self.ticks_below_exit = 0;
+        }
+
+        (self.cpu_ema, self.ram_free_ema as u64)
+    }
+}
 
 /// Main load monitoring loop
 pub async fn monitor_loop(state: Arc<RwLock<AppState>>) {
     let mut sys = System::new_all();
+    let mut disks = Disks::new_with_refreshed_list();
+    let mut tracker = LoadTracker::new();
     let interval_secs = {
         let s = state.read().await;
         s.load_report_interval
@@ -37,22 +97,30 @@ pub async fn monitor_loop(state: Arc<RwLock<AppState>>) {
         // Refresh system info
         sys.refresh_cpu_usage();
         sys.refresh_memory();
+        disks.refresh(true);
 
         // Calculate metrics
-        let cpu_percent = sys.global_cpu_usage();
-        let ram_free_mb = sys.available_memory() / 1024 / 1024;
+        let cpu_sample = sys.global_cpu_usage();
+        let ram_free_sample = sys.available_memory() / 1024 / 1024;
+        let (cpu_percent, ram_free_mb) = tracker.update(cpu_sample, ram_free_sample);
+
+        let io_busy = disks
+            .list()
+            .iter()
+            .map(|d| d.usage().read_bytes + d.usage().written_bytes)
+            .sum::<u64>()
+            >= IO_BUSY_BYTES_PER_TICK;
 
-        // Determine hints based on load
-        let hints = generate_hints(cpu_percent, ram_free_mb);
+        let cpu_temp_c = read_cpu_temp_c();
 
-        // Check I/O busy (simplified - just check if CPU iowait is high)
-        // In a real implementation, you'd check disk I/O specifically
-        let io_busy = cpu_percent > CPU_HIGH_THRESHOLD;
+        // Determine hints based on smoothed load, hysteresis state, and temperature
+        let hints = generate_hints(tracker.throttling, cpu_percent, ram_free_mb, cpu_temp_c);
 
         let load = ServerLoad {
             cpu_percent,
             ram_free_mb,
             io_busy,
+            cpu_temp_c,
             hints,
         };
 
@@ -61,32 +129,81 @@ pub async fn monitor_loop(state: Arc<RwLock<AppState>>) {
             let mut s = state.write().await;
             s.load = load.clone();
 
+            // Drop outbound frame compression to zero while the CPU is
+            // saturated (compressing is itself CPU work), and restore it
+            // once load recovers, rather than leaving it disabled forever.
+            if tracker.throttling {
+                s.compression_level.set(0);
+            } else {
+                s.compression_level.set(DEFAULT_COMPRESSION_LEVEL);
+            }
+
+            // Slow-start the transfer chunk size while healthy, and back off
+            // like TCP congestion control once I/O or CPU is under pressure.
+            if io_busy || tracker.throttling {
+                s.chunk_sizer.shrink();
+            } else {
+                s.chunk_sizer.grow();
+            }
+
             // Broadcast to all clients
             s.broadcast(ServerMessage::Load(load));
         }
 
         tracing::debug!(
-            "Load: CPU {:.1}%, RAM free {}MB, {} transfers active",
+            "Load: CPU {:.1}%, RAM free {}MB, temp {:?}C, {} transfers active",
             cpu_percent,
             ram_free_mb,
+            cpu_temp_c,
             state.read().await.active_transfers
         );
     }
 }
 
-/// Generate load hints based on current metrics
-fn generate_hints(cpu_percent: f32, ram_free_mb: u64) -> Vec<LoadHint> {
+/// Read CPU temperature from the Linux thermal subsystem, taking the
+/// hottest zone reported. Returns `None` on platforms without
+/// `/sys/class/thermal` (e.g. while developing off-device).
+fn read_cpu_temp_c() -> Option<f32> {
+    let mut hottest: Option<f32> = None;
+
+    for entry in std::fs::read_dir("/sys/class/thermal").ok()?.flatten() {
+        let path = entry.path().join("temp");
+        let Ok(raw) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(millidegrees) = raw.trim().parse::<f32>() else {
+            continue;
+        };
+
+        let celsius = millidegrees / 1000.0;
+        let is_hotter = match hottest {
+            Some(h) => celsius > h,
+            None => true,
+        };
+        if is_hotter {
+            hottest = Some(celsius);
+        }
+    }
+
+    hottest
+}
+
+/// Generate load hints based on smoothed/debounced load and temperature
+fn generate_hints(throttling: bool, cpu_percent: f32, ram_free_mb: u64, cpu_temp_c: Option<f32>) -> Vec<LoadHint> {
     let mut hints = Vec::new();
 
-    // CPU-based hints
-    if cpu_percent >= CPU_CRITICAL_THRESHOLD {
+    if throttling {
         hints.push(LoadHint::ThrottleTransfers);
         hints.push(LoadHint::GenerateThumbnailsLocally);
-        hints.push(LoadHint::SearchLocally);
+    } else {
+        hints.push(LoadHint::PreferCompression);
+    }
+
+    // Past the high threshold, CPU load can still climb further; once it
+    // crosses the critical threshold, treat it the same as critically low
+    // RAM -- the device needs active recovery, not just reduced throughput.
+    if cpu_percent >= CPU_CRITICAL_THRESHOLD {
         hints.push(LoadHint::Recovering);
-    } else if cpu_percent >= CPU_HIGH_THRESHOLD {
-        hints.push(LoadHint::ThrottleTransfers);
-        hints.push(LoadHint::GenerateThumbnailsLocally);
     }
 
     // RAM-based hints
@@ -97,6 +214,13 @@ fn generate_hints(cpu_percent: f32, ram_free_mb: u64) -> Vec<LoadHint> {
         hints.push(LoadHint::SearchLocally);
     }
 
+    if let Some(temp) = cpu_temp_c {
+        if temp >= THERMAL_THROTTLE_CELSIUS {
+            hints.push(LoadHint::ThermalThrottle);
+            hints.push(LoadHint::GenerateThumbnailsLocally);
+        }
+    }
+
     hints
 }
 
@@ -106,20 +230,67 @@ mod tests {
 
     #[test]
     fn test_hints_normal_load() {
-        let hints = generate_hints(50.0, 500);
-        assert!(hints.is_empty());
+        let hints = generate_hints(false, 50.0, 500, Some(45.0));
+        assert_eq!(hints, vec![LoadHint::PreferCompression]);
     }
 
     #[test]
-    fn test_hints_high_cpu() {
-        let hints = generate_hints(85.0, 500);
+    fn test_hints_throttling() {
+        let hints = generate_hints(true, 85.0, 500, None);
         assert!(hints.contains(&LoadHint::ThrottleTransfers));
         assert!(hints.contains(&LoadHint::GenerateThumbnailsLocally));
+        assert!(!hints.contains(&LoadHint::PreferCompression));
+    }
+
+    #[test]
+    fn test_hints_critical_cpu() {
+        let hints = generate_hints(true, 96.0, 500, None);
+        assert!(hints.contains(&LoadHint::Recovering));
+    }
+
+    #[test]
+    fn test_hints_high_cpu_below_critical_has_no_recovering() {
+        let hints = generate_hints(true, 85.0, 500, None);
+        assert!(!hints.contains(&LoadHint::Recovering));
     }
 
     #[test]
-    fn test_hints_critical_load() {
-        let hints = generate_hints(96.0, 40);
+    fn test_hints_critical_ram() {
+        let hints = generate_hints(false, 40.0, 40, None);
         assert!(hints.contains(&LoadHint::Recovering));
     }
+
+    #[test]
+    fn test_hints_thermal_throttle() {
+        let hints = generate_hints(false, 40.0, 500, Some(85.0));
+        assert!(hints.contains(&LoadHint::ThermalThrottle));
+    }
+
+    #[test]
+    fn test_tracker_hysteresis_debounces_exit() {
+        let mut tracker = LoadTracker::new();
+
+        // Spike above the enter threshold enough ticks for the EMA to cross it
+        for _ in 0..10 {
+            tracker.update(100.0, 500);
+        }
+        assert!(tracker.throttling);
+
+        // A single tick back below the exit threshold isn't enough to clear
+        tracker.update(0.0, 500);
+        assert!(tracker.throttling);
+
+        // But enough consecutive low ticks should clear it
+        for _ in 0..10 {
+            tracker.update(0.0, 500);
+        }
+        assert!(!tracker.throttling);
+    }
+
+    #[test]
+    fn test_tracker_ema_smooths_spikes() {
+        let mut tracker = LoadTracker::new();
+        let (cpu, _) = tracker.update(100.0, 500);
+        assert!(cpu < 100.0, "a single spike should be smoothed, got {cpu}");
+    }
 }
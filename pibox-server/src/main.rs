@@ -6,11 +6,21 @@
 //! - Manages load and offloads heavy ops to capable clients
 //! - Rate limits operations to protect embedded device CPU
 
+mod cache;
+mod compress;
 mod handlers;
 mod load;
+mod metrics;
 mod state;
+mod thumbnail;
+mod tls;
+mod transfer;
+mod uploads;
+mod watcher;
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use axum::{
@@ -21,6 +31,7 @@ use tokio::sync::RwLock;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+use pibox_core::auth::AuthProvider;
 use pibox_core::{Config, JwtAuth};
 
 use crate::state::AppState;
@@ -33,70 +44,288 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // Load configuration
-    let config = Config::load().unwrap_or_else(|e| {
+    // Load configuration. `Config::load()` first so an on-disk config
+    // behind `CURRENT_SCHEMA_VERSION` gets migrated and rewritten in place --
+    // its return value is kept only as a fallback. `load_layered` then
+    // re-reads the (now-current) file and layers `PIBOX_SECTION__FIELD`
+    // environment variable overrides on top of it, field-wise; no CLI flag
+    // parser exists in this binary yet, so `cli_overrides` is always empty.
+    let fallback = Config::load().unwrap_or_else(|e| {
         tracing::warn!("Failed to load config: {}, using defaults", e);
         Config::default()
     });
+    let config = Config::load_layered(&HashMap::new()).unwrap_or_else(|e| {
+        tracing::warn!("Failed to apply layered config overrides: {}, falling back to plain load", e);
+        fallback
+    });
+    for (path, source) in config.describe_sources() {
+        tracing::debug!("config {} from {}", path, source);
+    }
+
+    // Lint the config file for typos/unknown fields and semantic issues
+    // (e.g. `access_token_ttl >= refresh_token_ttl`). This is a warning, not
+    // a boot-blocking failure -- `config` above already parsed permissively
+    // and is serving regardless, the same way a stray/misspelled field has
+    // always been tolerated rather than refused outright.
+    if let Ok(path) = Config::config_path() {
+        if path.exists() {
+            if let Err(e) = Config::load_strict(&path) {
+                tracing::warn!("Config lint: {}", e);
+            }
+        }
+    }
+
+    // Bring up any configured connectivity (WiFi, Tailscale) before anything
+    // else needs the network -- a no-op when `[server.network]` is left at
+    // its defaults.
+    for result in config.server.network.apply() {
+        if result.success {
+            tracing::info!("{}: {}", result.action, result.message);
+        } else {
+            tracing::warn!("{}: {}", result.action, result.message);
+        }
+    }
 
-    // Initialize JWT auth
-    let jwt_secret = if let Some(ref secret) = config.server.jwt_secret {
+    // Initialize JWT auth. An explicitly configured legacy HMAC secret takes
+    // precedence; otherwise use a persisted Ed25519 keypair so tokens
+    // survive a restart.
+    let jwt_auth = if let Some(ref secret) = config.server.jwt_secret {
         use base64::Engine;
-        base64::engine::general_purpose::STANDARD
+        let secret = base64::engine::general_purpose::STANDARD
             .decode(secret)
-            .expect("Invalid JWT secret (must be base64)")
+            .expect("Invalid JWT secret (must be base64)");
+        JwtAuth::new(
+            &secret,
+            Some(config.server.access_token_ttl),
+            Some(config.server.refresh_token_ttl),
+        )
     } else {
-        // Generate random secret
-        let secret = pibox_core::auth::generate_secret();
-        tracing::info!("Generated random JWT secret (will change on restart)");
-        secret.to_vec()
+        let key_path = pibox_core::auth::default_key_path()?;
+        tracing::info!("Using persisted JWT signing key at {}", key_path.display());
+        JwtAuth::persisted(
+            &key_path,
+            Some(config.server.access_token_ttl),
+            Some(config.server.refresh_token_ttl),
+        )?
     };
 
-    let jwt_auth = JwtAuth::new(
-        &jwt_secret,
-        Some(config.server.access_token_ttl),
-        Some(config.server.refresh_token_ttl),
-    );
+    // `JwtAuth` is the default credential verifier; deployments can swap in
+    // an alternate `AuthProvider` (e.g. delegating to Filebrowser's own
+    // login, or an API-token provider for headless devices) here.
+    let auth: Box<dyn AuthProvider> = Box::new(jwt_auth);
 
-    // Initialize Filebrowser client
-    let mut fb_client = pibox_core::FilebrowserClient::new(&config.server.filebrowser_url);
+    // Initialize Filebrowser client. With a configured username/password,
+    // the client can transparently re-login on a mid-session token expiry
+    // (surfaced by Filebrowser as a 401/403) instead of every in-flight
+    // operation failing with `PermissionDenied` until restarted.
+    let fb_client = match (&config.server.filebrowser_username, &config.server.filebrowser_password) {
+        (Some(username), Some(password)) => {
+            let client = pibox_core::FilebrowserClient::with_credentials(
+                &config.server.filebrowser_url,
+                username,
+                password,
+            );
+            if let Err(e) = client.login(username, password).await {
+                tracing::warn!("Failed to authenticate with Filebrowser backend: {}", e);
+            }
+            client
+        }
+        _ => pibox_core::FilebrowserClient::new(&config.server.filebrowser_url),
+    };
 
-    // For now, we'll use the server's Filebrowser token directly
-    // In production, you'd want to configure this or use service auth
     tracing::info!("Filebrowser backend: {}", config.server.filebrowser_url);
 
+    // Remove any chunked-upload temp files left behind by a crash or a
+    // client that never reconnected to finish its upload
+    uploads::cleanup_stale_temp_files(&fb_client, "/").await;
+
+    // Download/thumbnail content cache, split into two sled trees under one
+    // `Db` so either can be inspected/cleared independently on disk. Unset
+    // `cache_dir` disables caching entirely.
+    let (download_cache, thumbnail_cache) = match &config.server.cache_dir {
+        Some(dir) => match sled::open(dir) {
+            Ok(db) => {
+                let capacity_bytes = config.server.cache_capacity_mb as u64 * 1024 * 1024 / 2;
+                let downloads = db
+                    .open_tree("downloads")
+                    .ok()
+                    .and_then(|t| cache::ContentCache::new(t, capacity_bytes).ok());
+                let thumbnails = db
+                    .open_tree("thumbnails")
+                    .ok()
+                    .and_then(|t| cache::ContentCache::new(t, capacity_bytes).ok());
+                (downloads, thumbnails)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to open content cache at {}: {}, caching disabled", dir, e);
+                (None, None)
+            }
+        },
+        None => (None, None),
+    };
+
     // Create shared application state
     let state = Arc::new(RwLock::new(AppState::new(
-        jwt_auth,
+        auth,
         fb_client,
         config.server.max_concurrent_transfers,
         config.server.load_report_interval,
+        download_cache,
+        thumbnail_cache,
     )));
 
+    // Watch config.toml for edits so the rate limiter and load-report cadence
+    // can be tuned without a restart; failures (parse/validation errors) are
+    // just logged -- the last-good config keeps serving.
+    if let Ok(config_path) = Config::config_path() {
+        match pibox_core::config_watcher::ConfigWatcher::watch(config_path, config.clone()) {
+            Ok(watcher) => {
+                let mut config_rx = watcher.subscribe();
+                let mut event_rx = watcher.subscribe_events();
+                let reload_state = Arc::clone(&state);
+                tokio::spawn(async move {
+                    loop {
+                        tokio::select! {
+                            Ok(()) = config_rx.changed() => {
+                                let new_config = config_rx.borrow().clone();
+                                let mut state = reload_state.write().await;
+                                state.max_concurrent_transfers = new_config.server.max_concurrent_transfers;
+                                state.load_report_interval = new_config.server.load_report_interval;
+                            }
+                            Ok(event) = event_rx.recv() => {
+                                match event {
+                                    pibox_core::config_watcher::ConfigEvent::Reloaded(diff) => {
+                                        tracing::info!("Config reloaded, changed fields: {:?}", diff.changed);
+                                    }
+                                    pibox_core::config_watcher::ConfigEvent::ReloadFailed(e) => {
+                                        tracing::warn!("Config reload failed, keeping previous config: {}", e);
+                                    }
+                                }
+                            }
+                            else => break,
+                        }
+                    }
+                });
+                state.write().await.config_watcher = Some(watcher);
+            }
+            Err(e) => tracing::warn!("Failed to watch config file for changes: {}", e),
+        }
+    }
+
     // Start load monitor
     let load_state = Arc::clone(&state);
     tokio::spawn(async move {
         load::monitor_loop(load_state).await;
     });
 
+    // Sweep chunked uploads abandoned mid-transfer
+    let upload_state = Arc::clone(&state);
+    let upload_idle_timeout = std::time::Duration::from_secs(config.server.upload_idle_timeout);
+    tokio::spawn(async move {
+        uploads::sweep_idle_loop(upload_state, upload_idle_timeout).await;
+    });
+
+    // Sweep expired/consumed pairing codes. `POST /pair/start` requires no
+    // auth, so this also runs on every claim (see `AppState::claim_pairing`)
+    // -- the periodic sweep here just bounds the map even if nothing ever
+    // claims the codes being minted.
+    let pairing_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(pibox_core::pairing::PAIRING_CODE_TTL_SECS));
+        loop {
+            ticker.tick().await;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            pairing_state.write().await.sweep_expired_pairing_codes(now);
+        }
+    });
+
+    // Start the filesystem watcher, if configured with a local root to
+    // watch. Kept alive on `AppState` -- dropping it would stop the watch.
+    if let Some(ref root) = config.server.watch_root {
+        match watcher::watch(PathBuf::from(root), Arc::clone(&state)) {
+            Ok(w) => state.write().await.watcher = Some(w),
+            Err(e) => tracing::warn!("Failed to watch {}: {}", root, e),
+        }
+    }
+
+    // Push metrics to a Pushgateway on an interval, for headless deployments
+    // that can't be scraped directly.
+    if let Some(ref url) = config.server.metrics_pushgateway_url {
+        let push_state = Arc::clone(&state);
+        let url = url.clone();
+        let interval_secs = config.server.metrics_push_interval;
+        tokio::spawn(async move {
+            metrics::push_loop(push_state, url, interval_secs).await;
+        });
+    }
+
     // Build router
     let app = Router::new()
         .route("/ws", get(handlers::ws_handler))
         .route("/health", get(handlers::health_handler))
+        .route("/metrics", get(handlers::metrics_handler))
         .route("/api/login", post(handlers::login_handler))
+        .route("/pair/start", post(handlers::pair_start_handler))
+        .route("/pair/claim", post(handlers::pair_claim_handler))
+        .route("/share/:token", get(handlers::share_handler))
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http())
         .with_state(state);
 
+    // Print a pairing QR for this server itself, so a phone or second client
+    // can scan its way to a working `DeviceConfig` instead of being told a
+    // `ws://` URL to type in. `listen_addr` is frequently `0.0.0.0`, which
+    // isn't dialable, so fall back to loopback -- a real LAN-reachable
+    // address has to come from the operator until this crate gains its own
+    // interface discovery.
+    let pairing_host = if config.server.listen_addr == "0.0.0.0" {
+        "127.0.0.1"
+    } else {
+        &config.server.listen_addr
+    };
+    match pibox_core::pairing::server_pairing_blob(
+        pairing_host,
+        config.server.ws_port,
+        pibox_core::config::DeviceType::Generic,
+    ) {
+        Ok(blob) => println!("Scan to pair a new client:\n{}", pibox_core::pairing::render_qr(&blob)),
+        Err(e) => tracing::warn!("Failed to build pairing QR: {}", e),
+    }
+
     // Start server
     let addr = SocketAddr::from((
         config.server.listen_addr.parse::<std::net::IpAddr>()?,
         config.server.ws_port,
     ));
-    tracing::info!("pibox-server listening on {}", addr);
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    if config.server.tls_enabled {
+        let cert_path = config
+            .server
+            .tls_cert_path
+            .clone()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Config::config_dir().unwrap_or_default().join("tls_cert.pem"));
+        let key_path = config
+            .server
+            .tls_key_path
+            .clone()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Config::config_dir().unwrap_or_default().join("tls_key.pem"));
+
+        let tls_config = tls::load_or_generate(&cert_path, &key_path).await?;
+
+        tracing::info!("pibox-server listening on wss://{}", addr);
+        axum_server::bind_rustls(addr, tls_config)
+            .serve(app.into_make_service())
+            .await?;
+    } else {
+        tracing::info!("pibox-server listening on ws://{}", addr);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, app).await?;
+    }
 
     Ok(())
 }
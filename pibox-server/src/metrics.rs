@@ -0,0 +1,277 @@
+//! Prometheus text-format metrics
+//!
+//! No metrics crate is pulled in for this -- the exposition format is
+//! simple enough to hand-render, the same call made for `pairing`'s QR
+//! rendering rather than reaching for a heavyweight dependency.
+
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+use crate::state::AppState;
+
+/// File operation kinds tracked individually, mirroring the `op` strings
+/// `handle_client_message` already attaches to `OpSuccess`/`OpError`
+#[derive(Clone, Copy)]
+pub enum Op {
+    List,
+    Download,
+    Upload,
+    Delete,
+    Rename,
+    Mkdir,
+}
+
+impl Op {
+    const ALL: [Op; 6] = [Op::List, Op::Download, Op::Upload, Op::Delete, Op::Rename, Op::Mkdir];
+
+    fn label(self) -> &'static str {
+        match self {
+            Op::List => "list",
+            Op::Download => "download",
+            Op::Upload => "upload",
+            Op::Delete => "delete",
+            Op::Rename => "rename",
+            Op::Mkdir => "mkdir",
+        }
+    }
+}
+
+/// Bucket boundaries for operation latency, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// A Prometheus-style latency histogram with fixed bucket boundaries.
+/// `bucket_counts[i]` holds the count of observations whose value falls in
+/// bucket `i` specifically (not cumulative) -- `render` accumulates them
+/// into the `le`-cumulative form Prometheus expects.
+struct Histogram {
+    bucket_counts: [AtomicU64; LATENCY_BUCKETS.len()],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            bucket_counts: std::array::from_fn(|_| AtomicU64::new(0)),
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, seconds: f64) {
+        self.sum_micros.fetch_add((seconds * 1_000_000.0).round() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+
+        for (bound, counter) in LATENCY_BUCKETS.iter().zip(self.bucket_counts.iter()) {
+            if seconds <= *bound {
+                counter.fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+        }
+        // Observations past the last finite bucket only land in `+Inf`,
+        // which `render` derives from `count` rather than a stored bucket.
+    }
+
+    fn render(&self, op_label: &str, out: &mut String) {
+        let mut cumulative = 0u64;
+        for (bound, counter) in LATENCY_BUCKETS.iter().zip(self.bucket_counts.iter()) {
+            cumulative += counter.load(Ordering::Relaxed);
+            out.push_str(&format!(
+                "pibox_op_latency_seconds_bucket{{op=\"{op_label}\",le=\"{bound}\"}} {cumulative}\n"
+            ));
+        }
+        let total = self.count.load(Ordering::Relaxed);
+        out.push_str(&format!("pibox_op_latency_seconds_bucket{{op=\"{op_label}\",le=\"+Inf\"}} {total}\n"));
+        out.push_str(&format!(
+            "pibox_op_latency_seconds_sum{{op=\"{op_label}\"}} {:.6}\n",
+            self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        ));
+        out.push_str(&format!("pibox_op_latency_seconds_count{{op=\"{op_label}\"}} {total}\n"));
+    }
+}
+
+/// Success/error counts and latency for one `Op`
+struct OpMetrics {
+    success: AtomicU64,
+    error: AtomicU64,
+    latency: Histogram,
+}
+
+impl OpMetrics {
+    fn new() -> Self {
+        Self {
+            success: AtomicU64::new(0),
+            error: AtomicU64::new(0),
+            latency: Histogram::new(),
+        }
+    }
+
+    fn record(&self, ok: bool, elapsed: Duration) {
+        if ok {
+            self.success.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.error.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency.observe(elapsed.as_secs_f64());
+    }
+}
+
+/// Server-wide counters and gauges, rendered as Prometheus text by
+/// `GET /metrics` and optionally pushed to a Pushgateway (see `push_loop`).
+/// All fields are atomics so recording a metric only needs a shared `&self`
+/// -- callers can hold `AppState` under a read lock rather than a write one.
+pub struct Metrics {
+    /// Websocket connections currently open, including ones still
+    /// authenticating (distinct from `sessions_total`, which only counts
+    /// ones that made it past `wait_for_auth`)
+    ws_connections_active: AtomicI64,
+    sessions_total: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    transfer_rejections: AtomicU64,
+
+    list: OpMetrics,
+    download: OpMetrics,
+    upload: OpMetrics,
+    delete: OpMetrics,
+    rename: OpMetrics,
+    mkdir: OpMetrics,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            ws_connections_active: AtomicI64::new(0),
+            sessions_total: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            transfer_rejections: AtomicU64::new(0),
+            list: OpMetrics::new(),
+            download: OpMetrics::new(),
+            upload: OpMetrics::new(),
+            delete: OpMetrics::new(),
+            rename: OpMetrics::new(),
+            mkdir: OpMetrics::new(),
+        }
+    }
+}
+
+impl Metrics {
+    fn op(&self, op: Op) -> &OpMetrics {
+        match op {
+            Op::List => &self.list,
+            Op::Download => &self.download,
+            Op::Upload => &self.upload,
+            Op::Delete => &self.delete,
+            Op::Rename => &self.rename,
+            Op::Mkdir => &self.mkdir,
+        }
+    }
+
+    pub fn connection_opened(&self) {
+        self.ws_connections_active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.ws_connections_active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn session_authenticated(&self) {
+        self.sessions_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_op(&self, op: Op, ok: bool, elapsed: Duration) {
+        self.op(op).record(ok, elapsed);
+    }
+
+    pub fn add_bytes_sent(&self, n: u64) {
+        self.bytes_sent.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_received(&self, n: u64) {
+        self.bytes_received.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn transfer_rejected(&self) {
+        self.transfer_rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP pibox_ws_connections_active Open websocket connections, including ones still authenticating\n");
+        out.push_str("# TYPE pibox_ws_connections_active gauge\n");
+        out.push_str(&format!(
+            "pibox_ws_connections_active {}\n",
+            self.ws_connections_active.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP pibox_sessions_total Client sessions that completed authentication\n");
+        out.push_str("# TYPE pibox_sessions_total counter\n");
+        out.push_str(&format!("pibox_sessions_total {}\n", self.sessions_total.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP pibox_bytes_sent_total Bytes sent to clients via file downloads\n");
+        out.push_str("# TYPE pibox_bytes_sent_total counter\n");
+        out.push_str(&format!("pibox_bytes_sent_total {}\n", self.bytes_sent.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP pibox_bytes_received_total Bytes received from clients via file uploads\n");
+        out.push_str("# TYPE pibox_bytes_received_total counter\n");
+        out.push_str(&format!("pibox_bytes_received_total {}\n", self.bytes_received.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP pibox_transfer_rejections_total Transfers rejected by the max_concurrent_transfers limit\n");
+        out.push_str("# TYPE pibox_transfer_rejections_total counter\n");
+        out.push_str(&format!(
+            "pibox_transfer_rejections_total {}\n",
+            self.transfer_rejections.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP pibox_op_total File operations by type and outcome\n");
+        out.push_str("# TYPE pibox_op_total counter\n");
+        for op in Op::ALL {
+            let m = self.op(op);
+            out.push_str(&format!(
+                "pibox_op_total{{op=\"{}\",result=\"success\"}} {}\n",
+                op.label(),
+                m.success.load(Ordering::Relaxed)
+            ));
+            out.push_str(&format!(
+                "pibox_op_total{{op=\"{}\",result=\"error\"}} {}\n",
+                op.label(),
+                m.error.load(Ordering::Relaxed)
+            ));
+        }
+
+        out.push_str("# HELP pibox_op_latency_seconds Latency of file operations\n");
+        out.push_str("# TYPE pibox_op_latency_seconds histogram\n");
+        for op in Op::ALL {
+            self.op(op).latency.render(op.label(), &mut out);
+        }
+
+        out
+    }
+}
+
+/// Periodically POST the rendered metrics to a Pushgateway, for headless
+/// deployments that can't be scraped directly (e.g. a Pi behind NAT)
+pub async fn push_loop(state: Arc<RwLock<AppState>>, url: String, interval_secs: u64) {
+    let client = reqwest::Client::new();
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+
+    loop {
+        ticker.tick().await;
+
+        let body = {
+            let s = state.read().await;
+            s.metrics.render()
+        };
+
+        if let Err(e) = client.post(&url).header("Content-Type", "text/plain; version=0.0.4").body(body).send().await
+        {
+            tracing::warn!("Failed to push metrics to {}: {}", url, e);
+        }
+    }
+}
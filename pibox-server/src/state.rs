@@ -1,10 +1,16 @@
 //! Shared server state
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::broadcast;
 
-use pibox_core::{FilebrowserClient, JwtAuth, ServerMessage};
+use pibox_core::auth::AuthProvider;
+use pibox_core::pairing::PairingCode;
+use pibox_core::share::ShareLink;
+use pibox_core::{FilebrowserClient, ServerMessage};
+
+use crate::compress::CompressionLevel;
+use crate::transfer::ChunkSizer;
 
 /// Connected client info
 pub struct ConnectedClient {
@@ -12,12 +18,68 @@ pub struct ConnectedClient {
     pub username: String,
     pub capabilities: Option<pibox_core::protocol::ClientCapabilities>,
     pub sender: broadcast::Sender<ServerMessage>,
+
+    /// Paths this client has `Watch`ed; an `FsEvent` only reaches it if one
+    /// of these is a prefix of one of the event's paths. Empty means the
+    /// client gets no `FsEvent` broadcasts at all.
+    pub watched_paths: HashSet<String>,
+}
+
+/// An offload task in flight: who asked for it and which worker it was
+/// routed to, so `OffloadResult` can be delivered back to the requester and
+/// a timeout or disconnect can be attributed to the right worker
+struct PendingOffload {
+    requester: String,
+    worker: String,
+    task: pibox_core::protocol::OffloadTask,
+}
+
+/// How many offload tasks a single client may have in flight at once, so
+/// one slow worker can't be handed the whole queue
+const MAX_INFLIGHT_OFFLOADS_PER_CLIENT: usize = 2;
+
+/// A chunked upload staged at a temp path but not yet committed, tracked so
+/// out-of-order `UploadChunk`s can be rejected and a stale, abandoned upload
+/// can be swept up later
+struct PendingUpload {
+    path: String,
+    tmp_path: String,
+    total_size: u64,
+    received: u64,
+    last_activity: std::time::Instant,
+}
+
+/// Derive the temp path a chunked upload's bytes are staged at before
+/// `CommitUpload` renames them onto their final destination
+fn tmp_upload_path(path: &str, upload_id: &str) -> String {
+    format!("{}.tmp-{}", path, upload_id)
+}
+
+/// Whether a directory entry's name looks like a leftover chunked-upload
+/// temp file, so a startup or idle sweep can find and remove it
+pub fn is_upload_tmp_name(name: &str) -> bool {
+    name.contains(".tmp-")
+}
+
+/// Cache key for a whole-file download: just `path`, boundary-marked with a
+/// trailing `\0` so `invalidate_prefix`/`rename_prefix` can address it
+/// without accidentally matching a longer path that happens to share the
+/// same string prefix
+fn download_cache_key(path: &str) -> String {
+    format!("{}\0", path)
+}
+
+/// Cache key for a thumbnail: `path`, the same `\0` boundary, then
+/// `max_dim` so every bounded size generated for a path shares one prefix
+fn thumbnail_cache_key(path: &str, max_dim: u32) -> String {
+    format!("{}\0{}", path, max_dim)
 }
 
 /// Shared application state
 pub struct AppState {
-    /// JWT authentication handler
-    pub jwt_auth: JwtAuth,
+    /// Credential verification and token issuance, pluggable so deployments
+    /// aren't locked to JWT-from-password
+    pub auth: Box<dyn AuthProvider>,
 
     /// Filebrowser backend client
     pub fb_client: FilebrowserClient,
@@ -37,34 +99,312 @@ pub struct AppState {
 
     /// Broadcast channel for server-wide events
     pub event_tx: broadcast::Sender<ServerMessage>,
+
+    /// Current DEFLATE level for outbound frames, driven down under load
+    pub compression_level: CompressionLevel,
+
+    /// Current chunk size for streamed transfers, slow-started and shrunk
+    /// under load like TCP congestion control
+    pub chunk_sizer: ChunkSizer,
+
+    /// Pairing codes minted by `POST /pair/start`, keyed by code, pending
+    /// claim via `POST /pair/claim` or `ClientMessage::Pair`
+    pairing_codes: HashMap<String, PairingCode>,
+
+    /// The filesystem watcher, if `ServerConfig::watch_root` is set. Kept
+    /// here purely so it lives as long as the server does -- dropping a
+    /// `notify::Watcher` stops it.
+    pub watcher: Option<notify::RecommendedWatcher>,
+
+    /// Watches `config.toml` for live edits, if one was started. Kept here
+    /// for the same reason as `watcher` -- dropping it stops the watch.
+    pub config_watcher: Option<pibox_core::config_watcher::ConfigWatcher>,
+
+    /// Offload tasks dispatched to a worker but not yet completed, keyed by
+    /// `task_id`
+    pending_offloads: HashMap<String, PendingOffload>,
+
+    /// Cursor into `clients` (by insertion order of iteration) used to
+    /// round-robin offload dispatch across equally-eligible workers, rather
+    /// than always handing the first match the whole queue
+    offload_cursor: usize,
+
+    /// Counters/gauges/histograms exposed via `GET /metrics`
+    pub metrics: crate::metrics::Metrics,
+
+    /// Outstanding share links minted by `ClientMessage::CreateShare`, keyed
+    /// by token
+    shares: HashMap<String, ShareLink>,
+
+    /// Path-keyed cache for `FilebrowserClient::download` results,
+    /// `None` if `ServerConfig::cache_dir` is unset
+    download_cache: Option<crate::cache::ContentCache>,
+
+    /// Path-keyed cache for generated thumbnails (locally generated
+    /// or from a completed `OffloadTask::Thumbnail`), `None` if
+    /// `ServerConfig::cache_dir` is unset
+    thumbnail_cache: Option<crate::cache::ContentCache>,
+
+    /// Chunked uploads staged but not yet committed, keyed by `upload_id`
+    uploads: HashMap<String, PendingUpload>,
 }
 
 impl AppState {
     pub fn new(
-        jwt_auth: JwtAuth,
+        auth: Box<dyn AuthProvider>,
         fb_client: FilebrowserClient,
         max_concurrent_transfers: u32,
         load_report_interval: u64,
+        download_cache: Option<crate::cache::ContentCache>,
+        thumbnail_cache: Option<crate::cache::ContentCache>,
     ) -> Self {
         let (event_tx, _) = broadcast::channel(100);
 
         Self {
-            jwt_auth,
+            auth,
             fb_client,
             clients: HashMap::new(),
             load: pibox_core::protocol::ServerLoad {
                 cpu_percent: 0.0,
                 ram_free_mb: 0,
                 io_busy: false,
+                cpu_temp_c: None,
                 hints: vec![],
             },
             max_concurrent_transfers,
             active_transfers: 0,
             load_report_interval,
             event_tx,
+            compression_level: CompressionLevel::default(),
+            chunk_sizer: ChunkSizer::default(),
+            pairing_codes: HashMap::new(),
+            watcher: None,
+            config_watcher: None,
+            pending_offloads: HashMap::new(),
+            offload_cursor: 0,
+            metrics: crate::metrics::Metrics::default(),
+            shares: HashMap::new(),
+            download_cache,
+            thumbnail_cache,
+            uploads: HashMap::new(),
+        }
+    }
+
+    /// Mint a new pairing code with a fresh TTL
+    pub fn start_pairing(&mut self) -> PairingCode {
+        let code = uuid::Uuid::new_v4().to_string();
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let pairing = PairingCode::new(code, created_at);
+        self.pairing_codes.insert(pairing.code.clone(), pairing.clone());
+        pairing
+    }
+
+    /// Consume `code` if it exists, hasn't expired, and hasn't already been
+    /// claimed. Returns `true` on a successful claim.
+    pub fn claim_pairing(&mut self, code: &str) -> bool {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.sweep_expired_pairing_codes(now);
+
+        match self.pairing_codes.get_mut(code) {
+            Some(pairing) if pairing.is_claimable(now) => {
+                pairing.consumed = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Drop pairing codes that are expired or already consumed. `POST
+    /// /pair/start` requires no authentication, so without this an
+    /// unauthenticated client looping that endpoint could grow
+    /// `pairing_codes` without bound.
+    pub fn sweep_expired_pairing_codes(&mut self, now: u64) {
+        self.pairing_codes.retain(|_, pairing| !pairing.consumed && !pairing.is_expired(now));
+    }
+
+    /// Mint a new share link for `path`, owned by `owner`
+    pub fn create_share(&mut self, owner: &str, path: String, expires_in: u64, download_limit: Option<u32>) -> ShareLink {
+        let token = uuid::Uuid::new_v4().to_string();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let share = ShareLink::new(token, path, owner.to_string(), now, expires_in, download_limit);
+        self.shares.insert(share.token.clone(), share.clone());
+        share
+    }
+
+    /// Revoke `token`, if it exists and is owned by `owner`. Returns `true`
+    /// on success.
+    pub fn revoke_share(&mut self, owner: &str, token: &str) -> bool {
+        match self.shares.get(token) {
+            Some(share) if share.owner == owner => {
+                self.shares.remove(token);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// List `owner`'s outstanding share links
+    pub fn list_shares(&self, owner: &str) -> Vec<&ShareLink> {
+        self.shares.values().filter(|share| share.owner == owner).collect()
+    }
+
+    /// Validate `token` and consume one download from it, returning the
+    /// shared file's path on success. Expired links are evicted as they're
+    /// hit, rather than waiting on a separate sweep.
+    pub fn consume_share(&mut self, token: &str) -> Option<String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let share = self.shares.get_mut(token)?;
+        if !share.consume(now) {
+            self.shares.remove(token);
+            return None;
+        }
+
+        let path = share.path.clone();
+        if share.downloads_remaining == Some(0) {
+            self.shares.remove(token);
+        }
+        Some(path)
+    }
+
+    /// Look up a previously generated thumbnail for (path, mtime, max_dim).
+    /// `None` (a miss) both when there's no cache configured and when the
+    /// entry is missing or stale.
+    pub fn cached_thumbnail(&self, path: &str, mtime: i64, max_dim: u32) -> Option<(String, Vec<u8>)> {
+        let cache = self.thumbnail_cache.as_ref()?;
+        let hit = cache.get(&thumbnail_cache_key(path, max_dim), mtime)?;
+        Some((hit.mime_type, hit.data))
+    }
+
+    /// Record a generated thumbnail for (path, mtime, max_dim). A no-op if
+    /// no cache is configured.
+    pub fn cache_thumbnail(&self, path: &str, mtime: i64, max_dim: u32, mime_type: String, data: Vec<u8>) {
+        if let Some(cache) = self.thumbnail_cache.as_ref() {
+            cache.put(&thumbnail_cache_key(path, max_dim), mtime, mime_type, data);
+        }
+    }
+
+    /// Look up a previously downloaded file's bytes for (path, mtime).
+    /// `None` (a miss) both when there's no cache configured and when the
+    /// entry is missing or stale.
+    pub fn cached_download(&self, path: &str, mtime: i64) -> Option<(String, Vec<u8>)> {
+        let cache = self.download_cache.as_ref()?;
+        let hit = cache.get(&download_cache_key(path), mtime)?;
+        Some((hit.mime_type, hit.data))
+    }
+
+    /// Record a downloaded file's bytes for (path, mtime). A no-op if no
+    /// cache is configured.
+    pub fn cache_download(&self, path: &str, mtime: i64, mime_type: String, data: Vec<u8>) {
+        if let Some(cache) = self.download_cache.as_ref() {
+            cache.put(&download_cache_key(path), mtime, mime_type, data);
+        }
+    }
+
+    /// Keep the download/thumbnail caches coherent with real-time
+    /// filesystem changes: drop a path's entries outright on `Modified`/
+    /// `Deleted` rather than waiting for the next freshness check, and carry
+    /// still-valid entries over to the new path on `Renamed`.
+    pub fn invalidate_cache_for_event(&self, event: &pibox_core::protocol::FsEvent) {
+        match event {
+            pibox_core::protocol::FsEvent::Modified { path } | pibox_core::protocol::FsEvent::Deleted { path } => {
+                let prefix = format!("{}\0", path);
+                if let Some(cache) = self.download_cache.as_ref() {
+                    cache.invalidate_prefix(&prefix);
+                }
+                if let Some(cache) = self.thumbnail_cache.as_ref() {
+                    cache.invalidate_prefix(&prefix);
+                }
+            }
+            pibox_core::protocol::FsEvent::Renamed { from, to } => {
+                let (old_prefix, new_prefix) = (format!("{}\0", from), format!("{}\0", to));
+                if let Some(cache) = self.download_cache.as_ref() {
+                    cache.rename_prefix(&old_prefix, &new_prefix);
+                }
+                if let Some(cache) = self.thumbnail_cache.as_ref() {
+                    cache.rename_prefix(&old_prefix, &new_prefix);
+                }
+            }
+            pibox_core::protocol::FsEvent::Created { .. } => {}
         }
     }
 
+    /// Start staging a chunked upload, returning the temp path its bytes
+    /// will be written to until `CommitUpload` renames them onto `path`
+    pub fn begin_upload(&mut self, path: String, total_size: u64, upload_id: String) -> String {
+        let tmp_path = tmp_upload_path(&path, &upload_id);
+        self.uploads.insert(
+            upload_id,
+            PendingUpload {
+                path,
+                tmp_path: tmp_path.clone(),
+                total_size,
+                received: 0,
+                last_activity: std::time::Instant::now(),
+            },
+        );
+        tmp_path
+    }
+
+    /// Look up the temp path and number of bytes already staged for
+    /// `upload_id`
+    pub fn upload_progress(&self, upload_id: &str) -> Option<(String, u64)> {
+        self.uploads.get(upload_id).map(|u| (u.tmp_path.clone(), u.received))
+    }
+
+    /// Record `len` more bytes staged for `upload_id` if `offset` matches
+    /// what's already been received; returns the authoritative byte count
+    /// staged so far either way, so the caller can report it back to the
+    /// client as a `ChunkAck` regardless of whether this chunk landed
+    pub fn record_upload_chunk(&mut self, upload_id: &str, offset: u64, len: u64) -> Option<u64> {
+        let upload = self.uploads.get_mut(upload_id)?;
+        upload.last_activity = std::time::Instant::now();
+        if offset == upload.received {
+            upload.received += len;
+        }
+        Some(upload.received)
+    }
+
+    /// Remove and return the pending upload for `upload_id`, e.g. once
+    /// `CommitUpload` has renamed its temp file onto its final path
+    pub fn finish_upload(&mut self, upload_id: &str) -> Option<(String, String, u64, u64)> {
+        self.uploads
+            .remove(upload_id)
+            .map(|u| (u.path, u.tmp_path, u.total_size, u.received))
+    }
+
+    /// Remove and return `(path, tmp_path)` for uploads idle longer than
+    /// `idle_timeout`, so their abandoned temp files can be deleted
+    pub fn sweep_stale_uploads(&mut self, idle_timeout: std::time::Duration) -> Vec<(String, String)> {
+        let stale_ids: Vec<String> = self
+            .uploads
+            .iter()
+            .filter(|(_, u)| u.last_activity.elapsed() >= idle_timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        stale_ids
+            .into_iter()
+            .filter_map(|id| self.uploads.remove(&id))
+            .map(|u| (u.path, u.tmp_path))
+            .collect()
+    }
+
     /// Register a new client connection
     pub fn register_client(&mut self, id: String, username: String) -> broadcast::Receiver<ServerMessage> {
         let (sender, receiver) = broadcast::channel(32);
@@ -76,15 +416,48 @@ impl AppState {
                 username,
                 capabilities: None,
                 sender,
+                watched_paths: HashSet::new(),
             },
         );
 
         receiver
     }
 
-    /// Unregister a client
-    pub fn unregister_client(&mut self, id: &str) {
+    /// Unregister a client, returning `(task_id, requester)` for any offload
+    /// this client was working on, so the caller can notify the requester
+    /// that it won't complete
+    pub fn unregister_client(&mut self, id: &str) -> Vec<(String, String)> {
         self.clients.remove(id);
+
+        let orphaned: Vec<String> = self
+            .pending_offloads
+            .iter()
+            .filter(|(_, pending)| pending.worker == id)
+            .map(|(task_id, _)| task_id.clone())
+            .collect();
+
+        orphaned
+            .into_iter()
+            .filter_map(|task_id| {
+                self.pending_offloads
+                    .remove(&task_id)
+                    .map(|pending| (task_id, pending.requester))
+            })
+            .collect()
+    }
+
+    /// Scope `client_id`'s `FsEvent` broadcasts to paths under `path`
+    pub fn watch_path(&mut self, client_id: &str, path: String) {
+        if let Some(client) = self.clients.get_mut(client_id) {
+            client.watched_paths.insert(path);
+        }
+    }
+
+    /// Stop scoping `client_id`'s `FsEvent` broadcasts to `path`
+    pub fn unwatch_path(&mut self, client_id: &str, path: &str) {
+        if let Some(client) = self.clients.get_mut(client_id) {
+            client.watched_paths.remove(path);
+        }
     }
 
     /// Update client capabilities
@@ -116,31 +489,164 @@ impl AppState {
         }
     }
 
-    /// Broadcast message to all connected clients
+    /// Broadcast message to all connected clients. An `FsEvent` also
+    /// invalidates the download/thumbnail caches for the path(s) it
+    /// touches, so every broadcast path (the watcher picking up an
+    /// out-of-band change, or a handler reacting to a client's own
+    /// mutation) keeps the cache coherent without having to remember to do
+    /// so itself.
     pub fn broadcast(&self, msg: ServerMessage) {
+        if let ServerMessage::FsEvent(ref event) = msg {
+            self.invalidate_cache_for_event(event);
+        }
         let _ = self.event_tx.send(msg);
     }
 
-    /// Find a capable client for offloading a task
-    pub fn find_offload_candidate(&self, task: &pibox_core::protocol::OffloadTask) -> Option<&ConnectedClient> {
-        self.clients.values().find(|client| {
-            if let Some(ref caps) = client.capabilities {
-                // Only offload to clients on AC power with spare resources
-                if !caps.on_ac_power || caps.ram_free_mb < 500 {
-                    return false;
-                }
+    /// Send a message to a single client (e.g. transfer progress), rather
+    /// than broadcasting it to everyone
+    pub fn send_to_client(&self, id: &str, msg: ServerMessage) {
+        if let Some(client) = self.clients.get(id) {
+            let _ = client.sender.send(msg);
+        }
+    }
 
-                match task {
-                    pibox_core::protocol::OffloadTask::Thumbnail { .. } => {
-                        caps.can_generate_thumbnails && (caps.has_gpu || caps.cpu_cores >= 4)
-                    }
-                    pibox_core::protocol::OffloadTask::Search { .. } => {
-                        caps.can_search_locally && caps.cpu_cores >= 4
-                    }
-                }
-            } else {
-                false
+    /// Whether `client` both advertises the capability `task` needs and has
+    /// spare in-flight capacity
+    fn is_eligible_offload_worker(&self, client: &ConnectedClient, task: &pibox_core::protocol::OffloadTask) -> bool {
+        let Some(ref caps) = client.capabilities else {
+            return false;
+        };
+
+        // Only offload to clients on AC power with spare resources
+        if !caps.on_ac_power || caps.ram_free_mb < 500 {
+            return false;
+        }
+
+        let capable = match task {
+            pibox_core::protocol::OffloadTask::Thumbnail { .. } => {
+                caps.can_generate_thumbnails && (caps.has_gpu || caps.cpu_cores >= 4)
             }
-        })
+            pibox_core::protocol::OffloadTask::Search { .. } => caps.can_search_locally && caps.cpu_cores >= 4,
+            pibox_core::protocol::OffloadTask::BlurHash { .. } => caps.can_generate_thumbnails,
+        };
+        if !capable {
+            return false;
+        }
+
+        let in_flight = self
+            .pending_offloads
+            .values()
+            .filter(|pending| pending.worker == client.id)
+            .count();
+        in_flight < MAX_INFLIGHT_OFFLOADS_PER_CLIENT
+    }
+
+    /// Find a capable, not-overloaded client for offloading `task`,
+    /// round-robining across eligible workers so repeated offloads don't all
+    /// land on the same (first-found) one
+    pub fn find_offload_candidate(&mut self, task: &pibox_core::protocol::OffloadTask) -> Option<String> {
+        let mut ids: Vec<&String> = self.clients.keys().collect();
+        ids.sort();
+        if ids.is_empty() {
+            return None;
+        }
+
+        for offset in 0..ids.len() {
+            let idx = (self.offload_cursor + offset) % ids.len();
+            let id = ids[idx];
+            if self.is_eligible_offload_worker(&self.clients[id], task) {
+                self.offload_cursor = (idx + 1) % ids.len();
+                return Some(id.clone());
+            }
+        }
+
+        None
+    }
+
+    /// Route `task` to a capable worker on behalf of `requester`, recording
+    /// the pending dispatch so a later `OffloadResult` can be routed back.
+    /// Returns the worker id on success, or an error message suitable for
+    /// relaying to `requester` as a `ServerMessage::Error`.
+    pub fn dispatch_offload(
+        &mut self,
+        requester: &str,
+        task_id: String,
+        task: pibox_core::protocol::OffloadTask,
+    ) -> Result<String, String> {
+        let worker = self
+            .find_offload_candidate(&task)
+            .ok_or_else(|| "No capable worker is currently available to offload this task".to_string())?;
+
+        self.send_to_client(
+            &worker,
+            ServerMessage::OffloadRequest {
+                task_id: task_id.clone(),
+                task: task.clone(),
+            },
+        );
+
+        self.pending_offloads.insert(
+            task_id,
+            PendingOffload {
+                requester: requester.to_string(),
+                worker: worker.clone(),
+                task,
+            },
+        );
+
+        Ok(worker)
+    }
+
+    /// Resolve a completed offload, returning the original requester (so the
+    /// caller can deliver `ServerMessage::OffloadComplete` to it) along with
+    /// the task that was dispatched (so e.g. a completed `Thumbnail` task
+    /// can be cached under its `path`)
+    pub fn complete_offload(&mut self, task_id: &str) -> Option<(String, pibox_core::protocol::OffloadTask)> {
+        self.pending_offloads
+            .remove(task_id)
+            .map(|pending| (pending.requester, pending.task))
+    }
+
+    /// Re-dispatch a pending offload (e.g. after a timeout) to a different
+    /// eligible worker, skipping the one that already failed it. Returns the
+    /// new worker id, or an error if none are available.
+    pub fn redispatch_offload(&mut self, task_id: &str, failed_worker: &str) -> Result<String, String> {
+        let Some(pending) = self.pending_offloads.get(task_id) else {
+            return Err("Offload is no longer pending".to_string());
+        };
+        let task = pending.task.clone();
+        let requester = pending.requester.clone();
+
+        // The round-robin cursor may land on `failed_worker` first; a second
+        // lookup moves past it to the next eligible worker, if there is one.
+        let worker = self
+            .find_offload_candidate(&task)
+            .filter(|id| id != failed_worker)
+            .or_else(|| self.find_offload_candidate(&task).filter(|id| id != failed_worker))
+            .ok_or_else(|| "No other capable worker is available to retry this task".to_string())?;
+
+        self.send_to_client(
+            &worker,
+            ServerMessage::OffloadRequest {
+                task_id: task_id.to_string(),
+                task: task.clone(),
+            },
+        );
+
+        self.pending_offloads.insert(
+            task_id.to_string(),
+            PendingOffload {
+                requester,
+                worker: worker.clone(),
+                task,
+            },
+        );
+
+        Ok(worker)
+    }
+
+    /// Whether `task_id` is still awaiting an `OffloadResult`
+    pub fn is_offload_pending(&self, task_id: &str) -> bool {
+        self.pending_offloads.contains_key(task_id)
     }
 }
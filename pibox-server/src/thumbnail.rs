@@ -0,0 +1,41 @@
+//! On-demand thumbnail generation
+//!
+//! Downscales a source image to fit within `max_dim` on its longest side,
+//! re-encoding it back into its original format (so the caller can keep
+//! treating the output as the same mime type as the source). Video frame
+//! extraction is out of scope here -- no video-decoding crate is pulled
+//! into this tree -- so a video mime type surfaces `UnsupportedFormat`
+//! rather than a thumbnail.
+
+use image::ImageFormat;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ThumbnailError {
+    #[error("unrecognized or unsupported mime type for thumbnailing: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("failed to decode image: {0}")]
+    DecodeFailed(String),
+
+    #[error("failed to encode thumbnail: {0}")]
+    EncodeFailed(String),
+}
+
+/// Produce a thumbnail for `source`, bounded to `max_dim` on its longest
+/// side, encoded back into the format `mime_type` names
+pub fn generate(source: &[u8], mime_type: &str, max_dim: u32) -> Result<Vec<u8>, ThumbnailError> {
+    let format =
+        ImageFormat::from_mime_type(mime_type).ok_or_else(|| ThumbnailError::UnsupportedFormat(mime_type.to_string()))?;
+
+    let image = image::load_from_memory_with_format(source, format).map_err(|e| ThumbnailError::DecodeFailed(e.to_string()))?;
+
+    let thumbnail = image.thumbnail(max_dim, max_dim);
+
+    let mut out = Vec::new();
+    thumbnail
+        .write_to(&mut std::io::Cursor::new(&mut out), format)
+        .map_err(|e| ThumbnailError::EncodeFailed(e.to_string()))?;
+
+    Ok(out)
+}
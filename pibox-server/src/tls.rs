@@ -0,0 +1,74 @@
+//! Optional WSS/TLS termination using rustls
+//!
+//! Plain `ws://` crosses the LAN in cleartext, exposing JWTs and file bytes
+//! to anyone sharing the network. This loads an operator-provided PEM
+//! cert+key pair, or generates a self-signed certificate on first boot for
+//! devices with no CA, and hands back a `RustlsConfig` ready to serve
+//! `wss://` / HTTPS.
+
+use std::path::Path;
+
+use axum_server::tls_rustls::RustlsConfig;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("TLS I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to generate self-signed certificate: {0}")]
+    CertGen(String),
+}
+
+/// Load the cert/key pair at the given paths, generating a self-signed pair
+/// first if either file is missing.
+pub async fn load_or_generate(cert_path: &Path, key_path: &Path) -> Result<RustlsConfig, TlsError> {
+    if !cert_path.exists() || !key_path.exists() {
+        generate_self_signed(cert_path, key_path)?;
+    }
+
+    RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .map_err(TlsError::Io)
+}
+
+fn generate_self_signed(cert_path: &Path, key_path: &Path) -> Result<(), TlsError> {
+    let certified = rcgen::generate_simple_self_signed(vec!["pibox.local".to_string(), "localhost".to_string()])
+        .map_err(|e| TlsError::CertGen(e.to_string()))?;
+
+    if let Some(parent) = cert_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if let Some(parent) = key_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(cert_path, certified.cert.pem())?;
+    write_key_file(key_path, certified.signing_key.serialize_pem().as_bytes())?;
+
+    Ok(())
+}
+
+/// Write the private key PEM with owner-only (`0600`) permissions on Unix,
+/// since unlike the cert, it must not be world-readable.
+fn write_key_file(key_path: &Path, pem: &[u8]) -> Result<(), TlsError> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(key_path)?;
+        file.write_all(pem)?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::fs::write(key_path, pem)?;
+    }
+
+    Ok(())
+}
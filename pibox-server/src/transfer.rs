@@ -0,0 +1,127 @@
+//! Load-adaptive chunked file transfer pipeline
+//!
+//! `AppState` already tracks `active_transfers` and the load monitor already
+//! emits `ThrottleTransfers` hints, but until now nothing actually responded
+//! to load beyond suggesting clients back off. This chunks outbound transfer
+//! bytes and grows/shrinks the chunk size much like TCP slow-start: the load
+//! monitor (`load.rs`) multiplicatively grows it while the server is healthy
+//! and halves it -- with an inter-chunk delay -- once `io_busy` or
+//! `cpu_percent` crosses the high threshold.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::load::CPU_HIGH_THRESHOLD;
+
+/// Starting chunk size: conservative enough for a Pi Zero 2W
+pub const BASE_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Upper bound so a single chunk can't balloon to the whole file
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Floor so repeated shrinking under sustained load doesn't stall progress
+const MIN_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Shared chunk-size state, grown/shrunk by the load monitor each tick and
+/// read by transfer handlers when slicing a buffer to send.
+pub struct ChunkSizer(AtomicUsize);
+
+impl ChunkSizer {
+    pub fn new() -> Self {
+        Self(AtomicUsize::new(BASE_CHUNK_SIZE))
+    }
+
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Multiplicative growth while the server is healthy
+    pub fn grow(&self) {
+        let next = (self.get() * 2).min(MAX_CHUNK_SIZE);
+        self.0.store(next, Ordering::Relaxed);
+    }
+
+    /// Halve on crossing the high/critical load thresholds
+    pub fn shrink(&self) {
+        let next = (self.get() / 2).max(MIN_CHUNK_SIZE);
+        self.0.store(next, Ordering::Relaxed);
+    }
+}
+
+impl Default for ChunkSizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Delay inserted between chunks while the server is under load, giving the
+/// CPU/IO subsystem room to recover instead of saturating it further.
+pub fn inter_chunk_delay(io_busy: bool, cpu_percent: f32) -> Option<Duration> {
+    if io_busy || cpu_percent >= CPU_HIGH_THRESHOLD {
+        Some(Duration::from_millis(20))
+    } else {
+        None
+    }
+}
+
+/// Split `data` into chunks of at most `chunk_size` bytes, yielding
+/// `(offset, bytes, is_last)` for each slice. Yields a single empty, "last"
+/// chunk for an empty buffer so zero-length files still get a response.
+pub fn chunks(data: &[u8], chunk_size: usize) -> impl Iterator<Item = (u64, &[u8], bool)> {
+    let len = data.len();
+    let chunk_size = chunk_size.max(1);
+    let offsets = if len == 0 { vec![0] } else { (0..len).step_by(chunk_size).collect() };
+    offsets.into_iter().map(move |offset| {
+        let end = (offset + chunk_size).min(len);
+        (offset as u64, &data[offset..end], end == len)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_sizer_grows_and_shrinks() {
+        let sizer = ChunkSizer::new();
+        assert_eq!(sizer.get(), BASE_CHUNK_SIZE);
+
+        sizer.grow();
+        assert_eq!(sizer.get(), BASE_CHUNK_SIZE * 2);
+
+        sizer.shrink();
+        sizer.shrink();
+        assert_eq!(sizer.get(), BASE_CHUNK_SIZE / 2);
+    }
+
+    #[test]
+    fn test_chunk_sizer_respects_bounds() {
+        let sizer = ChunkSizer::new();
+        for _ in 0..10 {
+            sizer.shrink();
+        }
+        assert!(sizer.get() >= MIN_CHUNK_SIZE);
+
+        for _ in 0..10 {
+            sizer.grow();
+        }
+        assert!(sizer.get() <= MAX_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_chunks_cover_whole_buffer() {
+        let data = vec![0u8; 100];
+        let pieces: Vec<_> = chunks(&data, 30).collect();
+
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces[0], (0, &data[0..30], false));
+        assert_eq!(pieces[3], (90, &data[90..100], true));
+    }
+
+    #[test]
+    fn test_inter_chunk_delay_only_under_load() {
+        assert!(inter_chunk_delay(false, 10.0).is_none());
+        assert!(inter_chunk_delay(true, 10.0).is_some());
+        assert!(inter_chunk_delay(false, 99.0).is_some());
+    }
+}
@@ -0,0 +1,68 @@
+//! Cleanup for chunked-upload temp files
+//!
+//! `AppState` tracks in-progress chunked uploads so `CommitUpload` knows what
+//! to rename, but a crash or a client that never reconnects leaves the
+//! `.tmp-<upload_id>` file behind with no in-memory record of it. This sweeps
+//! those up: once at startup, and periodically for uploads that stall
+//! mid-transfer.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use pibox_core::state::FileType;
+use pibox_core::FilebrowserClient;
+
+use crate::state::{is_upload_tmp_name, AppState};
+
+/// Recursively remove any `.tmp-*` upload staging file found under `path`
+pub async fn cleanup_stale_temp_files(fb_client: &FilebrowserClient, path: &str) {
+    let entries = match fb_client.list_dir(path).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("Failed to list {} while cleaning stale uploads: {}", path, e);
+            return;
+        }
+    };
+
+    for entry in entries {
+        match entry.file_type {
+            FileType::Directory => Box::pin(cleanup_stale_temp_files(fb_client, &entry.path)).await,
+            FileType::File if is_upload_tmp_name(&entry.name) => {
+                if let Err(e) = fb_client.delete(&entry.path).await {
+                    tracing::warn!("Failed to remove stale upload temp file {}: {}", entry.path, e);
+                } else {
+                    tracing::info!("Removed stale upload temp file {}", entry.path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Periodically sweep uploads that haven't seen a chunk in `idle_timeout`,
+/// deleting their staged temp file and forgetting them
+pub async fn sweep_idle_loop(state: Arc<RwLock<AppState>>, idle_timeout: Duration) {
+    let mut ticker = tokio::time::interval(idle_timeout);
+
+    loop {
+        ticker.tick().await;
+
+        let stale = {
+            let mut s = state.write().await;
+            s.sweep_stale_uploads(idle_timeout)
+        };
+
+        if stale.is_empty() {
+            continue;
+        }
+
+        let s = state.read().await;
+        for (path, tmp_path) in stale {
+            tracing::warn!("Upload of {} timed out, removing stale temp file {}", path, tmp_path);
+            if let Err(e) = s.fb_client.delete(&tmp_path).await {
+                tracing::warn!("Failed to remove stale upload temp file {}: {}", tmp_path, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Filesystem watcher that broadcasts external changes
+//!
+//! `FsEvent`s were previously only broadcast when the server itself
+//! performed an upload/delete/rename/mkdir inside `handle_client_message`;
+//! changes made directly on the backing filesystem (or by another process)
+//! were invisible to connected clients. This watches a local directory with
+//! `notify` and feeds the same `FsEvent`s into `AppState`'s broadcast
+//! channel, debounced so a single editor save doesn't produce a storm.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{mpsc, RwLock};
+
+use pibox_core::protocol::FsEvent;
+use pibox_core::ServerMessage;
+
+use crate::state::AppState;
+
+/// Coalescing window: repeated events for the same path within this
+/// interval are merged into one, so a single save (write + rename-into-
+/// place, chmod, etc.) doesn't produce a storm
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A filesystem change waiting out the debounce window before it's
+/// broadcast as an `FsEvent`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PendingKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// Start watching `root` recursively, forwarding debounced `FsEvent`s into
+/// `state`'s broadcast channel. The returned `Watcher` must be kept alive
+/// for as long as watching should continue -- dropping it stops the watch.
+pub fn watch(root: PathBuf, state: Arc<RwLock<AppState>>) -> notify::Result<RecommendedWatcher> {
+    let (raw_tx, raw_rx) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = raw_tx.send(event);
+        }
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+
+    tokio::spawn(debounce_loop(raw_rx, state));
+
+    Ok(watcher)
+}
+
+/// Coalesce raw `notify` events into debounced `FsEvent` broadcasts
+async fn debounce_loop(mut raw_rx: mpsc::UnboundedReceiver<notify::Event>, state: Arc<RwLock<AppState>>) {
+    let mut pending: HashMap<PathBuf, (PendingKind, Instant)> = HashMap::new();
+
+    loop {
+        let tick = tokio::time::sleep(DEBOUNCE);
+        tokio::pin!(tick);
+
+        tokio::select! {
+            event = raw_rx.recv() => match event {
+                Some(event) => record(&mut pending, event, &state).await,
+                None => break, // Watcher dropped; nothing more will arrive
+            },
+            _ = &mut tick => {}
+        }
+
+        flush_ready(&mut pending, &state).await;
+    }
+}
+
+/// Fold one raw `notify` event into the pending map, broadcasting
+/// immediately if it's already a complete rename pair rather than waiting
+/// out the debounce window for it
+async fn record(pending: &mut HashMap<PathBuf, (PendingKind, Instant)>, event: notify::Event, state: &Arc<RwLock<AppState>>) {
+    match event.kind {
+        EventKind::Create(_) => {
+            for path in event.paths {
+                pending.insert(path, (PendingKind::Created, Instant::now()));
+            }
+        }
+        // The OS reported both halves of the rename atomically (the common
+        // case for a rename within one filesystem); emit it directly rather
+        // than waiting to pair up a separate delete-then-create.
+        //
+        // TODO: some platforms/backends split a rename into independent
+        // Remove + Create events instead; pairing those would need tracking
+        // file identity (e.g. inode) before the delete, which isn't wired
+        // up here yet, so a split rename surfaces as a plain Deleted +
+        // Created pair.
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            pending.remove(&event.paths[0]);
+            pending.remove(&event.paths[1]);
+            broadcast(
+                state,
+                FsEvent::Renamed {
+                    from: path_to_string(&event.paths[0]),
+                    to: path_to_string(&event.paths[1]),
+                },
+            )
+            .await;
+        }
+        EventKind::Modify(_) => {
+            for path in event.paths {
+                // Don't downgrade an already-pending Created/Deleted to a
+                // plain Modified; just refresh its debounce timer.
+                pending.entry(path).or_insert((PendingKind::Modified, Instant::now())).1 = Instant::now();
+            }
+        }
+        EventKind::Remove(_) => {
+            for path in event.paths {
+                pending.insert(path, (PendingKind::Deleted, Instant::now()));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Broadcast any pending change whose debounce window has elapsed
+async fn flush_ready(pending: &mut HashMap<PathBuf, (PendingKind, Instant)>, state: &Arc<RwLock<AppState>>) {
+    let ready: Vec<PathBuf> = pending
+        .iter()
+        .filter(|(_, (_, at))| at.elapsed() >= DEBOUNCE)
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    for path in ready {
+        let Some((kind, _)) = pending.remove(&path) else { continue };
+        let event = match kind {
+            PendingKind::Created => FsEvent::Created {
+                is_dir: path.is_dir(),
+                path: path_to_string(&path),
+            },
+            PendingKind::Modified => FsEvent::Modified { path: path_to_string(&path) },
+            PendingKind::Deleted => FsEvent::Deleted { path: path_to_string(&path) },
+        };
+        broadcast(state, event).await;
+    }
+}
+
+async fn broadcast(state: &Arc<RwLock<AppState>>, event: FsEvent) {
+    let s = state.read().await;
+    s.broadcast(ServerMessage::FsEvent(event));
+}
+
+fn path_to_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
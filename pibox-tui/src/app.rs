@@ -1,10 +1,29 @@
 //! Application state and logic
 
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+use ratatui::text::Line;
+
 use pibox_core::{
+    bookmarks::Bookmarks,
+    preview::{self, Preview},
     state::{AppState, FileEntry, FileType, StatusLevel},
     Config,
 };
 
+use crate::exec::{self, CommandOutput};
+use crate::highlight::{self, HighlightCache};
+use crate::keymap::KeyMap;
+use crate::script::ScriptPipes;
+use crate::termgfx::{self, GraphicsProtocol, PlacementTracker};
+use crate::theme::{self, Palette};
+use crate::watch::DirWatcher;
+
+/// How long the cursor must rest on an entry before its preview loads, so
+/// holding `j`/`k` down doesn't trigger a load per keystroke
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(150);
+
 /// Application result for main loop
 pub enum AppResult {
     Continue,
@@ -16,6 +35,12 @@ pub struct App {
     /// Configuration
     pub config: Config,
 
+    /// Keybindings, loaded from `keymap.toml` and overlaid on the vim defaults
+    pub keymap: KeyMap,
+
+    /// Single-key directory bookmarks, loaded from `bookmarks.toml`
+    pub bookmarks: Bookmarks,
+
     /// UI state
     pub state: AppState,
 
@@ -24,6 +49,47 @@ pub struct App {
 
     /// Whether we're connected to server
     pub connected: bool,
+
+    /// External scripting pipe (FIFOs for driving/observing the app without
+    /// a TTY); absent if the session directory couldn't be created
+    pub script: Option<ScriptPipes>,
+
+    /// Debounce timer for preview loading: the entry the cursor has most
+    /// recently settled on, and when it landed there
+    preview_debounce: Option<(String, i64, Instant)>,
+
+    /// Syntax-highlighted rendering of the current text preview. Populated
+    /// lazily from `draw_preview_pane` (which only holds `&App`), hence the
+    /// `RefCell` -- rebuilding only happens when the cached entry/mtime
+    /// no longer match what's being rendered
+    pub text_highlight: RefCell<Option<HighlightCache>>,
+
+    /// What this terminal can display images with, detected once at startup
+    pub graphics: GraphicsProtocol,
+
+    /// Placement id of the image (if any) currently drawn via the
+    /// terminal's graphics protocol, so it can be cleared on cursor move
+    placements: PlacementTracker,
+
+    /// Colors for the active light/dark appearance, detected at startup
+    /// and recomputed on a terminal resize (the closest thing to a
+    /// reconfigure signal crossterm surfaces)
+    pub palette: Palette,
+
+    /// Watches `state.current_path` for external changes, polled in the
+    /// main loop alongside crossterm events; absent if the watch couldn't
+    /// be established (e.g. the path doesn't exist, as with demo mode's
+    /// synthetic paths) or after a navigation to a path that can't be
+    /// watched either.
+    watcher: Option<DirWatcher>,
+
+    /// Output of the most recently run `:`/`!` command, shown in place of
+    /// the preview pane until another command replaces it
+    pub command_output: Option<CommandOutput>,
+
+    /// Streamed events from the command `command_output` is tracking;
+    /// absent once the command has finished (or none has been run yet)
+    command_rx: Option<tokio::sync::mpsc::UnboundedReceiver<exec::CommandEvent>>,
 }
 
 impl App {
@@ -35,20 +101,38 @@ impl App {
             .as_ref()
             .and_then(|name| config.get_device(name))
             .map(|d| d.url.clone())
-            .unwrap_or_else(|| format!("ws://localhost:{}", pibox_core::DEFAULT_WS_PORT));
+            .unwrap_or_else(|| {
+                format!(
+                    "{}://localhost:{}",
+                    config.server.ws_scheme(),
+                    pibox_core::DEFAULT_WS_PORT
+                )
+            });
 
         let state = AppState::new(&server_url);
 
         // Start with demo data for now
         let mut app = Self {
             config,
+            keymap: KeyMap::load(),
+            bookmarks: Bookmarks::load(),
             state,
             status_text: "Not connected".to_string(),
             connected: false,
+            script: ScriptPipes::open(&ScriptPipes::default_dir()).ok(),
+            preview_debounce: None,
+            text_highlight: RefCell::new(None),
+            graphics: termgfx::detect(),
+            placements: PlacementTracker::new(),
+            palette: Palette::for_appearance(theme::detect()),
+            watcher: None,
+            command_output: None,
+            command_rx: None,
         };
 
         // Load demo data
         app.load_demo_data();
+        app.watcher = DirWatcher::watch(&app.state.current_path).ok();
 
         app
     }
@@ -118,15 +202,136 @@ impl App {
         self.status_text = "Demo mode (no server connection)".to_string();
     }
 
+    /// Re-query the terminal's background and recompute the palette. The
+    /// terminal may have been reconfigured (or the user's SSH session
+    /// reattached to a differently-themed emulator), so a resize -- the
+    /// only reconfigure-ish signal crossterm surfaces -- re-runs detection
+    /// rather than trusting the one done at startup forever
+    pub fn refresh_palette(&mut self) {
+        self.palette = Palette::for_appearance(theme::detect());
+    }
+
     /// Process async operations
     pub async fn tick(&mut self) {
         // TODO: Process WebSocket messages, update state
+        self.update_preview().await;
+    }
+
+    /// Reload the preview pane for the focused entry, debounced so a reload
+    /// only fires once the cursor has rested on an entry for
+    /// `PREVIEW_DEBOUNCE` rather than on every `j`/`k` step.
+    async fn update_preview(&mut self) {
+        if !self.state.show_preview {
+            self.preview_debounce = None;
+            return;
+        }
+
+        let Some(entry) = self.state.current_entry().cloned() else {
+            self.state.clear_preview();
+            self.preview_debounce = None;
+            return;
+        };
+
+        if !self.state.preview_stale() {
+            self.preview_debounce = None;
+            return;
+        }
+
+        match &self.preview_debounce {
+            Some((path, modified, settled_at))
+                if *path == entry.path && *modified == entry.modified =>
+            {
+                if settled_at.elapsed() >= PREVIEW_DEBOUNCE {
+                    self.load_preview(&entry).await;
+                    self.preview_debounce = None;
+                }
+            }
+            _ => {
+                self.preview_debounce = Some((entry.path.clone(), entry.modified, Instant::now()));
+            }
+        }
+    }
+
+    /// Build and store the preview for one entry: a child listing for
+    /// directories, highlighted text for text files, or summary info
+    /// otherwise. There's no network client wired into `App` yet (see
+    /// `delete_selected` below), so content comes from local metadata only
+    /// until downloading through `FilebrowserClient` lands here.
+    async fn load_preview(&mut self, entry: &FileEntry) {
+        // The focused entry is changing (or being reloaded), so whatever
+        // image was previously placed via the terminal's graphics protocol
+        // is about to be covered by new preview content -- clear it first
+        // so it doesn't linger on screen as a stale overlay
+        if let Some(clear) = self.placements.clear() {
+            use std::io::Write;
+            let _ = write!(std::io::stdout(), "{clear}");
+            let _ = std::io::stdout().flush();
+        }
+
+        let preview = if entry.is_dir() {
+            // TODO: fetch the child listing from the server; demo mode has
+            // no way to list an arbitrary path without navigating to it
+            Preview::Directory { entries: Vec::new() }
+        } else if preview::is_probably_text(entry.mime_type.as_deref()) {
+            // TODO: fetch file content via FilebrowserClient::download
+            Preview::Binary {
+                info: "preview unavailable: no content source wired up yet".to_string(),
+            }
+        } else {
+            // TODO: once content is fetched via FilebrowserClient::download,
+            // for image/* entries decode it with `termgfx::decode_rgba` and
+            // allocate a placement with `self.placements.place()`, writing
+            // `termgfx::encode_kitty`/a Sixel encoder to stdout instead of
+            // falling back to this text summary -- no content source is
+            // wired up yet, so there are no bytes to decode here either way.
+            Preview::Binary { info: preview::describe_binary(entry, None) }
+        };
+
+        self.state.set_preview(entry.path.clone(), entry.modified, preview);
+    }
+
+    /// Run (and cache) syntect highlighting for a text preview's
+    /// reconstructed content, returning an owned copy of the cached lines
+    /// so the caller isn't left holding the cache's `RefCell` borrow
+    pub fn highlighted_text_lines(
+        &self,
+        path: &str,
+        modified: i64,
+        content: &str,
+        extension: &str,
+        max_lines: usize,
+    ) -> Vec<Line<'static>> {
+        let mut cache = self.text_highlight.borrow_mut();
+        highlight::cached_highlight(&mut cache, path, modified, content, extension, max_lines).to_vec()
     }
 
     /// Navigate to a directory
     pub async fn navigate_to(&mut self, path: &str) {
         // TODO: Request directory listing from server
         self.state.set_status(format!("Navigate to: {}", path), StatusLevel::Info);
+        self.watcher = DirWatcher::watch(path).ok();
+    }
+
+    /// Poll the filesystem watcher for the focused directory (if any) and,
+    /// once its debounce window has elapsed, report the external change in
+    /// the status bar; the listing itself is not re-fetched. Called
+    /// once per main-loop iteration alongside keyboard/script-pipe polling,
+    /// so a burst of external changes surfaces as a single refresh instead
+    /// of thrashing the redraw.
+    pub async fn poll_watcher(&mut self) {
+        let Some(watcher) = self.watcher.as_mut() else { return };
+        if !watcher.poll() {
+            return;
+        }
+
+        // TODO: re-fetch the listing via FilebrowserClient once a real
+        // connection is wired up (see `load_preview`'s TODOs); there's no
+        // content source to re-read from yet, but `AppState::refresh_entries`
+        // is ready to take the fetched entries and preserve the cursor.
+        self.state.set_status(
+            format!("{} changed externally", self.state.current_path),
+            StatusLevel::Info,
+        );
     }
 
     /// Go up one directory
@@ -149,32 +354,116 @@ impl App {
         }
     }
 
-    /// Delete selected entries
-    pub async fn delete_selected(&mut self) {
-        let paths = self.state.selected_paths();
+    /// Delete selected entries, stashing them in `register` (or the unnamed
+    /// register) as a cut so the operation can feed a later paste. Queues a
+    /// pending delete op rather than deleting synchronously -- same as
+    /// `AppState::redo()`'s re-delete path, it's up to the op's eventual
+    /// completion handler to call `record_trash` with the real,
+    /// server-reported trash paths.
+    pub async fn delete_selected(&mut self, register: Option<char>) {
+        let paths: Vec<String> = self.state.selected_paths().into_iter().map(String::from).collect();
         if paths.is_empty() {
             return;
         }
 
-        // TODO: Confirm and delete
+        self.state.yank_to_register(register, paths.clone(), true);
         self.state.set_status(
-            format!("Delete {} item(s)?", paths.len()),
+            format!("Deleting {} item(s)...", paths.len()),
             StatusLevel::Warning,
         );
+        self.state.queue_delete(paths);
     }
 
-    /// Copy selected entries to clipboard
-    pub fn copy_selected(&mut self) {
-        let paths = self.state.selected_paths();
+    /// Copy selected entries into `register` (or the unnamed register) for
+    /// a later paste
+    pub fn copy_selected(&mut self, register: Option<char>) {
+        let paths: Vec<String> = self.state.selected_paths().into_iter().map(String::from).collect();
+        self.state.yank_to_register(register, paths.clone(), false);
         self.state.set_status(
             format!("Copied {} item(s)", paths.len()),
             StatusLevel::Success,
         );
     }
 
-    /// Paste from clipboard
-    pub async fn paste(&mut self) {
-        // TODO: Implement paste
-        self.state.set_status("Paste (not implemented)", StatusLevel::Info);
+    /// Run `command` through the shell in the focused directory, replacing
+    /// whatever `command_output` held before with a fresh (empty, still
+    /// running) buffer that fills in as `poll_command_output` drains it
+    pub fn start_command(&mut self, command: String) {
+        self.command_rx = Some(exec::spawn(&command, &self.state.current_path));
+        self.command_output = Some(CommandOutput::new(command));
+    }
+
+    /// Drain any events streamed from the running command (if any),
+    /// folding them into `command_output` and, once it reports the
+    /// process has exited, coloring the status bar by exit code
+    pub fn poll_command_output(&mut self) {
+        let Some(rx) = self.command_rx.as_mut() else { return };
+
+        let mut finished = None;
+        while let Ok(event) = rx.try_recv() {
+            if let exec::CommandEvent::Finished(code) = &event {
+                finished = Some(*code);
+            }
+            if let Some(output) = self.command_output.as_mut() {
+                output.apply(event);
+            }
+        }
+
+        if let Some(code) = finished {
+            self.command_rx = None;
+            match code {
+                Some(0) => self.state.set_status("Command finished", StatusLevel::Success),
+                Some(c) => self.state.set_status(format!("Command exited with status {c}"), StatusLevel::Error),
+                None => self.state.set_status("Command failed to run", StatusLevel::Error),
+            }
+        }
+    }
+
+    /// `:mkdir` built-in: create a directory inside the focused one
+    /// directly rather than shelling out to `mkdir`, since there's nothing
+    /// to stream and it should feel instant
+    pub fn mkdir(&mut self, name: &str) {
+        let path = std::path::Path::new(&self.state.current_path).join(name);
+        match std::fs::create_dir(&path) {
+            Ok(()) => self.state.set_status(format!("Created {}", path.display()), StatusLevel::Success),
+            Err(e) => self.state.set_status(format!("mkdir failed: {e}"), StatusLevel::Error),
+        }
+    }
+
+    /// `:rename` built-in: rename the focused entry to `new_name`, acting
+    /// on the current selection rather than taking an explicit source path
+    /// the way shelling out to `mv` would require
+    pub fn rename_current(&mut self, new_name: &str) {
+        let Some(entry) = self.state.current_entry() else {
+            self.state.set_status("rename: no entry focused", StatusLevel::Error);
+            return;
+        };
+
+        let from = std::path::Path::new(&entry.path).to_path_buf();
+        let to = match from.parent() {
+            Some(parent) => parent.join(new_name),
+            None => std::path::PathBuf::from(new_name),
+        };
+
+        match std::fs::rename(&from, &to) {
+            Ok(()) => self.state.set_status(format!("Renamed to {}", to.display()), StatusLevel::Success),
+            Err(e) => self.state.set_status(format!("rename failed: {e}"), StatusLevel::Error),
+        }
+    }
+
+    /// Paste the contents of `register` (or the unnamed register) at the
+    /// current location
+    pub async fn paste(&mut self, register: Option<char>) {
+        match self.state.register_contents(register) {
+            Some(reg) if !reg.paths.is_empty() => {
+                // TODO: actually copy/move the files server-side
+                let verb = if reg.cut { "Move" } else { "Paste" };
+                self.state.set_status(
+                    format!("{} {} item(s)", verb, reg.paths.len()),
+                    StatusLevel::Info,
+                );
+            }
+            _ => self.state.set_status("Register is empty", StatusLevel::Info),
+        }
     }
 }
@@ -0,0 +1,121 @@
+//! Execution of `:`/`!` commands with captured stdout/stderr
+//!
+//! `InputMode::Command` used to only echo the typed text. This actually
+//! spawns it through the user's shell in `state.current_path` and streams
+//! output back over a channel as it's produced, rather than waiting for
+//! the process to exit, the same non-blocking-main-loop shape
+//! `watch::DirWatcher` and `script::ScriptPipes` use.
+
+use std::process::Stdio;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// One line of captured output, tagged by which stream it came from so
+/// the UI can color stderr differently
+#[derive(Debug, Clone)]
+pub enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// An update from a running command: either another line of output, or
+/// its final exit code (`None` if the process couldn't be spawned, or was
+/// killed by a signal rather than exiting normally)
+#[derive(Debug, Clone)]
+pub enum CommandEvent {
+    Line(OutputLine),
+    Finished(Option<i32>),
+}
+
+/// A command's accumulated output, shown in place of the preview pane
+/// until another command replaces it
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub command: String,
+    pub lines: Vec<OutputLine>,
+    pub exit_code: Option<i32>,
+    pub running: bool,
+}
+
+impl CommandOutput {
+    pub fn new(command: String) -> Self {
+        Self {
+            command,
+            lines: Vec::new(),
+            exit_code: None,
+            running: true,
+        }
+    }
+
+    /// Fold one streamed event into the buffer
+    pub fn apply(&mut self, event: CommandEvent) {
+        match event {
+            CommandEvent::Line(line) => self.lines.push(line),
+            CommandEvent::Finished(code) => {
+                self.exit_code = code;
+                self.running = false;
+            }
+        }
+    }
+}
+
+/// Spawn `command` through `$SHELL -c` (falling back to `/bin/sh`) in
+/// `cwd`, streaming each output line back over the returned channel as
+/// it's produced
+pub fn spawn(command: &str, cwd: &str) -> mpsc::UnboundedReceiver<CommandEvent> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let command = command.to_string();
+    let cwd = cwd.to_string();
+
+    tokio::spawn(async move {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+        let child = Command::new(shell)
+            .arg("-c")
+            .arg(&command)
+            .current_dir(&cwd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = tx.send(CommandEvent::Line(OutputLine::Stderr(format!("failed to start: {e}"))));
+                let _ = tx.send(CommandEvent::Finished(None));
+                return;
+            }
+        };
+
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        let out_tx = tx.clone();
+        let out_task = async move {
+            if let Some(stdout) = stdout {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let _ = out_tx.send(CommandEvent::Line(OutputLine::Stdout(line)));
+                }
+            }
+        };
+
+        let err_tx = tx.clone();
+        let err_task = async move {
+            if let Some(stderr) = stderr {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let _ = err_tx.send(CommandEvent::Line(OutputLine::Stderr(line)));
+                }
+            }
+        };
+
+        tokio::join!(out_task, err_task);
+
+        let status = child.wait().await.ok().and_then(|s| s.code());
+        let _ = tx.send(CommandEvent::Finished(status));
+    });
+
+    rx
+}
@@ -0,0 +1,117 @@
+//! syntect-backed syntax highlighting for the preview pane
+//!
+//! `pibox_core::preview` keeps its own tiny dependency-free scanner so
+//! anything else linking against it (other clients, or a build for a device
+//! too constrained for a full grammar set) isn't forced to pull in a
+//! heavyweight highlighting crate. This module is the TUI's real
+//! highlighter, loading syntect's bundled syntaxes/themes once and
+//! re-highlighting only when the previewed entry or its mtime changes, so
+//! scrolling the same file doesn't redo the work every frame.
+
+use std::sync::OnceLock;
+
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+const THEME: &str = "base16-ocean.dark";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static SET: OnceLock<ThemeSet> = OnceLock::new();
+    SET.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Highlighted output for the last text preview built, keyed by the entry
+/// it was built for so scrolling the same file is just a cache hit
+pub struct HighlightCache {
+    pub path: String,
+    pub modified: i64,
+    pub lines: Vec<Line<'static>>,
+}
+
+/// Highlight `content` as `extension`-flavored source, falling back to
+/// first-line detection and then plain text, capped at `max_lines`
+pub fn highlight(content: &str, extension: &str, max_lines: usize) -> Vec<Line<'static>> {
+    let syntax_set = syntax_set();
+    let syntax = syntax_set
+        .find_syntax_by_extension(extension)
+        .or_else(|| syntax_set.find_syntax_by_first_line(content))
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+
+    let theme = &theme_set().themes[THEME];
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    content
+        .lines()
+        .take(max_lines)
+        .filter_map(|line| highlighter.highlight_line(line, syntax_set).ok())
+        .map(|segments| {
+            Line::from(
+                segments
+                    .into_iter()
+                    .map(|(style, text)| {
+                        let fg = style.foreground;
+                        Span::styled(text.to_string(), Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b)))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect()
+}
+
+/// Rebuild `cache` from `content` if it's stale for `path`/`modified`, then
+/// return the (possibly just-rebuilt) highlighted lines
+pub fn cached_highlight<'a>(
+    cache: &'a mut Option<HighlightCache>,
+    path: &str,
+    modified: i64,
+    content: &str,
+    extension: &str,
+    max_lines: usize,
+) -> &'a [Line<'static>] {
+    let stale = !matches!(cache, Some(c) if c.path == path && c.modified == modified);
+    if stale {
+        *cache = Some(HighlightCache {
+            path: path.to_string(),
+            modified,
+            lines: highlight(content, extension, max_lines),
+        });
+    }
+    &cache.as_ref().expect("just populated above if absent").lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_colors_a_rust_keyword() {
+        let lines = highlight("let x = 1;", "rs", 10);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].spans.iter().any(|s| s.content.contains("let")));
+    }
+
+    #[test]
+    fn test_highlight_respects_max_lines() {
+        let lines = highlight("one\ntwo\nthree\nfour", "txt", 2);
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn test_cached_highlight_rebuilds_when_mtime_changes() {
+        let mut cache = None;
+        let first = cached_highlight(&mut cache, "/a.rs", 1, "let x = 1;", "rs", 10);
+        assert_eq!(first.len(), 1);
+
+        // A different mtime invalidates the cache even though the path matches
+        let _ = cached_highlight(&mut cache, "/a.rs", 2, "let y = 2;\nlet z = 3;", "rs", 10);
+        assert_eq!(cache.as_ref().unwrap().lines.len(), 2);
+    }
+}
@@ -1,192 +1,173 @@
-//! Keyboard input handling with vim-style bindings
+//! Keyboard input handling, dispatched through the configurable [`KeyMap`]
 
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use pibox_core::state::InputMode;
+use crossterm::event::{KeyCode, KeyEvent};
+use pibox_core::state::{BookmarkAction, InputMode, KeyOutcome, Motion, Operator, StatusLevel};
 
 use crate::app::{App, AppResult};
+use crate::keymap::{Action, KeyCombo};
 
-/// Handle a key event
+/// Handle a key event: look up the bound action for the current input mode
+/// and dispatch it, falling back to plain text entry in modes that accept it
 pub async fn handle_key(app: &mut App, key: KeyEvent) -> AppResult {
-    match app.state.input_mode {
-        InputMode::Normal => handle_normal_mode(app, key).await,
-        InputMode::Search => handle_search_mode(app, key),
-        InputMode::Command => handle_command_mode(app, key),
-        InputMode::Rename => handle_rename_mode(app, key),
-        InputMode::Confirm(_) => handle_confirm_mode(app, key),
+    let combo = KeyCombo::new(key.code, key.modifiers);
+    let action = app.keymap.lookup(&app.state.input_mode, combo);
+
+    if let Some(action) = action {
+        return dispatch_action(app, action).await;
     }
-}
 
-/// Handle keys in normal mode (main navigation)
-async fn handle_normal_mode(app: &mut App, key: KeyEvent) -> AppResult {
-    match key.code {
-        // Navigation (vim-style)
-        KeyCode::Char('j') | KeyCode::Down => {
-            app.state.cursor_down();
-        }
-        KeyCode::Char('k') | KeyCode::Up => {
-            app.state.cursor_up();
-        }
-        KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace => {
-            app.navigate_up().await;
+    match app.state.input_mode {
+        InputMode::Search => {
+            if let KeyCode::Char(c) = key.code {
+                app.state.search_query.push(c);
+                app.state.update_search_filter();
+            }
         }
-        KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => {
-            app.enter().await;
+        InputMode::Command => {
+            if let KeyCode::Char(c) = key.code {
+                app.state.command_input.push(c);
+            }
         }
+        InputMode::Confirm(_) => return handle_confirm_mode(app, key),
+        InputMode::Bookmark(_) => return handle_bookmark_mode(app, key).await,
+        InputMode::Normal | InputMode::Rename => {}
+    }
 
-        // Jump navigation
-        KeyCode::Char('g') => {
-            // gg = go to top (would need state for multi-key)
-            app.state.cursor_top();
-        }
-        KeyCode::Char('G') => {
-            // G = go to bottom
-            app.state.cursor_bottom();
-        }
+    AppResult::Continue
+}
 
-        // Page navigation
-        KeyCode::PageDown | KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+/// Dispatch a resolved action onto `AppState`/`App`. Most actions are
+/// unambiguous; `Submit` and `Backspace` act on whichever buffer the
+/// current input mode owns.
+async fn dispatch_action(app: &mut App, action: Action) -> AppResult {
+    match action {
+        Action::VimKey(c) => {
+            let outcome = app.state.feed_key(c);
+            apply_key_outcome(app, outcome).await;
+        }
+        Action::StepDown => app.state.cursor_down(),
+        Action::StepUp => app.state.cursor_up(),
+        Action::NavigateUp => app.navigate_up().await,
+        Action::Enter => app.enter().await,
+        Action::ToggleSelection => {
+            app.state.toggle_selection();
+            app.state.cursor_down(); // Move to next after toggle
+        }
+        Action::StartRangeSelection => app.state.start_range_selection(),
+        Action::SelectAll => app.state.select_all(),
+        Action::PageDown => {
             for _ in 0..app.state.visible_rows {
                 app.state.cursor_down();
             }
         }
-        KeyCode::PageUp | KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+        Action::PageUp => {
             for _ in 0..app.state.visible_rows {
                 app.state.cursor_up();
             }
         }
-
-        // Selection
-        KeyCode::Char(' ') => {
-            app.state.toggle_selection();
-            app.state.cursor_down(); // Move to next after toggle
-        }
-        KeyCode::Char('V') => {
-            // Visual line mode (range selection)
-            app.state.start_range_selection();
-        }
-        KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.state.select_all();
+        Action::Paste => {
+            let register = app.state.take_pending_register();
+            app.paste(register).await;
         }
-
-        // Actions
-        KeyCode::Char('y') => {
-            // Yank (copy)
-            app.copy_selected();
-        }
-        KeyCode::Char('p') => {
-            // Paste
-            app.paste().await;
-        }
-        KeyCode::Char('d') => {
-            // Delete
-            app.delete_selected().await;
-        }
-        KeyCode::Char('r') => {
-            // Rename
-            app.state.input_mode = InputMode::Rename;
-        }
-
-        // Mode switching
-        KeyCode::Char('/') => {
-            app.state.enter_search_mode();
-        }
-        KeyCode::Char(':') => {
+        Action::Rename => app.state.input_mode = InputMode::Rename,
+        Action::EnterBookmarkSet => app.state.input_mode = InputMode::Bookmark(BookmarkAction::Set),
+        Action::EnterBookmarkJump => app.state.input_mode = InputMode::Bookmark(BookmarkAction::Jump),
+        Action::EnterSearch => app.state.enter_search_mode(),
+        Action::EnterCommand => {
             app.state.input_mode = InputMode::Command;
             app.state.command_input.clear();
         }
-
-        // Undo/Redo
-        KeyCode::Char('u') => {
-            app.state.undo();
-        }
-        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.state.redo();
-        }
-
-        // Escape clears selection
-        KeyCode::Esc => {
+        Action::Undo => app.state.undo(),
+        Action::Redo => app.state.redo(),
+        Action::ResetPending => {
+            app.state.reset_pending();
             app.state.clear_selection();
             app.state.clear_status();
+            app.command_output = None;
         }
-
-        // Help
-        KeyCode::Char('?') => {
+        Action::Help => {
             app.state.set_status(
-                "j/k:move h/l:nav space:select d:del y:copy p:paste /:search q:quit",
-                pibox_core::state::StatusLevel::Info,
+                "j/k:move h/l:nav space:select d:del y:copy p:paste \"a:register /:search q:quit",
+                StatusLevel::Info,
             );
         }
-
-        _ => {}
+        Action::TogglePreview => app.state.toggle_preview(),
+        Action::ExitMode => match app.state.input_mode {
+            InputMode::Search => app.state.cancel_search(),
+            _ => app.state.exit_input_mode(),
+        },
+        Action::Submit => submit_input_mode(app),
+        Action::Backspace => match app.state.input_mode {
+            InputMode::Search => {
+                app.state.search_query.pop();
+                app.state.update_search_filter();
+            }
+            InputMode::Command => {
+                app.state.command_input.pop();
+            }
+            _ => {}
+        },
     }
 
     AppResult::Continue
 }
 
-/// Handle keys in search mode
-fn handle_search_mode(app: &mut App, key: KeyEvent) -> AppResult {
-    match key.code {
-        KeyCode::Esc => {
-            app.state.exit_input_mode();
-        }
-        KeyCode::Enter => {
-            // Execute search
+/// Commit the current input mode's buffer (search query, command line, or rename)
+fn submit_input_mode(app: &mut App) {
+    match app.state.input_mode {
+        InputMode::Search => {
             let query = app.state.search_query.clone();
-            app.state.set_status(format!("Search: {}", query), pibox_core::state::StatusLevel::Info);
+            app.state.set_status(format!("Search: {}", query), StatusLevel::Info);
             app.state.exit_input_mode();
         }
-        KeyCode::Backspace => {
-            app.state.search_query.pop();
-        }
-        KeyCode::Char(c) => {
-            app.state.search_query.push(c);
-        }
-        _ => {}
-    }
-
-    AppResult::Continue
-}
-
-/// Handle keys in command mode
-fn handle_command_mode(app: &mut App, key: KeyEvent) -> AppResult {
-    match key.code {
-        KeyCode::Esc => {
-            app.state.exit_input_mode();
-        }
-        KeyCode::Enter => {
+        InputMode::Command => {
             let cmd = app.state.command_input.clone();
             execute_command(app, &cmd);
             app.state.exit_input_mode();
         }
-        KeyCode::Backspace => {
-            app.state.command_input.pop();
-        }
-        KeyCode::Char(c) => {
-            app.state.command_input.push(c);
+        InputMode::Rename => {
+            // TODO: Execute rename
+            app.state.exit_input_mode();
         }
-        _ => {}
+        InputMode::Normal | InputMode::Confirm(_) | InputMode::Bookmark(_) => {}
     }
-
-    AppResult::Continue
 }
 
-/// Handle keys in rename mode
-fn handle_rename_mode(app: &mut App, key: KeyEvent) -> AppResult {
-    match key.code {
-        KeyCode::Esc => {
-            app.state.exit_input_mode();
+/// Apply the result of feeding a key into the vim-grammar state machine
+async fn apply_key_outcome(app: &mut App, outcome: KeyOutcome) {
+    match outcome {
+        KeyOutcome::Pending => {}
+        KeyOutcome::Move { motion, count } => {
+            for _ in 0..count {
+                apply_motion(app, motion);
+            }
         }
-        KeyCode::Enter => {
-            // TODO: Execute rename
-            app.state.exit_input_mode();
+        KeyOutcome::Operate { operator, motion, count, register } => {
+            let target = app.state.motion_target(motion, count);
+            app.state.select_range(app.state.cursor, target);
+            match operator {
+                Operator::Delete => app.delete_selected(register).await,
+                Operator::Yank => app.copy_selected(register),
+            }
         }
-        _ => {}
     }
+}
 
-    AppResult::Continue
+/// Move the cursor by a single motion step
+fn apply_motion(app: &mut App, motion: Motion) {
+    match motion {
+        Motion::Down | Motion::Line => app.state.cursor_down(),
+        Motion::Up => app.state.cursor_up(),
+        Motion::Top => app.state.cursor_top(),
+        Motion::Bottom => app.state.cursor_bottom(),
+        Motion::WordForward => app.state.cursor_word_forward(),
+        Motion::WordBack => app.state.cursor_word_back(),
+        Motion::WordEnd => app.state.cursor_word_end(),
+    }
 }
 
-/// Handle keys in confirmation mode
+/// Handle keys in confirmation mode (not yet in the keymap: it's a
+/// two-choice prompt, not a set of independent bindings)
 fn handle_confirm_mode(app: &mut App, key: KeyEvent) -> AppResult {
     match key.code {
         KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
@@ -202,35 +183,89 @@ fn handle_confirm_mode(app: &mut App, key: KeyEvent) -> AppResult {
     AppResult::Continue
 }
 
-/// Execute a command-mode command
+/// Handle keys in bookmark mode: the overlay is waiting on a single
+/// character naming the bookmark slot to set or jump to
+async fn handle_bookmark_mode(app: &mut App, key: KeyEvent) -> AppResult {
+    let action = match app.state.input_mode {
+        InputMode::Bookmark(action) => action,
+        _ => unreachable!(),
+    };
+
+    match key.code {
+        KeyCode::Char(c) => match action {
+            BookmarkAction::Set => {
+                let path = app.state.current_path.clone();
+                app.bookmarks.set(c, path.clone());
+                if let Err(e) = app.bookmarks.save() {
+                    app.state.set_status(format!("Bookmark saved but not persisted: {e}"), StatusLevel::Warning);
+                } else {
+                    app.state.set_status(format!("Bookmarked '{}' as {}", path, c), StatusLevel::Success);
+                }
+                app.state.exit_input_mode();
+            }
+            BookmarkAction::Jump => {
+                app.state.exit_input_mode();
+                match app.bookmarks.get(c).map(str::to_string) {
+                    Some(path) => app.navigate_to(&path).await,
+                    None => app.state.set_status(format!("No bookmark at '{}'", c), StatusLevel::Error),
+                }
+            }
+        },
+        KeyCode::Esc => app.state.exit_input_mode(),
+        _ => {}
+    }
+
+    AppResult::Continue
+}
+
+/// Execute a command-mode command: a handful of built-ins (below) are
+/// special-cased because they act on the current selection rather than
+/// taking an explicit path the way shelling out would require; anything
+/// else -- and anything after an explicit `!` passthrough prefix -- is
+/// spawned for real through the shell, with its output streamed into
+/// `command_output`.
 fn execute_command(app: &mut App, cmd: &str) {
+    let cmd = cmd.trim();
+
+    if let Some(shell_cmd) = cmd.strip_prefix('!') {
+        app.start_command(shell_cmd.trim().to_string());
+        return;
+    }
+
     let parts: Vec<&str> = cmd.split_whitespace().collect();
     match parts.first().copied() {
         Some("q") | Some("quit") => {
             // Will be caught in main loop
         }
         Some("w") | Some("write") => {
-            app.state.set_status("Nothing to save", pibox_core::state::StatusLevel::Info);
+            app.state.set_status("Nothing to save", StatusLevel::Info);
         }
         Some("wq") => {
             // Save and quit
         }
         Some("cd") => {
             if let Some(path) = parts.get(1) {
-                app.state.set_status(format!("cd {}", path), pibox_core::state::StatusLevel::Info);
+                app.state.set_status(format!("cd {}", path), StatusLevel::Info);
             }
         }
         Some("set") => {
             if let Some(opt) = parts.get(1) {
-                app.state.set_status(format!("set {}", opt), pibox_core::state::StatusLevel::Info);
+                app.state.set_status(format!("set {}", opt), StatusLevel::Info);
             }
         }
-        Some(unknown) => {
-            app.state.set_status(
-                format!("Unknown command: {}", unknown),
-                pibox_core::state::StatusLevel::Error,
-            );
-        }
+        Some("reg") | Some("registers") => {
+            let summary = app.state.format_registers();
+            app.state.set_status(summary, StatusLevel::Info);
+        }
+        Some("mkdir") => match parts.get(1) {
+            Some(name) => app.mkdir(name),
+            None => app.state.set_status("mkdir: missing directory name", StatusLevel::Error),
+        },
+        Some("rename") => match parts.get(1) {
+            Some(new_name) => app.rename_current(new_name),
+            None => app.state.set_status("rename: missing new name", StatusLevel::Error),
+        },
+        Some(_) => app.start_command(cmd.to_string()),
         None => {}
     }
 }
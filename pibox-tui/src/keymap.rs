@@ -0,0 +1,431 @@
+//! Configurable keybindings
+//!
+//! Bindings are a table from `(InputMode, KeyCombo)` to a named [`Action`],
+//! kept as one table per mode since the same key means different things in
+//! different modes (`j` moves the cursor in Normal mode but types the
+//! letter `j` in Search mode). [`KeyMap::default`] ships the vim bindings
+//! pibox has always used, so behavior is unchanged out of the box; users
+//! can add or override individual entries in `keymap.toml` alongside
+//! `config.toml` without having to restate the whole table.
+//!
+//! Vim-grammar keys (digit counts, the `g` prefix, motions, `d`/`y`
+//! operators) are not each a distinct action — they all map to
+//! [`Action::VimKey`], which carries the character to feed into
+//! [`pibox_core::state::AppState::feed_key`]. Remapping e.g. the down
+//! motion to a different key is just pointing that key at `VimKey('j')`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use pibox_core::state::InputMode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum KeyMapError {
+    #[error("failed to read keymap: {0}")]
+    Read(#[from] std::io::Error),
+
+    #[error("failed to parse keymap: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("invalid key combo '{0}'")]
+    InvalidCombo(String),
+
+    #[error("config directory not found")]
+    NoConfigDir,
+}
+
+/// A named, dispatchable action that a bound key resolves to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Feed this character into the vim-grammar state machine (counts,
+    /// `gg`, motions, `d`/`y` operators)
+    VimKey(char),
+    /// Single-step cursor move, bypassing the vim-grammar count/operator
+    /// accumulation (used by the arrow keys)
+    StepDown,
+    StepUp,
+    NavigateUp,
+    Enter,
+    ToggleSelection,
+    StartRangeSelection,
+    SelectAll,
+    PageDown,
+    PageUp,
+    Paste,
+    Rename,
+    EnterSearch,
+    EnterCommand,
+    Undo,
+    Redo,
+    ResetPending,
+    Help,
+    /// Show/hide the preview pane for the focused entry
+    TogglePreview,
+    /// Leave the current input mode without committing it
+    ExitMode,
+    /// Commit the current input mode's buffer (search query, command, ...)
+    Submit,
+    Backspace,
+    /// Open the bookmark overlay waiting for a key to bookmark the current path under
+    EnterBookmarkSet,
+    /// Open the bookmark overlay waiting for a key naming a bookmark to jump to
+    EnterBookmarkJump,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::VimKey(c) => write!(f, "vim_key:{c}"),
+            Action::StepDown => write!(f, "step_down"),
+            Action::StepUp => write!(f, "step_up"),
+            Action::NavigateUp => write!(f, "navigate_up"),
+            Action::Enter => write!(f, "enter"),
+            Action::ToggleSelection => write!(f, "toggle_selection"),
+            Action::StartRangeSelection => write!(f, "start_range_selection"),
+            Action::SelectAll => write!(f, "select_all"),
+            Action::PageDown => write!(f, "page_down"),
+            Action::PageUp => write!(f, "page_up"),
+            Action::Paste => write!(f, "paste"),
+            Action::Rename => write!(f, "rename"),
+            Action::EnterSearch => write!(f, "enter_search"),
+            Action::EnterCommand => write!(f, "enter_command"),
+            Action::Undo => write!(f, "undo"),
+            Action::Redo => write!(f, "redo"),
+            Action::ResetPending => write!(f, "reset_pending"),
+            Action::Help => write!(f, "help"),
+            Action::TogglePreview => write!(f, "toggle_preview"),
+            Action::ExitMode => write!(f, "exit_mode"),
+            Action::Submit => write!(f, "submit"),
+            Action::Backspace => write!(f, "backspace"),
+            Action::EnterBookmarkSet => write!(f, "enter_bookmark_set"),
+            Action::EnterBookmarkJump => write!(f, "enter_bookmark_jump"),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(c) = s.strip_prefix("vim_key:") {
+            let ch = c.chars().next().ok_or("vim_key needs a character")?;
+            return Ok(Action::VimKey(ch));
+        }
+
+        Ok(match s {
+            "step_down" => Action::StepDown,
+            "step_up" => Action::StepUp,
+            "navigate_up" => Action::NavigateUp,
+            "enter" => Action::Enter,
+            "toggle_selection" => Action::ToggleSelection,
+            "start_range_selection" => Action::StartRangeSelection,
+            "select_all" => Action::SelectAll,
+            "page_down" => Action::PageDown,
+            "page_up" => Action::PageUp,
+            "paste" => Action::Paste,
+            "rename" => Action::Rename,
+            "enter_search" => Action::EnterSearch,
+            "enter_command" => Action::EnterCommand,
+            "undo" => Action::Undo,
+            "redo" => Action::Redo,
+            "reset_pending" => Action::ResetPending,
+            "help" => Action::Help,
+            "toggle_preview" => Action::TogglePreview,
+            "exit_mode" => Action::ExitMode,
+            "submit" => Action::Submit,
+            "backspace" => Action::Backspace,
+            "enter_bookmark_set" => Action::EnterBookmarkSet,
+            "enter_bookmark_jump" => Action::EnterBookmarkJump,
+            other => return Err(format!("unknown action '{other}'")),
+        })
+    }
+}
+
+impl Serialize for Action {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A key plus modifiers, the unit bindings are defined in terms of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyCombo {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    fn plain(code: KeyCode) -> Self {
+        Self::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> Self {
+        Self::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+}
+
+impl From<KeyEvent> for KeyCombo {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(tail) = rest.strip_prefix("ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix("shift+") {
+                modifiers |= KeyModifiers::SHIFT;
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix("alt+") {
+                modifiers |= KeyModifiers::ALT;
+                rest = tail;
+            } else {
+                break;
+            }
+        }
+
+        let code = match rest {
+            "esc" => KeyCode::Esc,
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            _ => {
+                let mut chars = rest.chars();
+                let c = chars.next().ok_or_else(|| s.to_string())?;
+                if chars.next().is_some() {
+                    return Err(s.to_string());
+                }
+                KeyCode::Char(c)
+            }
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+type ModeTable = HashMap<KeyCombo, Action>;
+
+/// Table of key bindings, one sub-table per [`InputMode`]
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    normal: ModeTable,
+    search: ModeTable,
+    command: ModeTable,
+    rename: ModeTable,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let mut normal = ModeTable::new();
+        for c in "0123456789gGjkwbedy\"".chars() {
+            normal.insert(KeyCombo::plain(KeyCode::Char(c)), Action::VimKey(c));
+        }
+        normal.insert(KeyCombo::plain(KeyCode::Down), Action::StepDown);
+        normal.insert(KeyCombo::plain(KeyCode::Up), Action::StepUp);
+        normal.insert(KeyCombo::plain(KeyCode::Char('h')), Action::NavigateUp);
+        normal.insert(KeyCombo::plain(KeyCode::Left), Action::NavigateUp);
+        normal.insert(KeyCombo::plain(KeyCode::Backspace), Action::NavigateUp);
+        normal.insert(KeyCombo::plain(KeyCode::Char('l')), Action::Enter);
+        normal.insert(KeyCombo::plain(KeyCode::Right), Action::Enter);
+        normal.insert(KeyCombo::plain(KeyCode::Enter), Action::Enter);
+        normal.insert(KeyCombo::plain(KeyCode::PageDown), Action::PageDown);
+        normal.insert(KeyCombo::ctrl('d'), Action::PageDown);
+        normal.insert(KeyCombo::plain(KeyCode::PageUp), Action::PageUp);
+        normal.insert(KeyCombo::ctrl('u'), Action::PageUp);
+        normal.insert(KeyCombo::plain(KeyCode::Char(' ')), Action::ToggleSelection);
+        normal.insert(KeyCombo::plain(KeyCode::Char('V')), Action::StartRangeSelection);
+        normal.insert(KeyCombo::ctrl('a'), Action::SelectAll);
+        normal.insert(KeyCombo::plain(KeyCode::Char('p')), Action::Paste);
+        normal.insert(KeyCombo::plain(KeyCode::Char('r')), Action::Rename);
+        normal.insert(KeyCombo::plain(KeyCode::Char('/')), Action::EnterSearch);
+        normal.insert(KeyCombo::plain(KeyCode::Char(':')), Action::EnterCommand);
+        normal.insert(KeyCombo::plain(KeyCode::Char('u')), Action::Undo);
+        normal.insert(KeyCombo::ctrl('r'), Action::Redo);
+        normal.insert(KeyCombo::plain(KeyCode::Esc), Action::ResetPending);
+        normal.insert(KeyCombo::plain(KeyCode::Char('?')), Action::Help);
+        normal.insert(KeyCombo::plain(KeyCode::Tab), Action::TogglePreview);
+        normal.insert(KeyCombo::plain(KeyCode::Char('m')), Action::EnterBookmarkSet);
+        normal.insert(KeyCombo::plain(KeyCode::Char('`')), Action::EnterBookmarkJump);
+
+        let mut search = ModeTable::new();
+        search.insert(KeyCombo::plain(KeyCode::Esc), Action::ExitMode);
+        search.insert(KeyCombo::plain(KeyCode::Enter), Action::Submit);
+        search.insert(KeyCombo::plain(KeyCode::Backspace), Action::Backspace);
+
+        let mut command = ModeTable::new();
+        command.insert(KeyCombo::plain(KeyCode::Esc), Action::ExitMode);
+        command.insert(KeyCombo::plain(KeyCode::Enter), Action::Submit);
+        command.insert(KeyCombo::plain(KeyCode::Backspace), Action::Backspace);
+
+        let mut rename = ModeTable::new();
+        rename.insert(KeyCombo::plain(KeyCode::Esc), Action::ExitMode);
+        rename.insert(KeyCombo::plain(KeyCode::Enter), Action::Submit);
+
+        Self { normal, search, command, rename }
+    }
+}
+
+impl KeyMap {
+    /// Look up the action bound to `combo` in `mode`, if any. Modes without
+    /// a table of their own (currently `Confirm` and `Bookmark`) have no bindings.
+    pub fn lookup(&self, mode: &InputMode, combo: KeyCombo) -> Option<Action> {
+        let table = match mode {
+            InputMode::Normal => &self.normal,
+            InputMode::Search => &self.search,
+            InputMode::Command => &self.command,
+            InputMode::Rename => &self.rename,
+            InputMode::Confirm(_) | InputMode::Bookmark(_) => return None,
+        };
+        table.get(&combo).copied()
+    }
+
+    /// Load the keymap from `~/.config/pibox/keymap.toml`, falling back to
+    /// the default vim bindings if the file is absent or fails to parse.
+    pub fn load() -> Self {
+        match Self::default_path().and_then(|path| {
+            if path.exists() {
+                Self::load_from(&path)
+            } else {
+                Ok(Self::default())
+            }
+        }) {
+            Ok(keymap) => keymap,
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn default_path() -> Result<std::path::PathBuf, KeyMapError> {
+        let dir = pibox_core::Config::config_dir().map_err(|_| KeyMapError::NoConfigDir)?;
+        Ok(dir.join("keymap.toml"))
+    }
+
+    /// Load from a specific file, overriding individual default bindings
+    /// with whatever the file specifies (unspecified bindings are kept)
+    pub fn load_from(path: &Path) -> Result<Self, KeyMapError> {
+        let content = std::fs::read_to_string(path)?;
+        let file: KeyMapFile = toml::from_str(&content)?;
+
+        let mut keymap = Self::default();
+        keymap.apply_overrides(&file.normal, |km| &mut km.normal)?;
+        keymap.apply_overrides(&file.search, |km| &mut km.search)?;
+        keymap.apply_overrides(&file.command, |km| &mut km.command)?;
+        keymap.apply_overrides(&file.rename, |km| &mut km.rename)?;
+        Ok(keymap)
+    }
+
+    fn apply_overrides(
+        &mut self,
+        overrides: &HashMap<String, Action>,
+        table: impl Fn(&mut Self) -> &mut ModeTable,
+    ) -> Result<(), KeyMapError> {
+        for (key, action) in overrides {
+            let combo = KeyCombo::from_str(key).map_err(KeyMapError::InvalidCombo)?;
+            table(self).insert(combo, *action);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct KeyMapFile {
+    #[serde(default)]
+    normal: HashMap<String, Action>,
+    #[serde(default)]
+    search: HashMap<String, Action>,
+    #[serde(default)]
+    command: HashMap<String, Action>,
+    #[serde(default)]
+    rename: HashMap<String, Action>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_keymap_matches_vim_bindings() {
+        let keymap = KeyMap::default();
+        assert_eq!(
+            keymap.lookup(&InputMode::Normal, KeyCombo::plain(KeyCode::Char('j'))),
+            Some(Action::VimKey('j'))
+        );
+        assert_eq!(
+            keymap.lookup(&InputMode::Normal, KeyCombo::ctrl('d')),
+            Some(Action::PageDown)
+        );
+        let confirm = InputMode::Confirm(pibox_core::state::ConfirmAction::Delete(vec![]));
+        assert_eq!(keymap.lookup(&confirm, KeyCombo::plain(KeyCode::Char('y'))), None);
+    }
+
+    #[test]
+    fn test_combo_parses_modifiers_and_named_keys() {
+        assert_eq!(
+            KeyCombo::from_str("ctrl+d").unwrap(),
+            KeyCombo::new(KeyCode::Char('d'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(KeyCombo::from_str("esc").unwrap(), KeyCombo::plain(KeyCode::Esc));
+        assert_eq!(KeyCombo::from_str("g").unwrap(), KeyCombo::plain(KeyCode::Char('g')));
+        assert!(KeyCombo::from_str("nonsense-key").is_err());
+    }
+
+    #[test]
+    fn test_action_roundtrips_through_string() {
+        assert_eq!(Action::VimKey('j').to_string(), "vim_key:j");
+        assert_eq!("vim_key:j".parse::<Action>().unwrap(), Action::VimKey('j'));
+        assert_eq!("undo".parse::<Action>().unwrap(), Action::Undo);
+        assert!("not_a_real_action".parse::<Action>().is_err());
+    }
+
+    #[test]
+    fn test_load_from_overrides_one_binding_and_keeps_rest() {
+        let dir = std::env::temp_dir().join("pibox-keymap-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keymap.toml");
+        std::fs::write(&path, "[normal]\n\"ctrl+d\" = \"vim_key:j\"\n").unwrap();
+
+        let keymap = KeyMap::load_from(&path).unwrap();
+        assert_eq!(
+            keymap.lookup(&InputMode::Normal, KeyCombo::ctrl('d')),
+            Some(Action::VimKey('j'))
+        );
+        // Untouched bindings remain at their default
+        assert_eq!(
+            keymap.lookup(&InputMode::Normal, KeyCombo::plain(KeyCode::Char('p'))),
+            Some(Action::Paste)
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+}
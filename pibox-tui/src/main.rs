@@ -7,8 +7,15 @@
 //! - Works on Pi Zero 2W (low memory)
 
 mod app;
+mod exec;
+mod highlight;
 mod input;
+mod keymap;
+mod script;
+mod termgfx;
+mod theme;
 mod ui;
+mod watch;
 
 use std::io;
 use std::time::Duration;
@@ -89,25 +96,52 @@ async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mu
 
         // Poll for events with timeout (allows async tasks to progress)
         if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                // Check for quit
-                if key.code == KeyCode::Char('q') && key.modifiers.is_empty() {
-                    if app.state.input_mode == pibox_core::state::InputMode::Normal {
+            match event::read()? {
+                Event::Resize(_, _) => app.refresh_palette(),
+                Event::Key(key) => {
+                    // Check for quit
+                    if key.code == KeyCode::Char('q') && key.modifiers.is_empty() {
+                        if app.state.input_mode == pibox_core::state::InputMode::Normal {
+                            return Ok(());
+                        }
+                    }
+                    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
                         return Ok(());
                     }
+
+                    // Handle key input
+                    match handle_key(app, key).await {
+                        AppResult::Continue => {}
+                        AppResult::Quit => return Ok(()),
+                    }
                 }
-                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
-                    return Ok(());
-                }
+                _ => {}
+            }
+        }
 
-                // Handle key input
-                match handle_key(app, key).await {
-                    AppResult::Continue => {}
-                    AppResult::Quit => return Ok(()),
+        // Poll the external scripting pipe alongside keyboard events, feeding
+        // parsed commands through the same AppState/App methods keys use
+        if let Some(mut pipes) = app.script.take() {
+            for result in pipes.poll_commands() {
+                let outcome = match result {
+                    Ok(cmd) => script::apply_script_command(app, cmd).await,
+                    Err(e) => Err(e),
+                };
+                match outcome {
+                    Ok(()) => pipes.write_result("ok"),
+                    Err(e) => pipes.write_result(&format!("error: {e}")),
                 }
             }
+            pipes.write_state(app);
+            app.script = Some(pipes);
         }
 
+        // Pick up external filesystem changes to the focused directory
+        app.poll_watcher().await;
+
+        // Stream in output from a running `:`/`!` command, if any
+        app.poll_command_output();
+
         // Process any pending async operations
         app.tick().await;
     }
@@ -0,0 +1,244 @@
+//! External scripting over a FIFO pipe, so automation can drive and observe
+//! the client without a TTY.
+//!
+//! On startup a session directory of named pipes is created: `msg_in`
+//! accepts newline-delimited [`ScriptCommand`]s, and `focus_out`,
+//! `selection_out`, `mode_out`, `result_out` are rewritten after every
+//! command so a reading script can observe state without polling a REST
+//! endpoint. Commands are dispatched through [`apply_script_command`],
+//! which calls the exact same `AppState`/`App` methods the keymap does —
+//! scripted and keyboard-driven input share one update path.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use nix::fcntl::{open, OFlag};
+use nix::sys::stat::Mode;
+use nix::unistd::mkfifo;
+
+use pibox_core::state::ViewMode;
+
+use crate::app::App;
+
+/// A scripted command read from `msg_in`, one per line (e.g. `FocusPath
+/// /foo`, `Select`, `SetViewMode Grid`, `Navigate /bar`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    FocusPath(String),
+    Navigate(String),
+    Select,
+    Deselect,
+    ToggleSelection,
+    SelectAll,
+    ClearSelection,
+    SetViewMode(ViewMode),
+}
+
+impl FromStr for ScriptCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parts = line.trim().splitn(2, ' ');
+        let verb = parts.next().unwrap_or("");
+        let rest = parts.next().unwrap_or("").trim();
+
+        match verb {
+            "FocusPath" if !rest.is_empty() => Ok(ScriptCommand::FocusPath(rest.to_string())),
+            "Navigate" if !rest.is_empty() => Ok(ScriptCommand::Navigate(rest.to_string())),
+            "Select" => Ok(ScriptCommand::Select),
+            "Deselect" => Ok(ScriptCommand::Deselect),
+            "ToggleSelection" => Ok(ScriptCommand::ToggleSelection),
+            "SelectAll" => Ok(ScriptCommand::SelectAll),
+            "ClearSelection" => Ok(ScriptCommand::ClearSelection),
+            "SetViewMode" => match rest {
+                "List" => Ok(ScriptCommand::SetViewMode(ViewMode::List)),
+                "Grid" => Ok(ScriptCommand::SetViewMode(ViewMode::Grid)),
+                "Tree" => Ok(ScriptCommand::SetViewMode(ViewMode::Tree)),
+                other => Err(format!("unknown view mode '{other}'")),
+            },
+            other => Err(format!("unknown command '{other}'")),
+        }
+    }
+}
+
+/// Apply a scripted command to the running app, the same way a bound key would
+pub async fn apply_script_command(app: &mut App, cmd: ScriptCommand) -> Result<(), String> {
+    match cmd {
+        ScriptCommand::FocusPath(path) => {
+            let index = app
+                .state
+                .entries
+                .iter()
+                .position(|e| e.path == path)
+                .ok_or_else(|| format!("no entry at path '{path}'"))?;
+            app.state.cursor = index;
+        }
+        ScriptCommand::Navigate(path) => app.navigate_to(&path).await,
+        ScriptCommand::Select | ScriptCommand::ToggleSelection => app.state.toggle_selection(),
+        ScriptCommand::Deselect => app.state.clear_selection(),
+        ScriptCommand::SelectAll => app.state.select_all(),
+        ScriptCommand::ClearSelection => app.state.clear_selection(),
+        ScriptCommand::SetViewMode(mode) => app.state.view_mode = mode,
+    }
+
+    Ok(())
+}
+
+/// The session's named pipes: one input, several state-observing outputs
+pub struct ScriptPipes {
+    dir: PathBuf,
+    msg_in: File,
+    read_buf: String,
+}
+
+const FIFOS: &[&str] = &["msg_in", "focus_out", "selection_out", "mode_out", "result_out"];
+
+impl ScriptPipes {
+    /// Create the session directory and its FIFOs, opening `msg_in` for
+    /// non-blocking reads so the main loop never stalls waiting on a script
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+
+        for name in FIFOS {
+            let path = dir.join(name);
+            if !path.exists() {
+                mkfifo(&path, Mode::from_bits_truncate(0o600))
+                    .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+            }
+        }
+
+        let msg_in = open_nonblocking_reader(&dir.join("msg_in"))?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            msg_in,
+            read_buf: String::new(),
+        })
+    }
+
+    /// Default session directory: `$XDG_RUNTIME_DIR/pibox/session-<pid>`,
+    /// falling back to `/tmp` when no runtime dir is available
+    pub fn default_dir() -> PathBuf {
+        dirs::runtime_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("pibox")
+            .join(format!("session-{}", std::process::id()))
+    }
+
+    /// Drain whatever has been written to `msg_in` since the last poll and
+    /// return the complete (newline-terminated) lines, parsed as commands.
+    /// Parse failures are reported as `Err` entries rather than dropped
+    /// silently, so a misbehaving script gets feedback via `result_out`.
+    pub fn poll_commands(&mut self) -> Vec<Result<ScriptCommand, String>> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            match self.msg_in.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => self.read_buf.push_str(&String::from_utf8_lossy(&chunk[..n])),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => break,
+            }
+        }
+
+        let mut commands = Vec::new();
+        while let Some(pos) = self.read_buf.find('\n') {
+            let line = self.read_buf[..pos].to_string();
+            self.read_buf.drain(..=pos);
+            if !line.trim().is_empty() {
+                commands.push(line.parse());
+            }
+        }
+        commands
+    }
+
+    /// Rewrite `focus_out`/`selection_out`/`mode_out` to reflect current state
+    pub fn write_state(&self, app: &App) {
+        let focus = app
+            .state
+            .entries
+            .get(app.state.cursor)
+            .map(|e| e.path.as_str())
+            .unwrap_or("");
+        self.write_pipe("focus_out", focus);
+
+        let selection = app.state.selected_paths().join("\n");
+        self.write_pipe("selection_out", &selection);
+
+        self.write_pipe(
+            "mode_out",
+            &format!("{:?} {:?}", app.state.input_mode, app.state.view_mode),
+        );
+    }
+
+    /// Report the outcome of the last command processed
+    pub fn write_result(&self, result: &str) {
+        self.write_pipe("result_out", result);
+    }
+
+    /// Best-effort write: opens the FIFO non-blocking so a command loop
+    /// never stalls when no script currently has the output pipe open
+    fn write_pipe(&self, name: &str, content: &str) {
+        let path = self.dir.join(name);
+        let fd = match open(&path, OFlag::O_WRONLY | OFlag::O_NONBLOCK, Mode::empty()) {
+            Ok(fd) => fd,
+            Err(_) => return, // no reader attached; drop the update
+        };
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        let _ = writeln!(file, "{content}");
+    }
+}
+
+fn open_nonblocking_reader(path: &Path) -> io::Result<File> {
+    let fd = open(path, OFlag::O_RDONLY | OFlag::O_NONBLOCK, Mode::empty())
+        .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            "FocusPath /foo".parse::<ScriptCommand>().unwrap(),
+            ScriptCommand::FocusPath("/foo".to_string())
+        );
+        assert_eq!("Select".parse::<ScriptCommand>().unwrap(), ScriptCommand::Select);
+        assert_eq!(
+            "SetViewMode Grid".parse::<ScriptCommand>().unwrap(),
+            ScriptCommand::SetViewMode(ViewMode::Grid)
+        );
+        assert_eq!(
+            "Navigate /bar".parse::<ScriptCommand>().unwrap(),
+            ScriptCommand::Navigate("/bar".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_command_and_missing_args() {
+        assert!("Bogus".parse::<ScriptCommand>().is_err());
+        assert!("FocusPath".parse::<ScriptCommand>().is_err());
+        assert!("SetViewMode Sideways".parse::<ScriptCommand>().is_err());
+    }
+
+    #[test]
+    fn test_pipes_roundtrip_commands() {
+        let dir = std::env::temp_dir().join(format!("pibox-script-test-{}", std::process::id()));
+        let pipes = ScriptPipes::open(&dir).unwrap();
+
+        std::fs::write(dir.join("msg_in"), "Select\nSetViewMode Grid\n").ok();
+        // `msg_in` is already open for reading, so this short-lived writer
+        // opens without blocking and its bytes wait in the pipe buffer for
+        // `poll_commands` to drain.
+
+        let mut pipes = pipes;
+        let commands = pipes.poll_commands();
+        assert!(commands.iter().any(|c| matches!(c, Ok(ScriptCommand::Select))));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,208 @@
+//! In-terminal image rendering via the Kitty graphics protocol
+//!
+//! Decoding is handled by the `image` crate, the same decoder
+//! `pibox-server::thumbnail`/`pibox_core::blurhash` use, producing the RGBA
+//! buffer `encode_kitty` transmits.
+
+use base64::Engine;
+use image::ImageFormat;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TermGfxError {
+    #[error("unrecognized or unsupported mime type for image preview: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("failed to decode image: {0}")]
+    DecodeFailed(String),
+}
+
+/// Decode `source` (whatever format `mime_type` names) into `(width,
+/// height, rgba)`, where `rgba` is `width * height * 4` bytes, row-major,
+/// no padding -- the shape [`encode_kitty`] expects.
+pub fn decode_rgba(source: &[u8], mime_type: &str) -> Result<(u32, u32, Vec<u8>), TermGfxError> {
+    let format =
+        ImageFormat::from_mime_type(mime_type).ok_or_else(|| TermGfxError::UnsupportedFormat(mime_type.to_string()))?;
+
+    let image =
+        image::load_from_memory_with_format(source, format).map_err(|e| TermGfxError::DecodeFailed(e.to_string()))?;
+
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    Ok((width, height, rgba.into_raw()))
+}
+
+/// How (if at all) the attached terminal can display images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Sixel,
+    None,
+}
+
+/// Detect graphics support from the environment, the way terminals
+/// themselves advertise it: `$KITTY_WINDOW_ID` is set only inside kitty,
+/// and a handful of `$TERM`/`$TERM_PROGRAM` values are known to speak Sixel
+pub fn detect() -> GraphicsProtocol {
+    detect_from(
+        std::env::var("TERM").ok().as_deref(),
+        std::env::var("TERM_PROGRAM").ok().as_deref(),
+        std::env::var("KITTY_WINDOW_ID").ok().as_deref(),
+    )
+}
+
+fn detect_from(term: Option<&str>, term_program: Option<&str>, kitty_window_id: Option<&str>) -> GraphicsProtocol {
+    if kitty_window_id.is_some() || term == Some("xterm-kitty") {
+        return GraphicsProtocol::Kitty;
+    }
+
+    let sixel_terms = ["foot", "wezterm", "mlterm", "xterm"];
+    if term_program == Some("WezTerm") || term.is_some_and(|t| sixel_terms.iter().any(|s| t.starts_with(s))) {
+        return GraphicsProtocol::Sixel;
+    }
+
+    GraphicsProtocol::None
+}
+
+/// Kitty's graphics transmission caps each chunk's base64 payload at this
+/// many bytes, continuing with `m=1` until the final chunk sets `m=0`
+const CHUNK_SIZE: usize = 4096;
+
+/// Next placement id handed out by [`PlacementTracker`]
+type PlacementId = u32;
+
+/// Encode `rgba` (tightly packed, `width * height * 4` bytes) as a Kitty
+/// graphics transmission, split into `CHUNK_SIZE`-byte base64 chunks with
+/// `m=1`/`m=0` continuation flags, placed under `id`
+pub fn encode_kitty(rgba: &[u8], width: u32, height: u32, id: PlacementId) -> Vec<String> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(rgba);
+    let chunks: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(CHUNK_SIZE)
+        .map(|c| std::str::from_utf8(c).expect("base64 output is ASCII"))
+        .collect();
+
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let more = if i + 1 < chunks.len() { 1 } else { 0 };
+            if i == 0 {
+                format!("\x1b_Gf=32,s={width},v={height},i={id},a=T,m={more};{chunk}\x1b\\")
+            } else {
+                format!("\x1b_Gm={more};{chunk}\x1b\\")
+            }
+        })
+        .collect()
+}
+
+/// Escape sequence that deletes a previously-placed image by id, so a
+/// stale preview doesn't linger on screen once the cursor moves off it
+pub fn clear_kitty_placement(id: PlacementId) -> String {
+    format!("\x1b_Ga=d,d=i,i={id}\x1b\\")
+}
+
+/// Hands out placement ids and remembers which one (if any) is currently
+/// on screen, so the preview pane can clear it when the cursor moves
+#[derive(Default)]
+pub struct PlacementTracker {
+    next_id: PlacementId,
+    current: Option<PlacementId>,
+}
+
+impl PlacementTracker {
+    pub fn new() -> Self {
+        Self { next_id: 1, current: None }
+    }
+
+    /// Allocate a fresh placement id for a newly-rendered preview
+    pub fn place(&mut self) -> PlacementId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.current = Some(id);
+        id
+    }
+
+    /// Drop the currently-tracked placement, returning the escape sequence
+    /// to clear it from the terminal if one was on screen
+    pub fn clear(&mut self) -> Option<String> {
+        self.current.take().map(clear_kitty_placement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_kitty_window_id_wins_regardless_of_term() {
+        assert_eq!(detect_from(Some("xterm-256color"), None, Some("1")), GraphicsProtocol::Kitty);
+    }
+
+    #[test]
+    fn test_detect_xterm_kitty_term() {
+        assert_eq!(detect_from(Some("xterm-kitty"), None, None), GraphicsProtocol::Kitty);
+    }
+
+    #[test]
+    fn test_detect_sixel_capable_term() {
+        assert_eq!(detect_from(Some("foot"), None, None), GraphicsProtocol::Sixel);
+        assert_eq!(detect_from(None, Some("WezTerm"), None), GraphicsProtocol::Sixel);
+    }
+
+    #[test]
+    fn test_detect_falls_back_to_none() {
+        assert_eq!(detect_from(Some("screen"), None, None), GraphicsProtocol::None);
+    }
+
+    #[test]
+    fn test_decode_rgba_rejects_unsupported_mime_type() {
+        assert_eq!(
+            decode_rgba(&[1, 2, 3], "video/mp4"),
+            Err(TermGfxError::UnsupportedFormat("video/mp4".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_decode_rgba_surfaces_decode_failure() {
+        assert!(matches!(decode_rgba(&[1, 2, 3], "image/png"), Err(TermGfxError::DecodeFailed(_))));
+    }
+
+    #[test]
+    fn test_decode_rgba_round_trips_a_real_png() {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        let image = image::RgbaImage::from_pixel(3, 2, image::Rgba([10, 20, 30, 255]));
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut buf, ImageFormat::Png)
+            .unwrap();
+
+        let (width, height, rgba) = decode_rgba(buf.get_ref(), "image/png").unwrap();
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(rgba.len(), 3 * 2 * 4);
+    }
+
+    #[test]
+    fn test_encode_kitty_single_chunk_sets_final_marker() {
+        let chunks = encode_kitty(&[0u8; 16], 2, 2, 7);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].starts_with("\x1b_Gf=32,s=2,v=2,i=7,a=T,m=0;"));
+        assert!(chunks[0].ends_with("\x1b\\"));
+    }
+
+    #[test]
+    fn test_encode_kitty_splits_large_payloads_into_continuation_chunks() {
+        let rgba = vec![0u8; 10_000];
+        let chunks = encode_kitty(&rgba, 50, 50, 1);
+        assert!(chunks.len() > 1);
+        assert!(chunks.first().unwrap().contains("m=1;"));
+        assert!(chunks.last().unwrap().starts_with("\x1b_Gm=0;"));
+    }
+
+    #[test]
+    fn test_placement_tracker_clears_only_once() {
+        let mut tracker = PlacementTracker::new();
+        let id = tracker.place();
+        assert_eq!(tracker.clear(), Some(clear_kitty_placement(id)));
+        assert_eq!(tracker.clear(), None);
+    }
+}
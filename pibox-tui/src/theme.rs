@@ -0,0 +1,224 @@
+//! Terminal background detection and the color palette it drives
+//!
+//! Queries the terminal's background color with the OSC 11 escape
+//! sequence and hands the reply to [`pibox_core::appearance`] to decide
+//! light or dark, then exposes one [`Palette`] so every `draw_*` function
+//! reads colors from a single place instead of sprinkling `Color::X`
+//! literals through the UI.
+
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use ratatui::style::Color;
+
+use pibox_core::appearance::{self, Appearance};
+
+/// Ask the terminal for its background color via `OSC 11` and parse the
+/// `rgb:RRRR/GGGG/BBBB` reply it sends back on stdin. Best-effort: many
+/// terminals (or a non-interactive stdout, e.g. under a test harness)
+/// never reply, in which case this returns `None` and the caller falls
+/// back to a default appearance.
+///
+/// Stdin is switched to non-blocking for the duration of the read: a
+/// blocking `read()` on a silent terminal's fd never returns (no EOF, no
+/// bytes), so the timeout below would otherwise never get a chance to
+/// fire. The original flags are restored before returning either way.
+pub fn query_background() -> Option<(u8, u8, u8)> {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(b"\x1b]11;?\x07").ok()?;
+    stdout.flush().ok()?;
+
+    let stdin = std::io::stdin();
+    let fd = stdin.as_raw_fd();
+    let orig_flags = OFlag::from_bits_truncate(fcntl(fd, FcntlArg::F_GETFL).ok()?);
+    fcntl(fd, FcntlArg::F_SETFL(orig_flags | OFlag::O_NONBLOCK)).ok()?;
+
+    let result = read_osc11_reply(&mut stdin.lock(), Duration::from_millis(200));
+
+    let _ = fcntl(fd, FcntlArg::F_SETFL(orig_flags));
+
+    result
+}
+
+fn read_osc11_reply(input: &mut impl Read, timeout: Duration) -> Option<(u8, u8, u8)> {
+    // Raw mode delivers the reply as plain bytes on stdin rather than a
+    // crossterm event, so this reads directly rather than going through
+    // the event queue. With stdin non-blocking, a silent terminal makes
+    // `read` return `WouldBlock` immediately instead of hanging, so the
+    // deadline check between reads actually gets to run.
+    let deadline = std::time::Instant::now() + timeout;
+    let mut buf = Vec::new();
+    let mut byte = [0u8; 1];
+
+    while std::time::Instant::now() < deadline {
+        match input.read(&mut byte) {
+            Ok(1) => {
+                buf.push(byte[0]);
+                if byte[0] == b'\\' || byte[0] == 0x07 {
+                    break;
+                }
+            }
+            Ok(0) => break,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            _ => break,
+        }
+    }
+
+    parse_osc11_reply(&buf)
+}
+
+fn parse_osc11_reply(reply: &[u8]) -> Option<(u8, u8, u8)> {
+    let text = std::str::from_utf8(reply).ok()?;
+    let rgb = text.split("rgb:").nth(1)?;
+    let mut channels = rgb.splitn(3, '/');
+
+    let parse_channel = |s: &str| -> Option<u8> {
+        let hex = &s[..s.len().min(2)];
+        u8::from_str_radix(hex, 16).ok()
+    };
+
+    let r = parse_channel(channels.next()?)?;
+    let g = parse_channel(channels.next()?)?;
+    let b = parse_channel(channels.next()?.trim_end_matches(['\x07', '\x1b', '\\']))?;
+    Some((r, g, b))
+}
+
+/// Detect the terminal's appearance, falling back to dark when the
+/// terminal doesn't answer the OSC 11 query
+pub fn detect() -> Appearance {
+    match query_background() {
+        Some((r, g, b)) => appearance::appearance_from_rgb(r, g, b),
+        None => Appearance::Dark,
+    }
+}
+
+/// Every color the TUI draws with, grouped so picking a theme is one
+/// struct swap instead of touching each `draw_*` function
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub title_fg: Color,
+    pub title_bg: Color,
+    pub connected: Color,
+    pub offline: Color,
+    pub cursor_bg: Color,
+    pub cursor_fg: Color,
+    pub selected_bg: Color,
+    pub selected_fg: Color,
+    pub muted: Color,
+    pub info: Color,
+    pub success: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub highlight: Color,
+}
+
+impl Palette {
+    pub fn for_appearance(appearance: Appearance) -> Self {
+        match appearance {
+            Appearance::Dark => Self::dark(),
+            Appearance::Light => Self::light(),
+        }
+    }
+
+    fn dark() -> Self {
+        Self {
+            title_fg: Color::White,
+            title_bg: Color::DarkGray,
+            connected: Color::Green,
+            offline: Color::Yellow,
+            cursor_bg: Color::Blue,
+            cursor_fg: Color::White,
+            selected_bg: Color::DarkGray,
+            selected_fg: Color::Yellow,
+            muted: Color::DarkGray,
+            info: Color::Blue,
+            success: Color::Green,
+            warning: Color::Yellow,
+            error: Color::Red,
+            highlight: Color::Cyan,
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            title_fg: Color::Black,
+            title_bg: Color::Gray,
+            connected: Color::Green,
+            offline: Color::Rgb(150, 110, 0),
+            cursor_bg: Color::Cyan,
+            cursor_fg: Color::Black,
+            selected_bg: Color::Gray,
+            selected_fg: Color::Rgb(120, 90, 0),
+            muted: Color::Gray,
+            info: Color::Blue,
+            success: Color::Rgb(0, 120, 0),
+            warning: Color::Rgb(150, 110, 0),
+            error: Color::Rgb(180, 0, 0),
+            highlight: Color::Rgb(0, 110, 130),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_standard_osc11_reply() {
+        let reply = b"\x1b]11;rgb:1a1a/2b2b/3c3c\x1b\\";
+        assert_eq!(parse_osc11_reply(reply), Some((0x1a, 0x2b, 0x3c)));
+    }
+
+    #[test]
+    fn test_parses_bell_terminated_reply() {
+        let reply = b"\x1b]11;rgb:ffff/ffff/ffff\x07";
+        assert_eq!(parse_osc11_reply(reply), Some((0xff, 0xff, 0xff)));
+    }
+
+    #[test]
+    fn test_rejects_reply_without_rgb_payload() {
+        assert_eq!(parse_osc11_reply(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_read_osc11_reply_returns_none_on_empty_input() {
+        let mut empty: &[u8] = b"";
+        assert_eq!(read_osc11_reply(&mut empty, Duration::from_millis(10)), None);
+    }
+
+    /// A non-blocking fd with no data available yet reports `WouldBlock`
+    /// on every read rather than EOF-ing -- unlike an exhausted in-memory
+    /// slice (`Ok(0)`), it never signals "done" on its own. This models a
+    /// silent terminal's stdin once `query_background` has made it
+    /// non-blocking: the read loop keeps retrying on `WouldBlock` and gives
+    /// up only when its deadline passes.
+    struct NeverReady;
+
+    impl Read for NeverReady {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::from(std::io::ErrorKind::WouldBlock))
+        }
+    }
+
+    #[test]
+    fn test_read_osc11_reply_times_out_on_terminal_that_never_replies() {
+        let mut never = NeverReady;
+        assert_eq!(read_osc11_reply(&mut never, Duration::from_millis(10)), None);
+    }
+
+    #[test]
+    fn test_for_appearance_selects_matching_palette() {
+        assert_eq!(Palette::for_appearance(Appearance::Dark).title_fg, Color::White);
+        assert_eq!(Palette::for_appearance(Appearance::Light).title_fg, Color::Black);
+    }
+}
@@ -2,32 +2,97 @@
 
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, Paragraph},
     Frame,
 };
 
-use pibox_core::state::{FileType, InputMode, StatusLevel};
+use pibox_core::preview::Preview;
+use pibox_core::state::{BookmarkAction, FileType, InputMode, StatusLevel};
 
 use crate::app::App;
 
+/// Maximum number of lines shown for a text preview, so a huge file doesn't
+/// blow up rendering time every frame regardless of how few fit on screen
+const PREVIEW_TEXT_MAX_LINES: usize = 500;
+
 /// Main draw function
 pub fn draw(f: &mut Frame, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(1), // Title bar
-            Constraint::Min(1),    // File list
+            Constraint::Min(1),    // File list (+ parent/preview panes)
             Constraint::Length(1), // Status bar
             Constraint::Length(1), // Input line (for search/command)
         ])
         .split(f.area());
 
     draw_title_bar(f, app, chunks[0]);
-    draw_file_list(f, app, chunks[1]);
+
+    // A running/finished command takes over the preview slot even if the
+    // preview pane is currently toggled off -- it was explicitly requested
+    if app.state.show_preview || app.command_output.is_some() {
+        let [parent_pct, list_pct, preview_pct] = app.config.client.tui.preview_ratios;
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(parent_pct),
+                Constraint::Percentage(list_pct),
+                Constraint::Percentage(preview_pct),
+            ])
+            .split(chunks[1]);
+        draw_parent_pane(f, app, panes[0]);
+        draw_file_list(f, app, panes[1]);
+        match &app.command_output {
+            Some(output) => draw_command_output(f, app, output, panes[2]),
+            None => draw_preview_pane(f, app, panes[2]),
+        }
+    } else {
+        draw_file_list(f, app, chunks[1]);
+    }
+
     draw_status_bar(f, app, chunks[2]);
     draw_input_line(f, app, chunks[3]);
+
+    if matches!(app.state.input_mode, InputMode::Bookmark(_)) {
+        draw_bookmarks(f, app, chunks[1]);
+    }
+}
+
+/// Draw the parent-directory column of the Miller-column layout: the
+/// current directory's siblings, so the user can see where they came from
+/// without navigating back up
+fn draw_parent_pane(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::RIGHT);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if app.state.parent_entries.is_empty() {
+        f.render_widget(
+            Paragraph::new("(no parent listing)").style(Style::default().fg(app.palette.muted)),
+            inner,
+        );
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .state
+        .parent_entries
+        .iter()
+        .map(|entry| {
+            let is_current = entry.path == app.state.current_path;
+            let style = if is_current {
+                Style::default().fg(app.palette.highlight).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(app.palette.muted)
+            };
+            ListItem::new(Line::from(Span::styled(entry.name.clone(), style)))
+        })
+        .collect();
+
+    f.render_widget(List::new(items), inner);
 }
 
 /// Draw the title bar with current path
@@ -36,13 +101,13 @@ fn draw_title_bar(f: &mut Frame, app: &App, area: Rect) {
     let connected_indicator = if app.connected { " [Connected]" } else { " [Offline]" };
 
     let title_bar = Paragraph::new(Line::from(vec![
-        Span::styled(title, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+        Span::styled(title, Style::default().fg(app.palette.title_fg).add_modifier(Modifier::BOLD)),
         Span::styled(
             connected_indicator,
-            Style::default().fg(if app.connected { Color::Green } else { Color::Yellow }),
+            Style::default().fg(if app.connected { app.palette.connected } else { app.palette.offline }),
         ),
     ]))
-    .style(Style::default().bg(Color::DarkGray));
+    .style(Style::default().bg(app.palette.title_bg));
 
     f.render_widget(title_bar, area);
 }
@@ -92,13 +157,13 @@ fn draw_file_list(f: &mut Frame, app: &App, area: Rect) {
 
             let style = if is_cursor {
                 Style::default()
-                    .bg(Color::Blue)
-                    .fg(Color::White)
+                    .bg(app.palette.cursor_bg)
+                    .fg(app.palette.cursor_fg)
                     .add_modifier(Modifier::BOLD)
             } else if is_selected {
                 Style::default()
-                    .bg(Color::DarkGray)
-                    .fg(Color::Yellow)
+                    .bg(app.palette.selected_bg)
+                    .fg(app.palette.selected_fg)
             } else {
                 Style::default()
             };
@@ -115,26 +180,116 @@ fn draw_file_list(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(list, area);
 }
 
+/// Draw the preview pane for the focused entry: highlighted text, a child
+/// listing, or summary info, depending on what `AppState::preview` holds
+fn draw_preview_pane(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::LEFT);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let Some(loaded) = &app.state.preview else {
+        f.render_widget(Paragraph::new("Loading...").style(Style::default().fg(app.palette.muted)), inner);
+        return;
+    };
+
+    match &loaded.preview {
+        Preview::Text { highlighted_lines } => {
+            // Reconstruct the raw text from the core scanner's spans (lossless,
+            // since they partition each line in full) and re-highlight it with
+            // syntect, which knows real grammars instead of a handful of keywords
+            let content = highlighted_lines
+                .iter()
+                .map(|spans| spans.iter().map(|span| span.text.as_str()).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let extension = loaded.path.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+
+            let lines = app.highlighted_text_lines(
+                &loaded.path,
+                loaded.modified,
+                &content,
+                extension,
+                PREVIEW_TEXT_MAX_LINES,
+            );
+            f.render_widget(Paragraph::new(lines), inner);
+        }
+        Preview::Directory { entries } => {
+            let items: Vec<ListItem> = entries
+                .iter()
+                .map(|entry| ListItem::new(Line::from(entry.name.clone())))
+                .collect();
+            if items.is_empty() {
+                f.render_widget(
+                    Paragraph::new("(empty)").style(Style::default().fg(app.palette.muted)),
+                    inner,
+                );
+            } else {
+                f.render_widget(List::new(items), inner);
+            }
+        }
+        Preview::Binary { info } => {
+            f.render_widget(Paragraph::new(info.clone()).style(Style::default().fg(app.palette.muted)), inner);
+        }
+    }
+}
+
+/// Draw a `:`/`!` command's captured output in place of the preview pane:
+/// a tail of however many lines fit (the closest thing to "scrollable"
+/// without dedicated scroll-offset state, same tradeoff the preview pane's
+/// `PREVIEW_TEXT_MAX_LINES` cap makes), stderr lines colored to stand out
+fn draw_command_output(f: &mut Frame, app: &App, output: &crate::exec::CommandOutput, area: Rect) {
+    let status = if output.running {
+        "running…".to_string()
+    } else {
+        match output.exit_code {
+            Some(code) => format!("exit {}", code),
+            None => "failed to run".to_string(),
+        }
+    };
+
+    let block = Block::default()
+        .borders(Borders::LEFT)
+        .title(format!(" {} [{}] ", output.command, status));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let visible = inner.height as usize;
+    let start = output.lines.len().saturating_sub(visible);
+
+    let lines: Vec<Line> = output.lines[start..]
+        .iter()
+        .map(|line| match line {
+            crate::exec::OutputLine::Stdout(text) => Line::from(text.clone()),
+            crate::exec::OutputLine::Stderr(text) => {
+                Line::styled(text.clone(), Style::default().fg(app.palette.error))
+            }
+        })
+        .collect();
+
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
 /// Draw the status bar
 fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     let (text, style) = if let Some((ref msg, ref level)) = app.state.status_message {
         let color = match level {
-            StatusLevel::Info => Color::Blue,
-            StatusLevel::Success => Color::Green,
-            StatusLevel::Warning => Color::Yellow,
-            StatusLevel::Error => Color::Red,
+            StatusLevel::Info => app.palette.info,
+            StatusLevel::Success => app.palette.success,
+            StatusLevel::Warning => app.palette.warning,
+            StatusLevel::Error => app.palette.error,
         };
         (msg.clone(), Style::default().fg(color))
     } else {
         // Default hints based on mode
         let hints = match app.state.input_mode {
-            InputMode::Normal => "j↓ k↑ l→ h← │ Space:select │ d:delete y:copy p:paste │ /:search ?:help q:quit",
+            InputMode::Normal => "j↓ k↑ l→ h← │ Space:select │ d:delete y:copy p:paste │ Tab:preview /:search ?:help q:quit",
             InputMode::Search => "Type to search │ Enter:confirm │ Esc:cancel",
             InputMode::Command => "Type command │ Enter:execute │ Esc:cancel",
             InputMode::Rename => "Enter new name │ Enter:confirm │ Esc:cancel",
             InputMode::Confirm(_) => "y:yes n:no │ Enter:confirm │ Esc:cancel",
+            InputMode::Bookmark(_) => "Type a key to name the bookmark │ Esc:cancel",
         };
-        (hints.to_string(), Style::default().fg(Color::DarkGray))
+        (hints.to_string(), Style::default().fg(app.palette.muted))
     };
 
     let status_bar = Paragraph::new(text).style(style);
@@ -151,7 +306,7 @@ fn draw_input_line(f: &mut Frame, app: &App, area: Rect) {
     };
 
     let input_line = Paragraph::new(format!("{}{}", prefix, content))
-        .style(Style::default().fg(Color::White));
+        .style(Style::default().fg(app.palette.title_fg));
 
     f.render_widget(input_line, area);
 
@@ -165,6 +320,57 @@ fn draw_input_line(f: &mut Frame, app: &App, area: Rect) {
     }
 }
 
+/// Draw the bookmark quick-jump overlay: a bordered list of saved bookmarks,
+/// centered over the file list, shown while waiting for the key that names
+/// the slot to set or jump to
+fn draw_bookmarks(f: &mut Frame, app: &App, area: Rect) {
+    let title = match app.state.input_mode {
+        InputMode::Bookmark(BookmarkAction::Set) => " Set bookmark (press a key) ",
+        InputMode::Bookmark(BookmarkAction::Jump) => " Jump to bookmark (press a key) ",
+        _ => " Bookmarks ",
+    };
+
+    let popup = centered_rect(60, 50, area);
+    let entries = app.bookmarks.entries();
+
+    let items: Vec<ListItem> = if entries.is_empty() {
+        vec![ListItem::new("(no bookmarks set)")]
+    } else {
+        entries
+            .iter()
+            .map(|(key, path)| ListItem::new(format!("{}  {}", key, path)))
+            .collect()
+    };
+
+    f.render_widget(ratatui::widgets::Clear, popup);
+    f.render_widget(
+        List::new(items).block(Block::default().title(title).borders(Borders::ALL)),
+        popup,
+    );
+}
+
+/// Carve a centered `Rect` covering `percent_x`/`percent_y` of `area`, used
+/// to position modal overlays like the bookmark popup
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
 /// Format file size in human-readable form
 fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
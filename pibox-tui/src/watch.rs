@@ -0,0 +1,108 @@
+//! Live directory refresh via a background filesystem watcher
+//!
+//! The entry list was otherwise static until an explicit reload. This
+//! watches the directory currently being viewed with `notify` (the same
+//! crate `pibox-server::watcher` uses to broadcast external changes over
+//! the wire) and surfaces a coalesced "something changed" signal, polled
+//! in the main loop alongside crossterm events the same way
+//! `script::ScriptPipes` feeds parsed commands.
+
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Coalescing window: a burst of events (e.g. a large `cp` into the
+/// directory) within this interval collapses into a single refresh
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Watches one directory non-recursively, delivering a coalesced "reload
+/// the listing" signal rather than raw per-file events -- a refresh always
+/// re-reads the whole directory, so there's nothing to gain from carrying
+/// per-event detail across the channel.
+pub struct DirWatcher {
+    path: String,
+    rx: mpsc::Receiver<()>,
+    _watcher: RecommendedWatcher,
+    pending_since: Option<Instant>,
+}
+
+impl DirWatcher {
+    /// Start watching `path`. Only direct children are watched
+    /// (`NonRecursive`); a change two levels down doesn't affect what's
+    /// shown for this directory.
+    pub fn watch(path: &str) -> notify::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if res.is_ok() {
+                let _ = tx.send(());
+            }
+        })?;
+        watcher.watch(Path::new(path), RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            rx,
+            _watcher: watcher,
+            pending_since: None,
+        })
+    }
+
+    /// The directory this watcher is watching
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Drain any raw events that have arrived and report whether the
+    /// debounce window since the first of the current burst has elapsed --
+    /// i.e. whether the caller should reload the listing now
+    pub fn poll(&mut self) -> bool {
+        while self.rx.try_recv().is_ok() {
+            self.pending_since.get_or_insert_with(Instant::now);
+        }
+
+        match self.pending_since {
+            Some(since) if since.elapsed() >= DEBOUNCE => {
+                self.pending_since = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_reports_change_after_debounce() {
+        let dir = std::env::temp_dir().join(format!("pibox-watch-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut watcher = DirWatcher::watch(dir.to_str().unwrap()).unwrap();
+        assert_eq!(watcher.path(), dir.to_str().unwrap());
+        assert!(!watcher.poll());
+
+        std::fs::write(dir.join("new_file.txt"), b"hello").unwrap();
+
+        // The underlying OS notification and the debounce window both take
+        // some real wall-clock time; poll in a loop rather than a single
+        // fixed sleep.
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut changed = false;
+        while Instant::now() < deadline {
+            if watcher.poll() {
+                changed = true;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        assert!(changed, "expected a reload signal after creating a file");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}